@@ -0,0 +1,305 @@
+use std::path::{Path, PathBuf};
+
+use glam::{ivec3, IVec3};
+
+use crate::{
+    export::{export_region, ExportFormat},
+    state::{
+        clock::WorldClock,
+        save::{backup::BackupStore, WorldSave, LEVEL_FILE},
+        voxel::{chunk::chunk_pos, terrain::Terrain},
+        world::{WORLD_DIR, WORLD_SEED},
+    },
+};
+
+const USAGE: &str = "\
+Usage:
+    minerust
+    minerust export <output.obj|output.glb> --from <x,y,z> --to <x,y,z> [--merge] [--seed <n> | --world <dir>]
+    minerust backup [--world <dir>]
+    minerust backups [--world <dir>]
+    minerust restore <snapshot> [--region <x,y,z>] [--world <dir>]
+    minerust time [--set <fraction>] [--day-length <seconds>] [--world <dir>]
+
+The world must not be open in the game while it is restored or its time is changed. Times
+of day are fractions of a day since sunrise: 0.25 is noon and 0.75 is midnight.";
+
+/// Runs the subcommand given on the command line without opening a window.
+///
+/// Returns `None` when no subcommand was given and the game should start normally,
+/// otherwise the process exit code.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+
+    let result = match command.as_str() {
+        "export" => export(args),
+        "backup" => backup(args),
+        "backups" => list_backups(args),
+        "restore" => restore(args),
+        "time" => time(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Unknown command: {}", command)),
+    };
+
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            Some(1)
+        }
+    }
+}
+
+fn export(args: &[String]) -> Result<(), String> {
+    let mut output = None;
+    let mut from = None;
+    let mut to = None;
+    let mut merge_faces = false;
+    let mut seed = None;
+    let mut world = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(parse_ivec3(next_value(&mut args, arg)?)?),
+            "--to" => to = Some(parse_ivec3(next_value(&mut args, arg)?)?),
+            "--merge" => merge_faces = true,
+            "--seed" => {
+                let value = next_value(&mut args, arg)?;
+                seed = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid seed: {}", value))?,
+                );
+            }
+            "--world" => world = Some(PathBuf::from(next_value(&mut args, arg)?)),
+            _ if output.is_none() && !arg.starts_with("--") => output = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    let output = output.ok_or("Missing output path.")?;
+    let from = from.ok_or("Missing --from.")?;
+    let to = to.ok_or("Missing --to.")?;
+    let format =
+        ExportFormat::from_path(&output).ok_or("Output must have an .obj or .glb extension.")?;
+
+    let mut terrain = match world {
+        Some(dir) => {
+            check_world(&dir)?;
+            let save = WorldSave::open(&dir)
+                .map_err(|e| format!("Couldn't open {}: {}", dir.display(), e))?;
+            let metadata = save.load_metadata();
+            let mut terrain = Terrain::new(seed.or(metadata.map(|m| m.seed)).unwrap_or(WORLD_SEED));
+            save.load_area(
+                &mut terrain,
+                chunk_pos(from.min(to)),
+                chunk_pos(from.max(to)),
+            );
+            terrain
+        }
+        None => Terrain::new(seed.unwrap_or(WORLD_SEED)),
+    };
+
+    export_region(&mut terrain, from, to, merge_faces, format, &output)
+        .map_err(|e| format!("Couldn't export to {}: {}", output.display(), e))?;
+
+    println!(
+        "Exported {} to {}",
+        region_string(from, to),
+        output.display()
+    );
+
+    Ok(())
+}
+
+fn backup(args: &[String]) -> Result<(), String> {
+    let world = world_arg(args)?;
+    let stats = BackupStore::for_world(&world)
+        .create(&world)
+        .map_err(|e| format!("Couldn't back up {}: {}", world.display(), e))?;
+
+    println!(
+        "Created backup {} ({} files copied, {} unchanged)",
+        stats.snapshot.id, stats.copied, stats.linked
+    );
+
+    Ok(())
+}
+
+fn list_backups(args: &[String]) -> Result<(), String> {
+    let world = world_dir(args)?;
+    let store = BackupStore::for_world(&world);
+    let snapshots = store
+        .snapshots()
+        .map_err(|e| format!("Couldn't list backups in {}: {}", store.dir().display(), e))?;
+
+    if snapshots.is_empty() {
+        println!("No backups of {}", world.display());
+    }
+    for snapshot in snapshots {
+        println!("{}", snapshot.id);
+    }
+
+    Ok(())
+}
+
+fn restore(args: &[String]) -> Result<(), String> {
+    let mut id = None;
+    let mut region = None;
+    let mut rest = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--region" => region = Some(parse_ivec3(next_value(&mut iter, arg)?)?),
+            _ if id.is_none() && !arg.starts_with("--") => id = Some(arg.as_str()),
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let id = id.ok_or("Missing snapshot name.")?;
+    let world = world_arg(&rest)?;
+    let store = BackupStore::for_world(&world);
+    let snapshot = store.snapshot(id).map_err(|e| e.to_string())?;
+
+    // Keep the current state around, so a restore can be undone by restoring this snapshot.
+    let current = store
+        .create(&world)
+        .map_err(|e| format!("Couldn't back up {}: {}", world.display(), e))?;
+    println!("Backed up the current world as {}", current.snapshot.id);
+
+    match region {
+        Some(region) => {
+            store
+                .restore_region(&snapshot, &world, region)
+                .map_err(|e| format!("Couldn't restore the region: {}", e))?;
+            println!(
+                "Restored region ({}, {}, {}) from {}",
+                region.x, region.y, region.z, snapshot.id
+            );
+        }
+        None => {
+            store
+                .restore_world(&snapshot, &world)
+                .map_err(|e| format!("Couldn't restore the world: {}", e))?;
+            println!("Restored {} from {}", world.display(), snapshot.id);
+        }
+    }
+
+    Ok(())
+}
+
+fn time(args: &[String]) -> Result<(), String> {
+    let mut set = None;
+    let mut day_length = None;
+    let mut rest = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--set" => set = Some(parse_f32(next_value(&mut iter, arg)?)?),
+            "--day-length" => {
+                let value = parse_f32(next_value(&mut iter, arg)?)?;
+                if value <= 0. {
+                    return Err(format!("Invalid day length: {}", value));
+                }
+                day_length = Some(value);
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let world = world_arg(&rest)?;
+    let save =
+        WorldSave::open(&world).map_err(|e| format!("Couldn't open {}: {}", world.display(), e))?;
+    let mut metadata = save
+        .load_metadata()
+        .ok_or_else(|| format!("Couldn't read the level of {}", world.display()))?;
+
+    if set.is_some() || day_length.is_some() {
+        metadata.clock = WorldClock::new(
+            set.unwrap_or(metadata.clock.time),
+            day_length.unwrap_or(metadata.clock.day_length),
+        );
+        save.save_metadata(&metadata)
+            .map_err(|e| format!("Couldn't save the level: {}", e))?;
+    }
+
+    println!(
+        "Time of day {:.3}, days last {} seconds",
+        metadata.clock.time, metadata.clock.day_length
+    );
+
+    Ok(())
+}
+
+/// Parses an optional `--world <dir>`, defaulting to the world the game plays in.
+fn world_dir(args: &[String]) -> Result<PathBuf, String> {
+    let mut world = PathBuf::from(WORLD_DIR);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--world" => world = PathBuf::from(next_value(&mut args, arg)?),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    Ok(world)
+}
+
+/// Like `world_dir`, but the world must exist.
+fn world_arg(args: &[String]) -> Result<PathBuf, String> {
+    let world = world_dir(args)?;
+    check_world(&world)?;
+    Ok(world)
+}
+
+fn check_world(dir: &Path) -> Result<(), String> {
+    if dir.join(LEVEL_FILE).exists() {
+        Ok(())
+    } else {
+        Err(format!("No world found at {}", dir.display()))
+    }
+}
+
+fn next_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<&'a str, String> {
+    args.next()
+        .map(String::as_str)
+        .ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+fn parse_f32(value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|v: &f32| v.is_finite())
+        .ok_or_else(|| format!("Invalid number: {}", value))
+}
+
+fn parse_ivec3(value: &str) -> Result<IVec3, String> {
+    let parts: Vec<i32> = value
+        .split(',')
+        .map(|p| p.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid position: {}", value))?;
+
+    match parts[..] {
+        [x, y, z] => Ok(ivec3(x, y, z)),
+        _ => Err(format!("Invalid position: {}", value)),
+    }
+}
+
+fn region_string(from: IVec3, to: IVec3) -> String {
+    format!(
+        "({}, {}, {})..=({}, {}, {})",
+        from.x, from.y, from.z, to.x, to.y, to.z
+    )
+}
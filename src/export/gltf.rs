@@ -0,0 +1,236 @@
+use std::{fs, io, path::Path};
+
+use glam::Vec3;
+
+use crate::{render::mesh::Mesh, state::voxel::block::Block};
+
+use super::texture_path;
+
+const GLB_MAGIC: u32 = 0x4654_6c67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4e4f_534a;
+const CHUNK_BIN: u32 = 0x004e_4942;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const WRAP_REPEAT: u32 = 10497;
+
+/// Writes the meshes as a self-contained binary glTF 2.0 file.
+///
+/// Every block becomes one primitive with its own material, and the block textures are
+/// embedded into the binary chunk.
+pub fn write(meshes: &[(Block, Mesh)], path: &Path) -> io::Result<()> {
+    let mut builder = GlbBuilder::default();
+
+    let mut primitives = Vec::new();
+    let mut materials = Vec::new();
+    let mut textures = Vec::new();
+    let mut images = Vec::new();
+
+    for (index, (block, mesh)) in meshes.iter().enumerate() {
+        let vertices = mesh.vertices();
+
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(v.position()), max.max(v.position())),
+        );
+
+        let positions = builder.push_view(
+            vertices.iter().flat_map(|v| v.position().to_array()),
+            ARRAY_BUFFER,
+        );
+        let positions = builder.push_accessor(
+            positions,
+            vertices.len(),
+            "VEC3",
+            COMPONENT_FLOAT,
+            Some((min, max)),
+        );
+
+        let normals = builder.push_view(
+            vertices.iter().flat_map(|v| v.normal().to_array()),
+            ARRAY_BUFFER,
+        );
+        let normals = builder.push_accessor(normals, vertices.len(), "VEC3", COMPONENT_FLOAT, None);
+
+        // glTF puts the texture origin in the top left corner, OpenGL in the bottom left.
+        let tex_coords = builder.push_view(
+            vertices.iter().flat_map(|v| {
+                let t = v.tex_coords();
+                [t.x, 1. - t.y]
+            }),
+            ARRAY_BUFFER,
+        );
+        let tex_coords =
+            builder.push_accessor(tex_coords, vertices.len(), "VEC2", COMPONENT_FLOAT, None);
+
+        let index_count = vertices.len() / 4 * 6;
+        let indices = (0..vertices.len() / 4).flat_map(|q| {
+            let q = (q * 4) as u32;
+            [q, q + 1, q + 2, q + 2, q + 3, q]
+        });
+        let indices = builder.push_index_view(indices);
+        let indices =
+            builder.push_accessor(indices, index_count, "SCALAR", COMPONENT_UNSIGNED_INT, None);
+
+        let image = fs::read(texture_path(*block))?;
+        let image = builder.push_bytes(&image, None);
+
+        primitives.push(format!(
+            concat!(
+                r#"{{"attributes":{{"POSITION":{},"NORMAL":{},"TEXCOORD_0":{}}},"#,
+                r#""indices":{},"material":{}}}"#,
+            ),
+            positions, normals, tex_coords, indices, index
+        ));
+        materials.push(format!(
+            concat!(
+                r#"{{"name":"{}","pbrMetallicRoughness":{{"baseColorTexture":{{"index":{}}},"#,
+                r#""metallicFactor":0,"roughnessFactor":1}}}}"#,
+            ),
+            block.name(),
+            index
+        ));
+        textures.push(format!(r#"{{"sampler":0,"source":{}}}"#, index));
+        images.push(format!(
+            r#"{{"bufferView":{},"mimeType":"{}"}}"#,
+            image,
+            mime_type(texture_path(*block))
+        ));
+    }
+
+    let mut fields = vec![
+        r#""asset":{"version":"2.0","generator":"minerust"}"#.to_string(),
+        r#""scene":0,"scenes":[{"nodes":[0]}]"#.to_string(),
+    ];
+
+    // A mesh needs at least one primitive, so an empty region is exported as an empty node.
+    if primitives.is_empty() {
+        fields.push(r#""nodes":[{"name":"region"}]"#.to_string());
+    } else {
+        fields.push(r#""nodes":[{"name":"region","mesh":0}]"#.to_string());
+        fields.push(format!(
+            r#""meshes":[{{"name":"region","primitives":[{}]}}]"#,
+            primitives.join(",")
+        ));
+        fields.push(format!(r#""materials":[{}]"#, materials.join(",")));
+        fields.push(format!(r#""textures":[{}]"#, textures.join(",")));
+        fields.push(format!(r#""images":[{}]"#, images.join(",")));
+        fields.push(format!(
+            r#""samplers":[{{"wrapS":{},"wrapT":{}}}]"#,
+            WRAP_REPEAT, WRAP_REPEAT
+        ));
+        fields.push(format!(r#""accessors":[{}]"#, builder.accessors.join(",")));
+        fields.push(format!(r#""bufferViews":[{}]"#, builder.views.join(",")));
+        fields.push(format!(
+            r#""buffers":[{{"byteLength":{}}}]"#,
+            builder.padded_len()
+        ));
+    }
+
+    let json = format!("{{{}}}", fields.join(","));
+    fs::write(path, builder.into_glb(json))
+}
+
+fn mime_type(path: &str) -> &'static str {
+    if path.ends_with(".jpg") || path.ends_with(".jpeg") {
+        "image/jpeg"
+    } else {
+        "image/png"
+    }
+}
+
+#[derive(Default)]
+struct GlbBuilder {
+    bin: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl GlbBuilder {
+    fn push_view(&mut self, data: impl Iterator<Item = f32>, target: u32) -> usize {
+        let bytes: Vec<u8> = data.flat_map(f32::to_le_bytes).collect();
+        self.push_bytes(&bytes, Some(target))
+    }
+
+    fn push_index_view(&mut self, data: impl Iterator<Item = u32>) -> usize {
+        let bytes: Vec<u8> = data.flat_map(u32::to_le_bytes).collect();
+        self.push_bytes(&bytes, Some(ELEMENT_ARRAY_BUFFER))
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        pad(&mut self.bin, 0);
+        let offset = self.bin.len();
+        self.bin.extend_from_slice(bytes);
+
+        let target = target.map_or(String::new(), |t| format!(r#","target":{}"#, t));
+        self.views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{}{}}}"#,
+            offset,
+            bytes.len(),
+            target
+        ));
+        self.views.len() - 1
+    }
+
+    fn push_accessor(
+        &mut self,
+        view: usize,
+        count: usize,
+        type_: &str,
+        component_type: u32,
+        bounds: Option<(Vec3, Vec3)>,
+    ) -> usize {
+        let bounds = bounds.map_or(String::new(), |(min, max)| {
+            format!(
+                r#","min":[{},{},{}],"max":[{},{},{}]"#,
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )
+        });
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"{}"{}}}"#,
+            view, component_type, count, type_, bounds
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn padded_len(&self) -> usize {
+        self.bin.len().div_ceil(4) * 4
+    }
+
+    fn into_glb(mut self, json: String) -> Vec<u8> {
+        let mut json = json.into_bytes();
+        pad(&mut json, b' ');
+        pad(&mut self.bin, 0);
+
+        let mut total = 12 + 8 + json.len();
+        if !self.bin.is_empty() {
+            total += 8 + self.bin.len();
+        }
+
+        let mut glb = Vec::with_capacity(total);
+        glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        glb.extend_from_slice(&GLB_VERSION.to_le_bytes());
+        glb.extend_from_slice(&(total as u32).to_le_bytes());
+
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(&json);
+
+        if !self.bin.is_empty() {
+            glb.extend_from_slice(&(self.bin.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+            glb.extend_from_slice(&self.bin);
+        }
+
+        glb
+    }
+}
+
+fn pad(bytes: &mut Vec<u8>, value: u8) {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(value);
+    }
+}
@@ -0,0 +1,151 @@
+use std::{io, path::Path};
+
+use glam::IVec3;
+
+use crate::{
+    render::{
+        mesh::Mesh,
+        mesher::{mesh_region, MesherOptions},
+    },
+    state::voxel::{block::Block, chunk::chunk_pos, terrain::Terrain},
+};
+
+pub mod gltf;
+pub mod obj;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Obj,
+    Glb,
+}
+
+impl ExportFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "obj" => Some(ExportFormat::Obj),
+            "glb" => Some(ExportFormat::Glb),
+            _ => None,
+        }
+    }
+}
+
+/// Meshes the inclusive block region `min..=max` and writes it to `path`.
+///
+/// Missing chunks are generated first, and the region is meshed as if it was surrounded by air
+/// so the exported model is closed.
+pub fn export_region(
+    terrain: &mut Terrain,
+    min: IVec3,
+    max: IVec3,
+    merge_faces: bool,
+    format: ExportFormat,
+    path: &Path,
+) -> io::Result<()> {
+    let (min, max) = (min.min(max), min.max(max));
+    terrain.generate_area(chunk_pos(min), chunk_pos(max));
+
+    let options = MesherOptions {
+        merge_faces,
+        closed: true,
+    };
+    let meshes = mesh_region(terrain, min, max + 1, options);
+
+    match format {
+        ExportFormat::Obj => obj::write(&meshes, path),
+        ExportFormat::Glb => gltf::write(&meshes, path),
+    }
+}
+
+fn texture_path(block: Block) -> &'static str {
+    block
+        .texture_path()
+        .expect("Meshed blocks always have a texture.")
+}
+
+fn quad_count(meshes: &[(Block, Mesh)]) -> usize {
+    meshes.iter().map(|(_, m)| m.vertices().len() / 4).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use glam::{ivec3, IVec3};
+
+    use crate::state::voxel::{block::Block, terrain::Terrain};
+
+    use super::{export_region, ExportFormat};
+
+    /// A single stone block high above the generated terrain, exported to a temporary file.
+    fn export_block(test: &str, format: ExportFormat) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("minerust-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(match format {
+            ExportFormat::Obj => "region.obj",
+            ExportFormat::Glb => "region.glb",
+        });
+
+        // Setting the block loads an empty chunk, so it isn't generated over.
+        let mut terrain = Terrain::new(1);
+        let pos = ivec3(3, 200, 5);
+        terrain.set_block(pos, Block::Stone);
+        export_region(&mut terrain, pos - 1, pos + IVec3::ONE, true, format, &path).unwrap();
+        path
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn single_block_exports_as_obj() {
+        let path = export_block("export-obj", ExportFormat::Obj);
+        let obj = fs::read_to_string(&path).unwrap();
+        let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+
+        assert_eq!(count("v "), 24);
+        assert_eq!(count("vt "), 24);
+        assert_eq!(count("vn "), 24);
+        assert_eq!(count("f "), 6);
+        assert_eq!(count("usemtl "), 1);
+        assert!(obj
+            .lines()
+            .any(|l| l == "f 21/21/21 22/22/22 23/23/23 24/24/24"));
+
+        let mtl = fs::read_to_string(path.with_extension("mtl")).unwrap();
+        assert_eq!(mtl.lines().filter(|l| l.starts_with("newmtl ")).count(), 1);
+    }
+
+    #[test]
+    fn single_block_exports_as_glb() {
+        let path = export_block("export-glb", ExportFormat::Glb);
+        let glb = fs::read(&path).unwrap();
+
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(u32_at(&glb, 4), 2);
+        assert_eq!(u32_at(&glb, 8) as usize, glb.len());
+
+        let json_len = u32_at(&glb, 12) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(json_len % 4, 0);
+        let json = std::str::from_utf8(&glb[20..20 + json_len]).unwrap();
+
+        let bin = 20 + json_len;
+        let bin_len = u32_at(&glb, bin) as usize;
+        assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+        assert_eq!(bin_len % 4, 0);
+        assert_eq!(bin + 8 + bin_len, glb.len());
+        assert!(json.contains(&format!(r#""buffers":[{{"byteLength":{}}}]"#, bin_len)));
+
+        // Positions, normals and texture coordinates of 24 vertices, then 36 indices.
+        assert!(json.contains(r#""count":24,"type":"VEC3""#));
+        assert!(json.contains(r#""count":24,"type":"VEC2""#));
+        assert!(json.contains(r#""count":36,"type":"SCALAR""#));
+        let texture = fs::metadata("assets/blocks/stone.png").unwrap().len() as usize;
+        assert_eq!(
+            bin_len,
+            (24 * 12 + 24 * 12 + 24 * 8 + 36 * 4 + texture).div_ceil(4) * 4
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{render::mesh::Mesh, state::voxel::block::Block};
+
+use super::{quad_count, texture_path};
+
+/// Writes the meshes as a Wavefront OBJ with a sibling MTL file, one material per block.
+///
+/// Materials reference the block textures in the assets directory by absolute path so the
+/// export can be imported from anywhere.
+pub fn write(meshes: &[(Block, Mesh)], path: &Path) -> io::Result<()> {
+    let mtl_path = path.with_extension("mtl");
+    let mtl_name = mtl_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("region.mtl");
+
+    write_mtl(meshes, &mtl_path)?;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(
+        out,
+        "# minerust region export, {} quads",
+        quad_count(meshes)
+    )?;
+    writeln!(out, "mtllib {}", mtl_name)?;
+    writeln!(out, "o region")?;
+
+    for (_, mesh) in meshes {
+        for vertex in mesh.vertices() {
+            let p = vertex.position();
+            writeln!(out, "v {} {} {}", p.x, p.y, p.z)?;
+        }
+    }
+    for (_, mesh) in meshes {
+        for vertex in mesh.vertices() {
+            let t = vertex.tex_coords();
+            writeln!(out, "vt {} {}", t.x, t.y)?;
+        }
+    }
+    for (_, mesh) in meshes {
+        for vertex in mesh.vertices() {
+            let n = vertex.normal();
+            writeln!(out, "vn {} {} {}", n.x, n.y, n.z)?;
+        }
+    }
+
+    // OBJ indices are 1-based and shared between v, vt and vn since they are written in lockstep.
+    let mut offset = 1;
+    for (block, mesh) in meshes {
+        writeln!(out, "usemtl {}", block.name())?;
+        for quad in 0..mesh.vertices().len() / 4 {
+            let i = offset + quad * 4;
+            writeln!(
+                out,
+                "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c} {d}/{d}/{d}",
+                a = i,
+                b = i + 1,
+                c = i + 2,
+                d = i + 3
+            )?;
+        }
+        offset += mesh.vertices().len();
+    }
+
+    out.flush()
+}
+
+fn write_mtl(meshes: &[(Block, Mesh)], path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    for (block, _) in meshes {
+        let texture = texture_path(*block);
+        let texture = fs::canonicalize(texture)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| texture.to_string());
+
+        writeln!(out, "newmtl {}", block.name())?;
+        writeln!(out, "Ka 1.0 1.0 1.0")?;
+        writeln!(out, "Kd 1.0 1.0 1.0")?;
+        writeln!(out, "Ks 0.0 0.0 0.0")?;
+        writeln!(out, "illum 1")?;
+        writeln!(out, "map_Kd {}", texture)?;
+        writeln!(out)?;
+    }
+
+    out.flush()
+}
@@ -1,3 +1,5 @@
+pub mod cli;
+pub mod export;
 pub mod render;
 pub mod state;
 
@@ -8,7 +10,7 @@ use sdl2::{
     video::GLProfile,
 };
 
-use crate::state::GameState;
+use crate::state::{physics::movement::PHYSICS_RATE, GameState};
 
 pub const WINDOW_WIDTH: u32 = 1280;
 pub const WINDOW_HEIGHT: u32 = 720;
@@ -47,6 +49,11 @@ fn init_sdl() -> (sdl2::Sdl, sdl2::video::Window, GameState) {
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let (sdl_context, window, mut game_state) = init_sdl();
 
     let timer = sdl_context.timer().unwrap();
@@ -56,7 +63,7 @@ fn main() {
     let mut t = 0;
     let mut acc: u64 = 0;
     let performance_freq = timer.performance_frequency();
-    let fixed_timestep: u64 = performance_freq / 40;
+    let fixed_timestep: u64 = performance_freq / PHYSICS_RATE;
 
     let mouse = sdl_context.mouse();
     mouse.show_cursor(false);
@@ -114,7 +121,7 @@ fn main() {
         t += delta;
         if t >= performance_freq {
             t -= performance_freq;
-            println!("FPS: {}", fps);
+            println!("FPS: {}, {}", fps, game_state.light_stats());
             fps = 0;
         }
         fps += 1;
@@ -122,8 +129,6 @@ fn main() {
         prev_ticks = ticks;
 
         while acc >= fixed_timestep {
-            // save prev state
-
             acc -= fixed_timestep;
 
             physics_update(&mut game_state);
@@ -139,6 +144,8 @@ fn main() {
 
         // std::thread::sleep(sleep_duration);
     }
+
+    game_state.save();
 }
 
 fn physics_update(game_state: &mut GameState) {
@@ -149,6 +156,6 @@ fn update(game_state: &mut GameState, delta: f32) {
     game_state.update(delta);
 }
 
-fn render(game_state: &mut GameState, _alpha: f32) {
-    game_state.draw();
+fn render(game_state: &mut GameState, alpha: f32) {
+    game_state.draw(alpha);
 }
@@ -0,0 +1,138 @@
+#version 330 core
+
+in vec3 tex_coords;
+
+out vec4 color;
+
+// Direction towards the sun, the moon is opposite.
+uniform vec3 sun_dir;
+uniform float stars;
+// Baking the sky map: linear colour without the sun and moon discs or the stars.
+uniform bool baking;
+
+#define PI 3.14159265
+#define PLANET_RADIUS 6371e3
+#define ATMOSPHERE_RADIUS 6471e3
+#define EYE_ALTITUDE 100.0
+#define RAYLEIGH_SCATTERING vec3(5.5e-6, 13.0e-6, 22.4e-6)
+#define RAYLEIGH_HEIGHT 8e3
+#define MIE_SCATTERING 21e-6
+// Aerosols absorb a little of the light they don't scatter.
+#define MIE_EXTINCTION (MIE_SCATTERING * 1.1)
+#define MIE_HEIGHT 1.2e3
+#define MIE_G 0.758
+#define SUN_INTENSITY 22.0
+#define MOON_INTENSITY 0.4
+#define VIEW_STEPS 16
+#define LIGHT_STEPS 8
+
+#define SUN_SIZE 0.9995
+#define MOON_SIZE 0.9997
+#define STAR_DENSITY 0.997
+#define STAR_GRID 200.0
+
+float hash(vec3 p) {
+    return fract(sin(dot(p, vec3(12.9898, 78.233, 37.719))) * 43758.5453);
+}
+
+// Distance from `origin` inside the atmosphere along `dir` to where the ray leaves it.
+float atmosphere_exit(vec3 origin, vec3 dir) {
+    float b = dot(origin, dir);
+    float c = dot(origin, origin) - ATMOSPHERE_RADIUS * ATMOSPHERE_RADIUS;
+    return -b + sqrt(max(b * b - c, 0.0));
+}
+
+bool hits_planet(vec3 origin, vec3 dir) {
+    float b = dot(origin, dir);
+    float c = dot(origin, origin) - PLANET_RADIUS * PLANET_RADIUS;
+    return b < 0.0 && b * b - c > 0.0;
+}
+
+// Rayleigh and Mie optical depth of a short step at `point`.
+vec2 density(vec3 point, float step_size) {
+    float height = length(point) - PLANET_RADIUS;
+    return exp(-height / vec2(RAYLEIGH_HEIGHT, MIE_HEIGHT)) * step_size;
+}
+
+vec3 extinction(vec2 optical_depth) {
+    return exp(-(RAYLEIGH_SCATTERING * optical_depth.x + MIE_EXTINCTION * optical_depth.y));
+}
+
+// Single scattering of the light coming from `light` towards the eye along `dir`.
+vec3 scattering(vec3 dir, vec3 light, float intensity) {
+    vec3 origin = vec3(0.0, PLANET_RADIUS + EYE_ALTITUDE, 0.0);
+    float step_size = atmosphere_exit(origin, dir) / float(VIEW_STEPS);
+
+    float mu = dot(dir, light);
+    float g2 = MIE_G * MIE_G;
+    float rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    float mie_phase = 3.0 / (8.0 * PI) * (1.0 - g2) * (1.0 + mu * mu) /
+        ((2.0 + g2) * pow(1.0 + g2 - 2.0 * MIE_G * mu, 1.5));
+
+    vec3 rayleigh = vec3(0.0);
+    vec3 mie = vec3(0.0);
+    vec2 view_depth = vec2(0.0);
+    for (int i = 0; i < VIEW_STEPS; i++) {
+        vec3 point = origin + dir * (float(i) + 0.5) * step_size;
+        vec2 step_density = density(point, step_size);
+        view_depth += step_density;
+
+        // In the planet's shadow.
+        if (hits_planet(point, light)) {
+            continue;
+        }
+
+        float light_step = atmosphere_exit(point, light) / float(LIGHT_STEPS);
+        vec2 light_depth = vec2(0.0);
+        for (int j = 0; j < LIGHT_STEPS; j++) {
+            light_depth += density(point + light * (float(j) + 0.5) * light_step, light_step);
+        }
+
+        vec3 transmittance = extinction(view_depth + light_depth);
+        rayleigh += step_density.x * transmittance;
+        mie += step_density.y * transmittance;
+    }
+
+    return intensity * (rayleigh_phase * RAYLEIGH_SCATTERING * rayleigh +
+        mie_phase * MIE_SCATTERING * mie);
+}
+
+void main() {
+    vec3 dir = normalize(tex_coords);
+    // The ground is out of sight anyway, below the horizon gets the horizon's colour.
+    vec3 view_dir = normalize(vec3(dir.x, max(dir.y, 0.0), dir.z));
+
+    vec3 lighting = vec3(0.0);
+    if (sun_dir.y > -0.3) {
+        lighting += scattering(view_dir, sun_dir, SUN_INTENSITY);
+    }
+    if (sun_dir.y < 0.3) {
+        lighting += scattering(view_dir, -sun_dir, MOON_INTENSITY);
+    }
+
+    if (baking) {
+        color = vec4(lighting, 1.0);
+        return;
+    }
+
+    float star = step(STAR_DENSITY, hash(floor(dir * STAR_GRID)));
+    lighting += vec3(star * stars * max(dir.y, 0.0));
+
+    // The sun's disc is seen through the atmosphere, so it reddens with the light it sends.
+    float sun = smoothstep(SUN_SIZE - 0.0002, SUN_SIZE, dot(dir, sun_dir));
+    vec3 origin = vec3(0.0, PLANET_RADIUS + EYE_ALTITUDE, 0.0);
+    float sun_depth = atmosphere_exit(origin, view_dir) / float(LIGHT_STEPS);
+    vec2 sun_optical_depth = vec2(0.0);
+    for (int i = 0; i < LIGHT_STEPS; i++) {
+        sun_optical_depth += density(origin + view_dir * (float(i) + 0.5) * sun_depth, sun_depth);
+    }
+    lighting += vec3(20.0) * extinction(sun_optical_depth) * sun * step(0.0, dir.y);
+    float moon = smoothstep(MOON_SIZE - 0.0001, MOON_SIZE, dot(dir, -sun_dir));
+    lighting += vec3(0.8, 0.85, 1.0) * moon;
+
+    float exposure = 1.0;
+    lighting = vec3(1.0) - exp(-lighting * exposure);
+    lighting = pow(lighting, vec3(1.0 / 2.2));
+
+    color = vec4(lighting, 1.0);
+}
@@ -0,0 +1,184 @@
+use std::time::{Duration, Instant};
+
+use glam::{Mat4, Vec3};
+use glow::*;
+
+use super::{model::Model, renderer::SkyState, shader::ShaderProgram};
+
+/// Width and height of each face of the baked sky map.
+const SKY_MAP_SIZE: i32 = 32;
+/// How often the sky map is baked again while it lights the scene. The sun moves less than a
+/// degree in this time with the default day length.
+const BAKE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// What's drawn behind the world and where the light from the sky comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SkyMode {
+    /// The day and night cube maps, the sky light is a single brightness.
+    #[default]
+    CubeMap,
+    /// Rayleigh and Mie scattering computed for every pixel of the sky.
+    Procedural,
+    /// The procedural sky, also baked into a cube map every few seconds and sampled along the
+    /// surface normal for coloured light from the sky.
+    ProceduralAmbient,
+}
+
+impl SkyMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::CubeMap => Self::Procedural,
+            Self::Procedural => Self::ProceduralAmbient,
+            Self::ProceduralAmbient => Self::CubeMap,
+        }
+    }
+
+    pub fn is_procedural(self) -> bool {
+        self != Self::CubeMap
+    }
+}
+
+/// The procedural sky shader and the cube map it's baked into.
+pub struct Atmosphere {
+    shader: ShaderProgram,
+    framebuffer: Framebuffer,
+    sky_map: Texture,
+    last_bake: Option<Instant>,
+}
+
+impl Atmosphere {
+    pub fn new(gl: &Context) -> Self {
+        let shader = ShaderProgram::new(
+            gl,
+            include_str!("skybox.vert"),
+            include_str!("atmosphere.frag"),
+        );
+
+        unsafe {
+            let framebuffer = gl
+                .create_framebuffer()
+                .expect("Couldn't create framebuffer.");
+
+            let sky_map = gl.create_texture().expect("Couldn't create texture.");
+            gl.bind_texture(TEXTURE_CUBE_MAP, Some(sky_map));
+            for face in 0..6 {
+                gl.tex_image_2d(
+                    TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    RGBA16F as i32,
+                    SKY_MAP_SIZE,
+                    SKY_MAP_SIZE,
+                    0,
+                    RGBA,
+                    FLOAT,
+                    None,
+                );
+            }
+            gl.tex_parameter_i32(TEXTURE_CUBE_MAP, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(TEXTURE_CUBE_MAP, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(TEXTURE_CUBE_MAP, TEXTURE_WRAP_R, CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(
+                TEXTURE_CUBE_MAP,
+                TEXTURE_MIN_FILTER,
+                LINEAR_MIPMAP_LINEAR as i32,
+            );
+            gl.tex_parameter_i32(TEXTURE_CUBE_MAP, TEXTURE_MAG_FILTER, LINEAR as i32);
+            gl.generate_mipmap(TEXTURE_CUBE_MAP);
+            gl.bind_texture(TEXTURE_CUBE_MAP, None);
+
+            Self {
+                shader,
+                framebuffer,
+                sky_map,
+                last_bake: None,
+            }
+        }
+    }
+
+    /// Draws the sky behind everything else, `model` is the skybox cube.
+    pub fn render(&self, gl: &Context, model: &Model, projection_view: Mat4, sky: &SkyState) {
+        self.shader.set_used(gl);
+        self.shader.set_mat4(gl, "projection_view", projection_view);
+        self.shader.set_vec3(gl, "sun_dir", sky.sun_direction);
+        self.shader.set_float(gl, "stars", sky.stars);
+        self.shader.set_int(gl, "baking", 0);
+        draw(gl, model);
+    }
+
+    /// Bakes the sky into the sky map if it's lighting the scene and the last bake is more than
+    /// a few seconds old. Leaves the default framebuffer bound with the viewport unset.
+    pub fn bake(&mut self, gl: &Context, model: &Model, sky: &SkyState) {
+        if sky.mode != SkyMode::ProceduralAmbient {
+            // Bake right away when it's turned back on.
+            self.last_bake = None;
+            return;
+        }
+        if self
+            .last_bake
+            .is_some_and(|last_bake| last_bake.elapsed() < BAKE_INTERVAL)
+        {
+            return;
+        }
+        self.last_bake = Some(Instant::now());
+
+        let projection = Mat4::perspective_rh_gl(90f32.to_radians(), 1., 0.1, 10.);
+        // The cube map faces in OpenGL's order, with the up vectors that orient them.
+        let faces = [
+            (Vec3::X, Vec3::NEG_Y),
+            (Vec3::NEG_X, Vec3::NEG_Y),
+            (Vec3::Y, Vec3::Z),
+            (Vec3::NEG_Y, Vec3::NEG_Z),
+            (Vec3::Z, Vec3::NEG_Y),
+            (Vec3::NEG_Z, Vec3::NEG_Y),
+        ];
+
+        unsafe {
+            gl.bind_framebuffer(FRAMEBUFFER, Some(self.framebuffer));
+            gl.viewport(0, 0, SKY_MAP_SIZE, SKY_MAP_SIZE);
+            gl.disable(DEPTH_TEST);
+            // Flipping the faces to cube map orientation flips the winding too.
+            gl.disable(CULL_FACE);
+
+            self.shader.set_used(gl);
+            self.shader.set_vec3(gl, "sun_dir", sky.sun_direction);
+            self.shader.set_int(gl, "baking", 1);
+
+            for (face, (front, up)) in faces.into_iter().enumerate() {
+                gl.framebuffer_texture_2d(
+                    FRAMEBUFFER,
+                    COLOR_ATTACHMENT0,
+                    TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    Some(self.sky_map),
+                    0,
+                );
+                let view = Mat4::look_at_rh(Vec3::ZERO, front, up);
+                self.shader
+                    .set_mat4(gl, "projection_view", projection * view);
+                draw(gl, model);
+            }
+
+            gl.bind_texture(TEXTURE_CUBE_MAP, Some(self.sky_map));
+            gl.generate_mipmap(TEXTURE_CUBE_MAP);
+            gl.bind_texture(TEXTURE_CUBE_MAP, None);
+
+            gl.enable(CULL_FACE);
+            gl.enable(DEPTH_TEST);
+            gl.bind_framebuffer(FRAMEBUFFER, None);
+        }
+    }
+
+    pub fn bind_sky_map(&self, gl: &Context, unit_index: i32) {
+        unsafe {
+            gl.active_texture(TEXTURE0 + unit_index as u32);
+            gl.bind_texture(TEXTURE_CUBE_MAP, Some(self.sky_map));
+        }
+    }
+}
+
+fn draw(gl: &Context, model: &Model) {
+    unsafe {
+        gl.bind_vertex_array(Some(model.vao()));
+        gl.draw_elements(TRIANGLES, model.len() as i32, UNSIGNED_INT, 0);
+        gl.bind_vertex_array(None);
+    }
+}
@@ -2,8 +2,6 @@ use std::ops::Add;
 
 use glam::{vec3, Mat4, Vec3};
 
-use crate::state::input::InputState;
-
 pub struct Camera {
     pos: Vec3,
     front: Vec3,
@@ -15,8 +13,6 @@ pub struct Camera {
     fov: f32,
     near: f32,
     far: f32,
-    velocity: Vec3,
-    max_speed: f32,
 
     view: Mat4,
     projection: Mat4,
@@ -37,6 +33,10 @@ impl Camera {
         self.pos += v;
     }
 
+    pub fn set_pos(&mut self, pos: Vec3) {
+        self.pos = pos;
+    }
+
     pub fn add_yaw(&mut self, x: f32) {
         self.yaw += x.clamp(-180., 180.);
         if self.yaw > 180. {
@@ -47,12 +47,7 @@ impl Camera {
     }
 
     pub fn add_pitch(&mut self, y: f32) {
-        self.pitch += y;
-        if self.pitch >= 89. {
-            self.pitch = 89.;
-        } else if self.pitch <= -89. {
-            self.pitch = -89.;
-        }
+        self.pitch = (self.pitch + y).clamp(-89., 89.);
     }
 
     pub fn pos(&self) -> Vec3 {
@@ -67,6 +62,28 @@ impl Camera {
         self.right
     }
 
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-89., 89.);
+        self.update();
+    }
+
     pub fn update(&mut self) {
         let mut front = vec3(0., 0., 0.);
         front.x = self.yaw.to_radians().cos() * self.pitch.to_radians().cos();
@@ -78,53 +95,6 @@ impl Camera {
         self.up = self.right.cross(self.front).normalize();
     }
 
-    pub fn update_movement(&mut self, input: &InputState, delta: f32) {
-        let mut move_dir = vec3(0., 0., 0.);
-
-        if input.forward ^ input.backward {
-            if input.forward {
-                move_dir.x = 1.;
-            } else if input.backward {
-                move_dir.x = -1.;
-            }
-        }
-
-        if input.right ^ input.left {
-            if input.right {
-                move_dir.z = 1.;
-            } else if input.left {
-                move_dir.z = -1.;
-            }
-        }
-
-        let move_dir_length_recip = move_dir.length_recip();
-
-        if move_dir.x != 0. {
-            self.velocity.x = move_dir.x * self.max_speed * move_dir_length_recip;
-        }
-        if move_dir.z != 0. {
-            self.velocity.z = move_dir.z * self.max_speed * move_dir_length_recip;
-        }
-
-        let friction = 10. * delta;
-
-        if self.velocity.x != 0.0 {
-            self.velocity.x -= friction * self.velocity.x.signum();
-        }
-        if self.velocity.z != 0.0 {
-            self.velocity.z -= friction * self.velocity.z.signum();
-        }
-
-        if self.velocity.x.abs() < friction {
-            self.velocity.x = 0.;
-        }
-        if self.velocity.z.abs() < friction {
-            self.velocity.z = 0.;
-        }
-
-        self.translate((self.front * self.velocity.x + self.right * self.velocity.z) * delta);
-    }
-
     fn get_view_matrix(&self) -> Mat4 {
         Mat4::look_at_rh(self.pos, self.pos.add(self.front), self.up)
     }
@@ -171,10 +141,8 @@ impl Default for Camera {
             yaw: 90.,
             pitch: 0.,
             fov: 42.,
-            near: 0.5,
+            near: 0.1,
             far: 400.,
-            velocity: vec3(0., 0., 0.),
-            max_speed: 5.0,
 
             projection: Default::default(),
             view: Default::default(),
@@ -0,0 +1,455 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use glam::{ivec2, ivec3, vec3, IVec2, IVec3, Mat4, Vec3};
+use glow::*;
+
+use super::camera::Camera;
+
+/// Screen tiles are this many pixels wide and high.
+pub const TILE_SIZE: i32 = 64;
+/// Each tile is split into this many clusters along the view depth.
+pub const DEPTH_SLICES: i32 = 16;
+/// Lights beyond this many, the furthest from the camera, are dropped.
+pub const MAX_POINT_LIGHTS: usize = 1024;
+/// Width of the texture holding the light index lists, it wraps into as many rows as needed.
+const INDEX_TEXTURE_WIDTH: usize = 1024;
+
+/// A point or spot light as the lighting pass shades it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClusterLight {
+    pub position: Vec3,
+    /// Colour times intensity.
+    pub color: Vec3,
+    /// Distance at which the light fades out completely.
+    pub radius: f32,
+    pub direction: Vec3,
+    /// Cosines of the angles from `direction` inside which the light is at full strength and
+    /// outside which it's off. Point lights shine everywhere, below -1.
+    pub cos_inner: f32,
+    pub cos_outer: f32,
+}
+
+impl ClusterLight {
+    pub fn point(position: Vec3, color: Vec3, radius: f32) -> Self {
+        Self {
+            position,
+            color,
+            radius,
+            direction: Vec3::NEG_Z,
+            cos_inner: -1.,
+            cos_outer: -2.,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        radius: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            position,
+            color,
+            radius,
+            direction,
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+        }
+    }
+}
+
+/// The view frustum split into screen tiles and exponential depth slices, with the lights that
+/// reach each cluster.
+///
+/// Clusters are ordered by tile x, then tile y, then depth slice, matching how they're laid out
+/// in the cluster texture: one row per tile row of each slice.
+pub struct LightClusters {
+    pub dims: IVec3,
+    /// Offset into `indices` and number of lights of each cluster.
+    pub clusters: Vec<(u32, u32)>,
+    pub indices: Vec<u32>,
+}
+
+impl LightClusters {
+    /// Bins `lights` into the clusters of a `screen` sized view with the given camera.
+    pub fn build(
+        lights: &[ClusterLight],
+        view: Mat4,
+        projection: Mat4,
+        screen: IVec2,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        let dims = ivec3(
+            (screen.x + TILE_SIZE - 1) / TILE_SIZE,
+            (screen.y + TILE_SIZE - 1) / TILE_SIZE,
+            DEPTH_SLICES,
+        );
+        let mut lists = vec![Vec::new(); (dims.x * dims.y * dims.z) as usize];
+
+        for (index, light) in lights.iter().enumerate() {
+            let center = view.transform_point3(light.position);
+            let (min_depth, max_depth) = (-center.z - light.radius, -center.z + light.radius);
+            if max_depth < near || min_depth > far {
+                continue;
+            }
+
+            let min_slice = depth_slice(min_depth, near, far);
+            let max_slice = depth_slice(max_depth, near, far);
+            let (min_tile, max_tile) = match screen_tiles(light, center, projection, screen, near) {
+                Some(tiles) => tiles,
+                None => continue,
+            };
+
+            for z in min_slice..=max_slice {
+                for y in min_tile.y..=max_tile.y {
+                    for x in min_tile.x..=max_tile.x {
+                        lists[(x + (y + z * dims.y) * dims.x) as usize].push(index as u32);
+                    }
+                }
+            }
+        }
+
+        let mut clusters = Vec::with_capacity(lists.len());
+        let mut indices = Vec::new();
+        for list in lists {
+            clusters.push((indices.len() as u32, list.len() as u32));
+            indices.extend(list);
+        }
+
+        Self {
+            dims,
+            clusters,
+            indices,
+        }
+    }
+}
+
+/// The depth slice a view space depth falls in. Slices get exponentially deeper so clusters
+/// stay roughly cube shaped.
+fn depth_slice(depth: f32, near: f32, far: f32) -> i32 {
+    let depth = depth.clamp(near, far);
+    let slice = ((depth / near).ln() / (far / near).ln() * DEPTH_SLICES as f32) as i32;
+    slice.min(DEPTH_SLICES - 1)
+}
+
+/// The range of screen tiles covered by the bounding box of a light around the view space
+/// `center`, or `None` if it's off screen. Lights reaching past the near plane cover the whole
+/// screen.
+fn screen_tiles(
+    light: &ClusterLight,
+    center: Vec3,
+    projection: Mat4,
+    screen: IVec2,
+    near: f32,
+) -> Option<(IVec2, IVec2)> {
+    let last = (screen - 1) / TILE_SIZE;
+    if -center.z - light.radius <= near {
+        return Some((IVec2::ZERO, last));
+    }
+
+    let mut min = Vec3::splat(f32::INFINITY);
+    let mut max = Vec3::splat(f32::NEG_INFINITY);
+    for corner in 0..8 {
+        let sign = vec3(
+            if corner & 1 == 0 { -1. } else { 1. },
+            if corner & 2 == 0 { -1. } else { 1. },
+            if corner & 4 == 0 { -1. } else { 1. },
+        );
+        let ndc = projection.project_point3(center + sign * light.radius);
+        min = min.min(ndc);
+        max = max.max(ndc);
+    }
+    if max.x < -1. || max.y < -1. || min.x > 1. || min.y > 1. {
+        return None;
+    }
+
+    let to_tile = |ndc: f32, size: i32| ((ndc * 0.5 + 0.5) * size as f32) as i32 / TILE_SIZE;
+    let min_tile = ivec2(to_tile(min.x, screen.x), to_tile(min.y, screen.y));
+    let max_tile = ivec2(to_tile(max.x, screen.x), to_tile(max.y, screen.y));
+    Some((
+        min_tile.clamp(IVec2::ZERO, last),
+        max_tile.clamp(IVec2::ZERO, last),
+    ))
+}
+
+/// What shading point lights cost in the last frame.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LightStats {
+    pub lights: usize,
+    /// Lights summed over every cluster, what the lighting pass loops over.
+    pub entries: usize,
+    pub max_per_cluster: usize,
+    pub bin_time: Duration,
+}
+
+impl fmt::Display for LightStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Lights: {} ({} in clusters, at most {} per cluster, binned in {:.2} ms)",
+            self.lights,
+            self.entries,
+            self.max_per_cluster,
+            self.bin_time.as_secs_f64() * 1000.
+        )
+    }
+}
+
+/// Textures the lighting pass reads the point lights and clusters from.
+pub struct ClusterTextures {
+    /// Three texels per light in each row: position and radius, colour and inner cone cosine,
+    /// then direction and outer cone cosine.
+    lights: Texture,
+    /// Offset and count of each cluster.
+    clusters: Texture,
+    indices: Texture,
+    stats: LightStats,
+}
+
+impl ClusterTextures {
+    pub fn new(gl: &Context) -> Self {
+        unsafe {
+            Self {
+                lights: create_data_texture(gl),
+                clusters: create_data_texture(gl),
+                indices: create_data_texture(gl),
+                stats: LightStats::default(),
+            }
+        }
+    }
+
+    /// Bins the lights nearest to the camera and uploads them.
+    pub fn update(
+        &mut self,
+        gl: &Context,
+        lights: &[ClusterLight],
+        camera: &Camera,
+        screen: IVec2,
+    ) -> IVec3 {
+        let start = Instant::now();
+
+        let mut lights = lights.to_vec();
+        if lights.len() > MAX_POINT_LIGHTS {
+            lights.sort_by(|a, b| {
+                let a = a.position.distance_squared(camera.pos());
+                let b = b.position.distance_squared(camera.pos());
+                a.total_cmp(&b)
+            });
+            lights.truncate(MAX_POINT_LIGHTS);
+        }
+
+        let clusters = LightClusters::build(
+            &lights,
+            camera.view(),
+            camera.projection(),
+            screen,
+            camera.near(),
+            camera.far(),
+        );
+
+        let light_data: Vec<f32> = lights
+            .iter()
+            .flat_map(|l| {
+                let (p, c, d) = (l.position, l.color, l.direction);
+                [
+                    p.x,
+                    p.y,
+                    p.z,
+                    l.radius,
+                    c.x,
+                    c.y,
+                    c.z,
+                    l.cos_inner,
+                    d.x,
+                    d.y,
+                    d.z,
+                    l.cos_outer,
+                ]
+            })
+            .collect();
+        let cluster_data: Vec<u32> = clusters
+            .clusters
+            .iter()
+            .flat_map(|&(offset, count)| [offset, count])
+            .collect();
+        let mut index_data = clusters.indices.clone();
+        let index_rows = index_data.len().div_ceil(INDEX_TEXTURE_WIDTH).max(1);
+        index_data.resize(index_rows * INDEX_TEXTURE_WIDTH, 0);
+
+        unsafe {
+            upload(
+                gl,
+                self.lights,
+                RGBA32F,
+                RGBA,
+                FLOAT,
+                ivec2(3, lights.len().max(1) as i32),
+                &light_data,
+            );
+            upload(
+                gl,
+                self.clusters,
+                RG32UI,
+                RG_INTEGER,
+                UNSIGNED_INT,
+                ivec2(clusters.dims.x, clusters.dims.y * clusters.dims.z),
+                &cluster_data,
+            );
+            upload(
+                gl,
+                self.indices,
+                R32UI,
+                RED_INTEGER,
+                UNSIGNED_INT,
+                ivec2(INDEX_TEXTURE_WIDTH as i32, index_rows as i32),
+                &index_data,
+            );
+        }
+
+        self.stats = LightStats {
+            lights: lights.len(),
+            entries: clusters.indices.len(),
+            max_per_cluster: clusters
+                .clusters
+                .iter()
+                .map(|&(_, count)| count as usize)
+                .max()
+                .unwrap_or(0),
+            bin_time: start.elapsed(),
+        };
+
+        clusters.dims
+    }
+
+    /// Binds the light, cluster and index textures to three texture units from `first_unit`.
+    pub fn bind(&self, gl: &Context, first_unit: u32) {
+        unsafe {
+            for (i, texture) in [self.lights, self.clusters, self.indices]
+                .into_iter()
+                .enumerate()
+            {
+                gl.active_texture(TEXTURE0 + first_unit + i as u32);
+                gl.bind_texture(TEXTURE_2D, Some(texture));
+            }
+        }
+    }
+
+    pub fn stats(&self) -> LightStats {
+        self.stats
+    }
+}
+
+unsafe fn create_data_texture(gl: &Context) -> Texture {
+    let texture = gl.create_texture().expect("Couldn't create texture.");
+    gl.bind_texture(TEXTURE_2D, Some(texture));
+    gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as i32);
+    gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as i32);
+    gl.bind_texture(TEXTURE_2D, None);
+    texture
+}
+
+/// Replaces the contents of a data texture. An empty `data` leaves the texture undefined, the
+/// shader never reads it then.
+unsafe fn upload<T>(
+    gl: &Context,
+    texture: Texture,
+    internal_format: u32,
+    format: u32,
+    type_: u32,
+    dims: IVec2,
+    data: &[T],
+) {
+    let bytes =
+        core::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data));
+    gl.bind_texture(TEXTURE_2D, Some(texture));
+    gl.pixel_store_i32(UNPACK_ALIGNMENT, 4);
+    gl.tex_image_2d(
+        TEXTURE_2D,
+        0,
+        internal_format as i32,
+        dims.x,
+        dims.y,
+        0,
+        format,
+        type_,
+        (!bytes.is_empty()).then_some(bytes),
+    );
+    gl.bind_texture(TEXTURE_2D, None);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec2, vec3, Mat4, Vec3};
+
+    use super::{ClusterLight, LightClusters, DEPTH_SLICES};
+
+    const NEAR: f32 = 0.1;
+    const FAR: f32 = 400.;
+
+    /// Bins lights for a 640x384 screen (10x6 tiles) looking down -Z from the origin.
+    fn build(lights: &[ClusterLight]) -> LightClusters {
+        let projection = Mat4::perspective_rh_gl(60f32.to_radians(), 640. / 384., NEAR, FAR);
+        LightClusters::build(
+            lights,
+            Mat4::IDENTITY,
+            projection,
+            ivec2(640, 384),
+            NEAR,
+            FAR,
+        )
+    }
+
+    fn light(position: Vec3, radius: f32) -> ClusterLight {
+        ClusterLight::point(position, Vec3::ONE, radius)
+    }
+
+    /// Which clusters the first light is in, as (x, y, slice).
+    fn clusters_with_light(clusters: &LightClusters) -> Vec<(i32, i32, i32)> {
+        let dims = clusters.dims;
+        let mut found = Vec::new();
+        for (i, &(offset, count)) in clusters.clusters.iter().enumerate() {
+            let list = &clusters.indices[offset as usize..(offset + count) as usize];
+            if list.contains(&0) {
+                let i = i as i32;
+                found.push((i % dims.x, i / dims.x % dims.y, i / (dims.x * dims.y)));
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn bins_lights_into_the_clusters_they_reach() {
+        let clusters = build(&[light(vec3(0., 0., -20.), 1.)]);
+        assert_eq!(clusters.dims, glam::ivec3(10, 6, DEPTH_SLICES));
+
+        let found = clusters_with_light(&clusters);
+        assert!(!found.is_empty());
+        for (x, y, _) in &found {
+            assert!((4..=5).contains(x) && (2..=3).contains(y));
+        }
+        assert!(found.len() < 4 * 3);
+    }
+
+    #[test]
+    fn skips_lights_out_of_view() {
+        let behind = light(vec3(0., 0., 20.), 1.);
+        let beside = light(vec3(100., 0., -20.), 1.);
+        let beyond = light(vec3(0., 0., -500.), 10.);
+        let clusters = build(&[behind, beside, beyond]);
+        assert!(clusters.indices.is_empty());
+    }
+
+    #[test]
+    fn lights_around_the_camera_cover_the_screen() {
+        let clusters = build(&[light(vec3(0., 0., 1.), 3.)]);
+        let found = clusters_with_light(&clusters);
+        assert!(found.contains(&(0, 0, 0)));
+        assert!(found.contains(&(9, 5, 0)));
+    }
+}
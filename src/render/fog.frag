@@ -0,0 +1,111 @@
+#version 330 core
+
+in vec2 tex_coords;
+
+out vec4 color;
+
+uniform sampler2D g_position;
+uniform sampler2D g_normal;
+// One layer for each cascade of the sun's shadow map, see `shadow.rs`.
+uniform sampler2DArrayShadow shadow_map;
+
+uniform vec3 view_pos;
+uniform mat4 view;
+// Finds the direction of the sky behind pixels without geometry.
+uniform mat4 inverse_projection_view;
+// The camera's far plane, how far away the sky is for fog.
+uniform float z_far;
+uniform vec3 light_dir;
+// Sun colour times intensity, black without a sun.
+uniform vec3 sun_color;
+uniform vec3 horizon_color;
+// Ray marching steps through the shadow map for the light shafts, none to only draw the fog.
+uniform int ray_steps;
+
+#define CASCADES 4
+uniform mat4 cascade_projection_views[CASCADES];
+// View space depths where the cascades end.
+uniform float cascade_splits[CASCADES];
+
+// Fog density at FOG_HEIGHT, it thins out exponentially above it.
+#define FOG_DENSITY 0.012
+#define FOG_HEIGHT 0.0
+#define FOG_FALLOFF 0.08
+// Light shafts are only marched as far as the shadow map reaches.
+#define RAY_DISTANCE 128.0
+// Density of the haze that scatters the sunlight into shafts.
+#define RAY_DENSITY 0.006
+// Henyey-Greenstein asymmetry, the haze scatters mostly forwards.
+#define RAY_G 0.6
+#define PI 3.14159265
+
+// Fog between `origin` and `dist` along `dir`, the integral of the height fog density.
+float fog_amount(vec3 origin, vec3 dir, float dist) {
+    float start_density = FOG_DENSITY * exp(-FOG_FALLOFF * (origin.y - FOG_HEIGHT));
+    float falloff = FOG_FALLOFF * dir.y;
+    if (abs(falloff) < 0.0001) {
+        return start_density * dist;
+    }
+    return start_density * (1.0 - exp(-falloff * dist)) / falloff;
+}
+
+// Whether sunlight reaches `position`, from the first cascade it's in.
+float sunlight(vec3 position) {
+    float depth = -(view * vec4(position, 1.0)).z;
+    for (int i = 0; i < CASCADES; i++) {
+        if (depth < cascade_splits[i]) {
+            vec3 light_position = (cascade_projection_views[i] * vec4(position, 1.0)).xyz * 0.5 + 0.5;
+            return texture(shadow_map, vec4(light_position.xy, float(i), light_position.z));
+        }
+    }
+    return 1.0;
+}
+
+float phase(float cos_angle) {
+    float g2 = RAY_G * RAY_G;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * RAY_G * cos_angle, 1.5));
+}
+
+// Noise that's even across the screen, hides the banding of few ray marching steps.
+float interleaved_gradient_noise(vec2 pixel) {
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+void main() {
+    vec3 position = texture(g_position, tex_coords).rgb;
+    bool sky = length(texture(g_normal, tex_coords).rgb) < 0.5;
+
+    vec3 dir;
+    float dist;
+    if (sky) {
+        vec4 far = inverse_projection_view * vec4(tex_coords * 2.0 - 1.0, 1.0, 1.0);
+        dir = normalize(far.xyz / far.w - view_pos);
+        dist = z_far;
+    } else {
+        dir = normalize(position - view_pos);
+        dist = distance(position, view_pos);
+    }
+
+    float fog = 1.0 - exp(-fog_amount(view_pos, dir, dist));
+    float sun_amount = pow(max(dot(dir, -light_dir), 0.0), 8.0);
+    vec3 fog_color = horizon_color + sun_color * sun_amount * 0.3;
+
+    vec3 rays = vec3(0.0);
+    if (ray_steps > 0) {
+        float step_length = min(dist, RAY_DISTANCE) / float(ray_steps);
+        float offset = interleaved_gradient_noise(gl_FragCoord.xy);
+        float lit = 0.0;
+        for (int i = 0; i < ray_steps; i++) {
+            float t = (float(i) + offset) * step_length;
+            float transmittance = exp(-fog_amount(view_pos, dir, t));
+            lit += sunlight(view_pos + dir * t) * transmittance;
+        }
+        rays = sun_color * lit * step_length * RAY_DENSITY * phase(dot(dir, -light_dir));
+    }
+
+    // The scene under the fog is already tonemapped, so this blends in display space. The
+    // shafts skip the gamma curve, which would wash the whole screen out with the faint haze.
+    fog_color = pow(vec3(1.0) - exp(-fog_color), vec3(1.0 / 2.2));
+    rays = vec3(1.0) - exp(-rays);
+    color = vec4(fog_color * fog + rays, fog);
+}
@@ -3,16 +3,26 @@
 layout (location = 0) out vec3 g_position;
 layout (location = 1) out vec3 g_normal;
 layout (location = 2) out vec4 g_albedo_spec;
+layout (location = 3) out vec4 g_light;
 
 in vec3 position;
 in vec3 normal;
 in vec2 tex_coords;
+in vec4 light;
 
 uniform sampler2D texture_diffuse;
 
 void main() {
+    vec4 albedo = texture(texture_diffuse, tex_coords);
+    // Ladders, vines and stained glass are see-through cutouts.
+    if (albedo.a < 0.5) {
+        discard;
+    }
+
     g_position = position;
     g_normal = normal;
-    g_albedo_spec.rgb = texture(texture_diffuse, tex_coords).rgb;
+    g_albedo_spec.rgb = albedo.rgb;
     g_albedo_spec.a = 0.4;
+    // Sky light and red, green and blue block light of the voxels around the surface.
+    g_light = light;
 }
\ No newline at end of file
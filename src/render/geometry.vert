@@ -3,10 +3,12 @@
 layout (location = 0) in vec3 in_position;
 layout (location = 1) in vec3 in_normal;
 layout (location = 2) in vec2 in_tex_coords;
+layout (location = 3) in vec4 in_light;
 
 out vec3 position;
 out vec3 normal;
 out vec2 tex_coords;
+out vec4 light;
 
 uniform mat4 model;
 uniform mat4 projection_view;
@@ -15,6 +17,7 @@ void main() {
     vec4 world_pos = model * vec4(in_position, 1.0);
     position = world_pos.xyz;
     tex_coords = in_tex_coords;
+    light = in_light;
 
     mat3 normal_matrix = transpose(inverse(mat3(model)));
     normal = normalize(normal_matrix * in_normal);
@@ -5,32 +5,172 @@ in vec2 tex_coords;
 uniform sampler2D g_position;
 uniform sampler2D g_normal;
 uniform sampler2D g_albedo_spec;
-uniform sampler2DShadow shadow_map;
+// One layer for each cascade of the sun's shadow map, see `shadow.rs`.
+uniform sampler2DArrayShadow shadow_map;
+uniform sampler2D g_light;
+// Point lights binned into view space clusters, see `cluster.rs`.
+uniform sampler2D point_lights;
+uniform usampler2D light_clusters;
+uniform usampler2D light_indices;
+// How much of the sky each pixel sees past the geometry around it, see `ssao.rs`.
+uniform sampler2D ssao;
+// The procedural sky baked into a cube map, see `atmosphere.rs`.
+uniform samplerCube sky_map;
 
 uniform vec3 view_pos;
-uniform mat4 shadow_projection_view;
 uniform vec3 light_dir;
+// Sun colour times intensity, black without a sun.
+uniform vec3 sun_color;
+uniform bool sun_shadows;
+// Strength of the light from the whole sky, lower at night.
+uniform float ambient;
+// Whether the light from the sky comes from the sky map instead of `ambient`.
+uniform bool sky_map_ambient;
+uniform bool ambient_occlusion;
+uniform mat4 view;
+uniform int tile_size;
+uniform ivec3 cluster_dims;
+uniform float z_near;
+uniform float z_far;
+
+#define CASCADES 4
+uniform mat4 cascade_projection_views[CASCADES];
+// View space depths where the cascades end.
+uniform float cascade_splits[CASCADES];
+// World space size of a texel of each cascade.
+uniform float cascade_texel_sizes[CASCADES];
 
 out vec4 color;
 
 #define EPSILON 0.00001
-#define SHADOW_WIDTH 4096.0
-#define SHADOW_HEIGHT 4096.0
+#define SHADOW_SIZE 2048.0
+// Fraction at the end of each cascade over which it fades into the next.
+#define CASCADE_BLEND 0.1
+// How far the shadow map is sampled off the surface along its normal, in texels. Keeps surfaces
+// from shadowing themselves however big the cascade's texels are.
+#define NORMAL_OFFSET 1.5
 
 #define SHADOW_BLUR 1
 #define SHADOW_BLUR_WIDTH (SHADOW_BLUR * 2 + 1)
 #define SHADOW_FACTOR_SIZE (SHADOW_BLUR_WIDTH * SHADOW_BLUR_WIDTH)
 
+#define MIN_AMBIENT 0.01
+// Mip of the 32 pixel sky map with 2x2 pixel faces, blurry enough to stand in for the light
+// from the half of the sky a surface faces.
+#define SKY_MAP_LOD 4.0
+#define SKY_MAP_AMBIENT 0.5
+
+#define INDEX_TEXTURE_WIDTH 1024
+
+// Voxel light levels are linear steps, this makes each step look about as bright as the last.
+float light_brightness(float level) {
+    return level / (4.0 - 3.0 * level);
+}
+
+vec3 light_brightness(vec3 level) {
+    return level / (4.0 - 3.0 * level);
+}
+
+// Smoothly fades out to zero at the light's radius.
+float attenuation(float dist, float radius) {
+    float falloff = clamp(1.0 - pow(dist / radius, 4.0), 0.0, 1.0);
+    return falloff * falloff / (dist * dist + 1.0);
+}
+
+// Fraction of the sunlight reaching `position` in one cascade.
+float cascade_shadow(int cascade, vec3 position, vec3 normal) {
+    vec3 offset_position = position + normal * cascade_texel_sizes[cascade] * NORMAL_OFFSET;
+    vec3 light_position = (cascade_projection_views[cascade] * vec4(offset_position, 1.0)).xyz * 0.5 + 0.5;
+
+    float shadow_step = 1.0 / SHADOW_SIZE;
+    float shadow_factor = 0.0;
+    for (int y = -SHADOW_BLUR; y <= SHADOW_BLUR; y++) {
+        for (int x = -SHADOW_BLUR; x <= SHADOW_BLUR; x++) {
+            vec2 offsets = vec2(x, y) * shadow_step;
+            vec4 uvlc = vec4(light_position.xy + offsets, float(cascade), light_position.z + EPSILON);
+            shadow_factor += texture(shadow_map, uvlc);
+        }
+    }
+    return shadow_factor / float(SHADOW_FACTOR_SIZE);
+}
+
+// Fraction of the sunlight reaching `position`, from the first cascade it's in. Shadows fade
+// out at the end of the last one.
+float sun_shadow(vec3 position, vec3 normal) {
+    if (!sun_shadows) {
+        return 1.0;
+    }
+
+    float depth = -(view * vec4(position, 1.0)).z;
+    float start = z_near;
+    for (int i = 0; i < CASCADES; i++) {
+        float end = cascade_splits[i];
+        if (depth < end) {
+            float shadow = cascade_shadow(i, position, normal);
+            float blend = (end - depth) / ((end - start) * CASCADE_BLEND);
+            if (blend < 1.0) {
+                float next = i + 1 < CASCADES ? cascade_shadow(i + 1, position, normal) : 1.0;
+                shadow = mix(next, shadow, blend);
+            }
+            return shadow;
+        }
+        start = end;
+    }
+    return 1.0;
+}
+
+vec3 point_lighting(vec3 position, vec3 normal, vec3 view_dir, vec3 albedo, float specular_strength) {
+    float depth = -(view * vec4(position, 1.0)).z;
+    int slice = int(log(max(depth, z_near) / z_near) / log(z_far / z_near) * float(cluster_dims.z));
+    slice = clamp(slice, 0, cluster_dims.z - 1);
+    ivec2 tile = min(ivec2(gl_FragCoord.xy) / tile_size, cluster_dims.xy - 1);
+    uvec2 cluster = texelFetch(light_clusters, ivec2(tile.x, tile.y + slice * cluster_dims.y), 0).rg;
+
+    vec3 lighting = vec3(0.0);
+    for (uint i = 0u; i < cluster.y; i++) {
+        int entry = int(cluster.x + i);
+        int index = int(texelFetch(light_indices, ivec2(entry % INDEX_TEXTURE_WIDTH, entry / INDEX_TEXTURE_WIDTH), 0).r);
+        vec4 position_radius = texelFetch(point_lights, ivec2(0, index), 0);
+        vec4 color_inner = texelFetch(point_lights, ivec2(1, index), 0);
+        vec4 direction_outer = texelFetch(point_lights, ivec2(2, index), 0);
+
+        vec3 to_light = position_radius.xyz - position;
+        float dist = length(to_light);
+        if (dist >= position_radius.w) {
+            continue;
+        }
+        vec3 dir = to_light / dist;
+        float cone = smoothstep(direction_outer.w, color_inner.w, dot(-dir, direction_outer.xyz));
+
+        vec3 diffuse = max(dot(normal, dir), 0.0) * albedo;
+        float spec = pow(max(dot(view_dir, reflect(-dir, normal)), 0.0), 8.0);
+        lighting += (diffuse + spec * specular_strength) * color_inner.rgb * cone *
+            attenuation(dist, position_radius.w);
+    }
+    return lighting;
+}
+
 void main() {
     vec3 position = texture(g_position, tex_coords).rgb;
     vec3 normal = texture(g_normal, tex_coords).rgb;
     vec3 albedo = texture(g_albedo_spec, tex_coords).rgb;
     float specular_strength = texture(g_albedo_spec, tex_coords).a;
-    vec4 light_view_position = shadow_projection_view * vec4(position, 1.0);
+    vec4 voxel_light = texture(g_light, tex_coords);
+    float sky_light = light_brightness(voxel_light.r);
+    vec3 block_light = light_brightness(voxel_light.gba);
 
-    vec3 light_color = vec3(1.0, 1.0, 1.0);
+    vec3 light_color = sun_color;
 
-    vec3 lighting = albedo * 0.2;
+    vec3 sky_ambient = vec3(ambient);
+    if (sky_map_ambient) {
+        sky_ambient = textureLod(sky_map, normal, SKY_MAP_LOD).rgb * SKY_MAP_AMBIENT;
+    }
+    float occlusion = 1.0;
+    if (ambient_occlusion) {
+        occlusion = texture(ssao, tex_coords).r;
+    }
+    vec3 lighting = albedo * (sky_ambient * sky_light + MIN_AMBIENT) * occlusion;
+    lighting += albedo * block_light;
     vec3 view_dir = normalize(view_pos - position);
 
     vec3 diffuse = max(dot(-light_dir, normal), 0.0) * albedo * light_color;
@@ -39,18 +179,12 @@ void main() {
     float spec = pow(max(dot(view_dir, reflect_dir), 0.0), 8.0);
     vec3 specular = light_color * spec * specular_strength;
 
-    vec2 shadow_step = vec2(1.0 / SHADOW_WIDTH, 1.0 / SHADOW_HEIGHT);
-    float shadow_factor = 0.0;
-    for (int y = -SHADOW_BLUR; y <= SHADOW_BLUR; y++) {
-        for (int x = -SHADOW_BLUR; x <= SHADOW_BLUR; x++) {
-            vec2 offsets = vec2(x * shadow_step.x, y * shadow_step.y);
-            vec3 uvc = vec3(light_view_position.xy + offsets, light_view_position.z + EPSILON);
-            shadow_factor += texture(shadow_map, uvc);
-        }
-    }
+    float shadow = sun_shadow(position, normal);
+
+    // The sun only reaches what the sky does, so caves stay dark outside the shadow map too.
+    lighting += (diffuse * (0.5 + shadow * 0.5) + specular * shadow) * sky_light;
 
-    lighting += diffuse * (0.5 + (shadow_factor / (float(SHADOW_FACTOR_SIZE) * 2))) +
-        specular * (shadow_factor / float(SHADOW_FACTOR_SIZE));
+    lighting += point_lighting(position, normal, view_dir, albedo, specular_strength);
 
     // lighting = lighting / (lighting + vec3(1.0));
     float exposure = 1.0;
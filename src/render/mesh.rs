@@ -1,21 +1,42 @@
-use glam::{vec2, vec3, Vec2, Vec3};
+use glam::{vec2, vec3, Vec2, Vec3, Vec4};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[repr(C)]
 pub struct Vertex {
     position: Vec3,
     normal: Vec3,
     tex_coords: Vec2,
+    /// Sky light and red, green and blue block light in `0..=1`.
+    light: Vec4,
 }
 
 impl Vertex {
+    /// A vertex under the open sky.
     pub fn new(position: Vec3, normal: Vec3, tex_coords: Vec2) -> Self {
         Self {
             position,
             normal,
             tex_coords,
+            light: Vec4::X,
         }
     }
+
+    pub fn with_light(mut self, light: Vec4) -> Self {
+        self.light = light;
+        self
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn tex_coords(&self) -> Vec2 {
+        self.tex_coords
+    }
 }
 
 pub struct Quad {
@@ -66,10 +87,14 @@ impl Mesh {
         &self.vertices
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
     pub fn from_cube(cube_size: f32) -> Self {
         let (x, y, z) = (0., 0., 0.);
 
-        let points = vec![
+        let points = [
             vec3(x, y, z),                                     // 0
             vec3(x + cube_size, y, z),                         // 1
             vec3(x + cube_size, y + cube_size, z),             // 2
@@ -0,0 +1,322 @@
+use std::collections::BTreeMap;
+
+use glam::{ivec3, uvec4, vec2, IVec3, UVec4, Vec3, Vec4};
+
+use crate::state::voxel::{
+    block::Block,
+    light::{LightChannel, MAX_LIGHT},
+    terrain::Terrain,
+};
+
+use super::mesh::{Mesh, Quad, Vertex};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MesherOptions {
+    /// Merge adjacent coplanar faces of the same block into larger quads.
+    pub merge_faces: bool,
+    /// Treat blocks outside the meshed region as air so the mesh is closed at its borders.
+    pub closed: bool,
+}
+
+/// A visible block face and the light at its corners.
+#[derive(Clone, Copy, PartialEq)]
+struct Face {
+    block: Block,
+    light: [Vec4; 4],
+}
+
+impl Face {
+    /// Only evenly lit faces can be merged, larger quads would interpolate the light wrong.
+    fn is_evenly_lit(&self) -> bool {
+        self.light.iter().all(|&light| light == self.light[0])
+    }
+}
+
+/// Meshes the blocks in `min..max` (max exclusive) into one mesh per block type.
+///
+/// Vertex positions are relative to `min`. Texture coordinates are in block units and rely on
+/// the texture repeating, so merged faces keep the same texel density as single blocks. Each
+/// vertex gets the average light of the blocks in front of the face around it.
+pub fn mesh_region(
+    terrain: &Terrain,
+    min: IVec3,
+    max: IVec3,
+    options: MesherOptions,
+) -> Vec<(Block, Mesh)> {
+    let size = max - min;
+    let grid = PaddedGrid::new(terrain, min, size, options.closed);
+    let mut meshes = BTreeMap::<Block, Mesh>::new();
+
+    for d in 0..3 {
+        let u = (d + 1) % 3;
+        let v = (d + 2) % 3;
+
+        for positive in [false, true] {
+            let mut normal = IVec3::ZERO;
+            normal[d] = if positive { 1 } else { -1 };
+
+            let mut mask = vec![None; (size[u] * size[v]) as usize];
+
+            for slice in 0..size[d] {
+                for j in 0..size[v] {
+                    for i in 0..size[u] {
+                        let mut pos = IVec3::ZERO;
+                        pos[d] = slice;
+                        pos[u] = i;
+                        pos[v] = j;
+
+                        let block = grid.get(pos);
+                        let neighbour = grid.get(pos + normal);
+                        let visible =
+                            !block.is_air() && !neighbour.is_opaque() && neighbour != block;
+                        mask[(i + j * size[u]) as usize] = visible.then(|| Face {
+                            block,
+                            light: grid.face_light(pos + normal, u, v),
+                        });
+                    }
+                }
+
+                for j in 0..size[v] {
+                    let mut i = 0;
+                    while i < size[u] {
+                        let face = match mask[(i + j * size[u]) as usize] {
+                            Some(face) => face,
+                            None => {
+                                i += 1;
+                                continue;
+                            }
+                        };
+
+                        let (mut w, mut h) = (1, 1);
+                        if options.merge_faces && face.is_evenly_lit() {
+                            while i + w < size[u]
+                                && mask[(i + w + j * size[u]) as usize] == Some(face)
+                            {
+                                w += 1;
+                            }
+                            'grow: while j + h < size[v] {
+                                for k in 0..w {
+                                    if mask[(i + k + (j + h) * size[u]) as usize] != Some(face) {
+                                        break 'grow;
+                                    }
+                                }
+                                h += 1;
+                            }
+                        }
+
+                        for y in j..j + h {
+                            for x in i..i + w {
+                                mask[(x + y * size[u]) as usize] = None;
+                            }
+                        }
+
+                        let mut base = IVec3::ZERO;
+                        base[d] = slice + positive as i32;
+                        base[u] = i;
+                        base[v] = j;
+
+                        let quad = face_quad(d, positive, base, w, h, face.light);
+                        meshes.entry(face.block).or_default().push_quad(quad);
+
+                        i += w;
+                    }
+                }
+            }
+        }
+    }
+
+    meshes.into_iter().collect()
+}
+
+/// `light` is indexed by corner, counter-clockwise from `base` when looking along +u then +v.
+fn face_quad(d: usize, positive: bool, base: IVec3, w: i32, h: i32, light: [Vec4; 4]) -> Quad {
+    let u = (d + 1) % 3;
+    let v = (d + 2) % 3;
+
+    let mut normal = Vec3::ZERO;
+    normal[d] = if positive { 1. } else { -1. };
+
+    let corner = |a: i32, b: i32| {
+        let mut pos = base;
+        pos[u] += a;
+        pos[v] += b;
+
+        // Keep the texture upright on side faces: t always follows Y when the face is vertical.
+        let tex_coords = if d == 2 {
+            vec2(a as f32, b as f32)
+        } else {
+            vec2(b as f32, a as f32)
+        };
+
+        let corner = match (a > 0, b > 0) {
+            (false, false) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+            (false, true) => 3,
+        };
+
+        Vertex::new(pos.as_vec3(), normal, tex_coords).with_light(light[corner])
+    };
+
+    if positive {
+        Quad::new(corner(0, 0), corner(w, 0), corner(w, h), corner(0, h))
+    } else {
+        Quad::new(corner(0, 0), corner(0, h), corner(w, h), corner(w, 0))
+    }
+}
+
+/// Local copy of the meshed region with a one block border, so neighbour lookups don't have
+/// to go through the chunk map.
+struct PaddedGrid {
+    blocks: Vec<Block>,
+    /// Sky, red, green and blue light levels.
+    light: Vec<UVec4>,
+    size: IVec3,
+}
+
+impl PaddedGrid {
+    fn new(terrain: &Terrain, min: IVec3, size: IVec3, closed: bool) -> Self {
+        let padded = size + 2;
+        let mut blocks = Vec::with_capacity((padded.x * padded.y * padded.z) as usize);
+        let mut light = Vec::with_capacity(blocks.capacity());
+
+        for y in -1..=size.y {
+            for z in -1..=size.z {
+                for x in -1..=size.x {
+                    let local = ivec3(x, y, z);
+                    let [sky, red, green, blue] = LightChannel::ALL
+                        .map(|channel| terrain.get_light(min + local, channel) as u32);
+                    light.push(uvec4(sky, red, green, blue));
+
+                    let inside = local.cmpge(IVec3::ZERO).all() && local.cmplt(size).all();
+                    if closed && !inside {
+                        blocks.push(Block::Air);
+                    } else {
+                        blocks.push(terrain.get_block(min + local));
+                    }
+                }
+            }
+        }
+
+        Self {
+            blocks,
+            light,
+            size,
+        }
+    }
+
+    fn index(&self, local: IVec3) -> usize {
+        let padded = self.size + 2;
+        let p = local + 1;
+        (p.x + p.z * padded.x + p.y * padded.x * padded.z) as usize
+    }
+
+    fn get(&self, local: IVec3) -> Block {
+        self.blocks[self.index(local)]
+    }
+
+    /// Light at the four corners of the face whose front block is `front`, in [`face_quad`]
+    /// corner order. Each corner averages the front block with the blocks beside it and
+    /// diagonally across the corner, skipping opaque ones. The diagonal block can't be seen
+    /// through when both sides are opaque.
+    fn face_light(&self, front: IVec3, u: usize, v: usize) -> [Vec4; 4] {
+        [(-1, -1), (1, -1), (1, 1), (-1, 1)].map(|(su, sv)| {
+            let mut side_u = front;
+            side_u[u] += su;
+            let mut side_v = front;
+            side_v[v] += sv;
+            let mut diagonal = side_u;
+            diagonal[v] += sv;
+
+            let open_u = !self.get(side_u).is_opaque();
+            let open_v = !self.get(side_v).is_opaque();
+            let open_diagonal = (open_u || open_v) && !self.get(diagonal).is_opaque();
+
+            let mut sum = self.light[self.index(front)];
+            let mut count = 1;
+            for (pos, open) in [
+                (side_u, open_u),
+                (side_v, open_v),
+                (diagonal, open_diagonal),
+            ] {
+                if open {
+                    sum += self.light[self.index(pos)];
+                    count += 1;
+                }
+            }
+            sum.as_vec4() / (count * MAX_LIGHT as u32) as f32
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, vec3, vec4, IVec3, Vec3};
+
+    use crate::{
+        render::mesh::{Mesh, Vertex},
+        state::voxel::{block::Block, light::LightEngine, terrain::Terrain},
+    };
+
+    use super::{mesh_region, MesherOptions};
+
+    /// A stone floor at y = 0 under an open sky, optionally with lava on top of it.
+    fn floor_mesh(lava: Option<IVec3>) -> Mesh {
+        let mut terrain = Terrain::stone_floor(1);
+        if let Some(pos) = lava {
+            terrain.set_block(pos, Block::Lava);
+        }
+        LightEngine::new().light_all(&mut terrain);
+
+        let options = MesherOptions {
+            merge_faces: true,
+            ..Default::default()
+        };
+        mesh_region(&terrain, IVec3::ZERO, IVec3::splat(16), options)
+            .into_iter()
+            .find(|(block, _)| *block == Block::Stone)
+            .unwrap()
+            .1
+    }
+
+    fn top_vertices(mesh: &Mesh) -> Vec<&Vertex> {
+        mesh.vertices()
+            .iter()
+            .filter(|v| v.normal() == Vec3::Y && v.position().y == 1.)
+            .collect()
+    }
+
+    #[test]
+    fn merges_evenly_lit_faces() {
+        assert_eq!(top_vertices(&floor_mesh(None)).len(), 4);
+    }
+
+    #[test]
+    fn averages_light_around_vertices() {
+        let mesh = floor_mesh(Some(ivec3(8, 1, 8)));
+
+        // The four faces around the corner of the lava all average the same four blocks: the
+        // lava, which dims the sky a little, two blocks next to it and one diagonal to it. Its
+        // dimmer green and blue light fades the same way.
+        let corner: Vec<_> = top_vertices(&mesh)
+            .into_iter()
+            .filter(|v| v.position() == vec3(8., 1., 8.))
+            .collect();
+        assert_eq!(corner.len(), 4);
+        for vertex in corner {
+            let expected = Vertex::new(vertex.position(), vertex.normal(), vertex.tex_coords())
+                .with_light(
+                    vec4(
+                        15. + 15. + 15. + 13.,
+                        15. + 14. + 14. + 13.,
+                        9. + 8. + 8. + 7.,
+                        3. + 2. + 2. + 1.,
+                    ) / 60.,
+                );
+            assert_eq!(*vertex, expected);
+        }
+
+        // Faces far from the lava still merge, the ones lit by it don't.
+        assert!(top_vertices(&mesh).len() > 4);
+    }
+}
@@ -1,7 +1,12 @@
+pub mod atmosphere;
 pub mod camera;
+pub mod cluster;
 pub mod mesh;
+pub mod mesher;
 pub mod model;
+pub mod outline;
 pub mod renderer;
 pub mod shader;
 pub mod shadow;
+pub mod ssao;
 pub mod texture;
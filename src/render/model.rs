@@ -1,4 +1,4 @@
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 use glow::*;
 
 use super::mesh::{Mesh, Vertex};
@@ -12,7 +12,7 @@ pub struct Model {
 }
 
 impl Model {
-    pub fn new(gl: &Context, indices: &Vec<u32>, mesh: &Mesh) -> Self {
+    pub fn new(gl: &Context, indices: &[u32], mesh: &Mesh) -> Self {
         let model = unsafe {
             let vao = gl
                 .create_vertex_array()
@@ -27,7 +27,7 @@ impl Model {
             gl.bind_buffer(ELEMENT_ARRAY_BUFFER, Some(ebo));
             let ebo_data = core::slice::from_raw_parts(
                 indices.as_ptr() as *const u8,
-                indices.len() * std::mem::size_of::<u32>(),
+                std::mem::size_of_val(indices),
             );
             gl.buffer_data_u8_slice(ELEMENT_ARRAY_BUFFER, ebo_data, STATIC_DRAW);
 
@@ -61,6 +61,16 @@ impl Model {
                 (std::mem::size_of::<Vec3>() * 2) as i32,
             );
 
+            gl.enable_vertex_attrib_array(3);
+            gl.vertex_attrib_pointer_f32(
+                3,
+                4,
+                FLOAT,
+                false,
+                std::mem::size_of::<Vertex>() as i32,
+                (std::mem::size_of::<Vec3>() * 2 + std::mem::size_of::<Vec2>()) as i32,
+            );
+
             gl.bind_vertex_array(None);
             gl.bind_buffer(ELEMENT_ARRAY_BUFFER, None);
             gl.bind_buffer(ARRAY_BUFFER, None);
@@ -86,6 +96,10 @@ impl Model {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     pub fn update(&self, gl: &Context, mesh: &Mesh) {
         unsafe {
             gl.bind_vertex_array(Some(self.vao));
@@ -0,0 +1,73 @@
+use glam::{vec3, Vec3};
+use glow::*;
+
+/// Edges of a unit cube drawn as lines, used to outline blocks.
+pub struct BoxOutline {
+    vao: VertexArray,
+    vbo: Buffer,
+    len: usize,
+}
+
+impl BoxOutline {
+    pub fn new(gl: &Context) -> Self {
+        let corner = |i: usize| vec3((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32);
+
+        // Every pair of corners that differ in exactly one coordinate is an edge.
+        let mut vertices = Vec::with_capacity(24);
+        for a in 0..8 {
+            for bit in [1, 2, 4] {
+                if a & bit == 0 {
+                    vertices.push(corner(a));
+                    vertices.push(corner(a | bit));
+                }
+            }
+        }
+
+        unsafe {
+            let vao = gl
+                .create_vertex_array()
+                .expect("Couldn't create vertex array.");
+            let vbo = gl.create_buffer().expect("Couldn't create buffer.");
+
+            gl.bind_vertex_array(Some(vao));
+            gl.bind_buffer(ARRAY_BUFFER, Some(vbo));
+
+            let data = core::slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                std::mem::size_of_val(vertices.as_slice()),
+            );
+            gl.buffer_data_u8_slice(ARRAY_BUFFER, data, STATIC_DRAW);
+
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 3, FLOAT, false, std::mem::size_of::<Vec3>() as i32, 0);
+
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(ARRAY_BUFFER, None);
+
+            Self {
+                vao,
+                vbo,
+                len: vertices.len(),
+            }
+        }
+    }
+
+    pub fn vao(&self) -> VertexArray {
+        self.vao
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn drop(&self, gl: &Context) {
+        unsafe {
+            gl.delete_buffer(self.vbo);
+            gl.delete_vertex_array(self.vao);
+        }
+    }
+}
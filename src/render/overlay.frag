@@ -0,0 +1,11 @@
+#version 330 core
+
+in vec2 tex_coords;
+
+out vec4 color;
+
+uniform sampler2D overlay;
+
+void main() {
+    color = texture(overlay, tex_coords);
+}
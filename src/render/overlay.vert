@@ -0,0 +1,14 @@
+#version 330 core
+
+layout (location = 0) in vec3 in_position;
+layout (location = 2) in vec2 in_tex_coords;
+
+out vec2 tex_coords;
+
+uniform mat4 model;
+uniform mat4 projection_view;
+
+void main() {
+    tex_coords = in_tex_coords;
+    gl_Position = projection_view * model * vec4(in_position, 1.0);
+}
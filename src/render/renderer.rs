@@ -1,18 +1,82 @@
-use glam::{ivec2, vec2, vec3, IVec2, Mat3, Mat4, Vec3};
+use glam::{ivec2, vec2, vec3, IVec2, IVec3, Mat3, Mat4, Vec3};
 use glow::*;
 use sdl2::video::{GLContext, Window};
 
 use crate::state::ecs::transform::Transform;
 
 use super::{
+    atmosphere::{Atmosphere, SkyMode},
     camera::Camera,
+    cluster::{ClusterLight, ClusterTextures, LightStats, TILE_SIZE},
     mesh::{Mesh, Quad, Vertex},
     model::Model,
+    outline::BoxOutline,
     shader::ShaderProgram,
     shadow::ShadowMap,
-    texture::{CubeMap, GameTexture, Skybox},
+    ssao::Ssao,
+    texture::{GameTexture, Skybox},
 };
 
+/// The directional light shading the scene, usually the sun.
+#[derive(Clone, Copy, Debug)]
+pub struct SunLight {
+    /// Direction the light travels in.
+    pub direction: Vec3,
+    /// Colour times intensity.
+    pub color: Vec3,
+    /// Whether the shadow map was rendered for this light.
+    pub cast_shadows: bool,
+}
+
+/// What the sky shows at the current time of day.
+#[derive(Clone, Copy, Debug)]
+pub struct SkyState {
+    /// Direction towards the sun, the moon is opposite.
+    pub sun_direction: Vec3,
+    /// Blend from the day to the night cube map, from 0 to 1.
+    pub night: f32,
+    /// Visibility of the stars, from 0 to 1.
+    pub stars: f32,
+    /// Colour of the sky near the horizon, which fog takes on.
+    pub horizon_color: Vec3,
+    /// Strength of the light from the whole sky, used unless the baked procedural sky gives it.
+    pub ambient: f32,
+    pub mode: SkyMode,
+}
+
+/// How much of the atmosphere is drawn over the scene. Light shafts ray march the shadow map
+/// for every pixel, so the lower presets leave them out for integrated graphics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FogQuality {
+    Off,
+    /// Height fog only.
+    Low,
+    /// Height fog and light shafts with a few steps.
+    #[default]
+    Medium,
+    High,
+}
+
+impl FogQuality {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Low,
+            Self::Low => Self::Medium,
+            Self::Medium => Self::High,
+            Self::High => Self::Off,
+        }
+    }
+
+    /// Ray marching steps for the light shafts.
+    fn ray_steps(self) -> i32 {
+        match self {
+            Self::Off | Self::Low => 0,
+            Self::Medium => 12,
+            Self::High => 48,
+        }
+    }
+}
+
 pub struct Renderer {
     gl: Context,
     _gl_context: GLContext,
@@ -20,10 +84,18 @@ pub struct Renderer {
     geometry_shader: ShaderProgram,
     lighting_shader: ShaderProgram,
     skybox_shader: ShaderProgram,
+    fog_shader: ShaderProgram,
+    outline_shader: ShaderProgram,
+    box_outline: BoxOutline,
+    overlay_shader: ShaderProgram,
+    overlay_cube: Model,
     g_buffer: GBuffer,
     screen_quad: Model,
     dims: IVec2,
     shadow_map: ShadowMap,
+    clusters: ClusterTextures,
+    atmosphere: Atmosphere,
+    ssao: Ssao,
 }
 
 impl Renderer {
@@ -44,6 +116,21 @@ impl Renderer {
             include_str!("skybox.vert"),
             include_str!("skybox.frag"),
         );
+        let fog_shader =
+            ShaderProgram::new(&gl, include_str!("lighting.vert"), include_str!("fog.frag"));
+
+        let outline_shader = ShaderProgram::new(
+            &gl,
+            include_str!("outline.vert"),
+            include_str!("outline.frag"),
+        );
+        let box_outline = BoxOutline::new(&gl);
+        let overlay_shader = ShaderProgram::new(
+            &gl,
+            include_str!("overlay.vert"),
+            include_str!("overlay.frag"),
+        );
+        let overlay_cube = Model::new(&gl, &quad_indices, &Mesh::from_cube(1.));
 
         let drawable_size = window.drawable_size();
         let dims = ivec2(drawable_size.0 as i32, drawable_size.1 as i32);
@@ -57,6 +144,8 @@ impl Renderer {
             // gl.enable(MULTISAMPLE);
             gl.disable(BLEND);
             gl.enable(CULL_FACE);
+            // Blends across the edges of the faces of the blurry mips of the sky map.
+            gl.enable(TEXTURE_CUBE_MAP_SEAMLESS);
             // gl.polygon_mode(FRONT_AND_BACK, LINE);
 
             gl.viewport(0, 0, dims.x, dims.y);
@@ -67,8 +156,27 @@ impl Renderer {
         lighting_shader.set_int(&gl, "g_normal", 1);
         lighting_shader.set_int(&gl, "g_albedo_spec", 2);
         lighting_shader.set_int(&gl, "shadow_map", 3);
+        lighting_shader.set_int(&gl, "g_light", 4);
+        lighting_shader.set_int(&gl, "point_lights", 5);
+        lighting_shader.set_int(&gl, "light_clusters", 6);
+        lighting_shader.set_int(&gl, "light_indices", 7);
+        lighting_shader.set_int(&gl, "sky_map", 8);
+        lighting_shader.set_int(&gl, "ssao", 9);
+        lighting_shader.set_int(&gl, "tile_size", TILE_SIZE);
+
+        fog_shader.set_used(&gl);
+        fog_shader.set_int(&gl, "g_position", 0);
+        fog_shader.set_int(&gl, "g_normal", 1);
+        fog_shader.set_int(&gl, "shadow_map", 2);
+
+        skybox_shader.set_used(&gl);
+        skybox_shader.set_int(&gl, "day_sky", 0);
+        skybox_shader.set_int(&gl, "night_sky", 1);
 
         let shadow_map = ShadowMap::new(&gl);
+        let clusters = ClusterTextures::new(&gl);
+        let atmosphere = Atmosphere::new(&gl);
+        let ssao = Ssao::new(&gl, dims);
 
         Self {
             gl,
@@ -77,10 +185,18 @@ impl Renderer {
             geometry_shader,
             lighting_shader,
             skybox_shader,
+            fog_shader,
+            outline_shader,
+            box_outline,
+            overlay_shader,
+            overlay_cube,
             g_buffer,
             screen_quad,
             dims,
             shadow_map,
+            clusters,
+            atmosphere,
+            ssao,
         }
     }
 
@@ -97,7 +213,15 @@ impl Renderer {
     }
 
     pub fn create_model(&self, mesh: &Mesh) -> Model {
-        Model::new(&self.gl, &self.quad_indices, mesh)
+        if mesh.vertices().len() <= self.quad_indices.len() / 6 * 4 {
+            Model::new(&self.gl, &self.quad_indices, mesh)
+        } else {
+            Model::new(&self.gl, &create_quad_indices(mesh.vertices().len()), mesh)
+        }
+    }
+
+    pub fn delete_model(&self, model: &Model) {
+        model.drop(&self.gl);
     }
 
     pub fn create_texture(&self, path: &str) -> GameTexture {
@@ -139,7 +263,32 @@ impl Renderer {
         }
     }
 
-    pub fn render_shading(&self, camera: &Camera) {
+    /// Shades the G-buffer with the sun, if there is one, light from the sky, and `lights`. With
+    /// `ambient_occlusion`, the light from the sky is darkened in corners and crevices first.
+    pub fn render_shading(
+        &mut self,
+        camera: &Camera,
+        sun: Option<&SunLight>,
+        sky: &SkyState,
+        lights: &[ClusterLight],
+        ambient_occlusion: bool,
+    ) {
+        let sun = sun.copied().unwrap_or(SunLight {
+            direction: Vec3::NEG_Y,
+            color: Vec3::ZERO,
+            cast_shadows: false,
+        });
+        let cluster_dims = self.clusters.update(&self.gl, lights, camera, self.dims);
+        if ambient_occlusion {
+            self.ssao.render(
+                &self.gl,
+                &self.screen_quad,
+                self.g_buffer.position,
+                self.g_buffer.normal,
+                camera,
+            );
+        }
+
         unsafe {
             self.gl.viewport(0, 0, self.dims.x, self.dims.y);
             self.gl.bind_framebuffer(FRAMEBUFFER, None);
@@ -159,22 +308,52 @@ impl Renderer {
                 .bind_texture(TEXTURE_2D, Some(self.g_buffer.albedo_spec));
             self.gl.active_texture(TEXTURE3);
             self.gl
-                .bind_texture(TEXTURE_2D, Some(self.shadow_map.depth_map()));
+                .bind_texture(TEXTURE_2D_ARRAY, Some(self.shadow_map.depth_map()));
+            self.gl.active_texture(TEXTURE4);
+            self.gl.bind_texture(TEXTURE_2D, Some(self.g_buffer.light));
+            self.clusters.bind(&self.gl, 5);
+            self.atmosphere.bind_sky_map(&self.gl, 8);
+            self.gl.active_texture(TEXTURE9);
+            self.gl
+                .bind_texture(TEXTURE_2D, Some(self.ssao.occlusion()));
 
             self.gl.disable(DEPTH_TEST);
 
             self.lighting_shader
                 .set_vec3(&self.gl, "view_pos", camera.pos());
-            self.lighting_shader.set_mat4(
+            self.shadow_map
+                .set_cascade_uniforms(&self.gl, &self.lighting_shader);
+            for (i, cascade) in self.shadow_map.cascades().iter().enumerate() {
+                self.lighting_shader.set_float(
+                    &self.gl,
+                    &format!("cascade_texel_sizes[{}]", i),
+                    cascade.texel_size,
+                );
+            }
+            self.lighting_shader
+                .set_vec3(&self.gl, "light_dir", sun.direction);
+            self.lighting_shader
+                .set_vec3(&self.gl, "sun_color", sun.color);
+            self.lighting_shader
+                .set_int(&self.gl, "sun_shadows", sun.cast_shadows as i32);
+            self.lighting_shader
+                .set_float(&self.gl, "ambient", sky.ambient);
+            self.lighting_shader
+                .set_int(&self.gl, "ambient_occlusion", ambient_occlusion as i32);
+            self.lighting_shader.set_int(
                 &self.gl,
-                "shadow_projection_view",
-                Mat4::from_translation(vec3(0.5, 0.5, 0.5))
-                    * Mat4::from_scale(vec3(0.5, 0.5, 0.5))
-                    * self.shadow_map.projection_view(),
+                "sky_map_ambient",
+                (sky.mode == SkyMode::ProceduralAmbient) as i32,
             );
             self.lighting_shader
-                .set_vec3(&self.gl, "light_dir", self.shadow_map.light_dir());
-            self.render_screen_quad();
+                .set_mat4(&self.gl, "view", camera.view());
+            self.lighting_shader
+                .set_ivec3(&self.gl, "cluster_dims", cluster_dims);
+            self.lighting_shader
+                .set_float(&self.gl, "z_near", camera.near());
+            self.lighting_shader
+                .set_float(&self.gl, "z_far", camera.far());
+            self.render_screen_quad(&self.lighting_shader);
 
             self.gl
                 .bind_framebuffer(READ_FRAMEBUFFER, Some(self.g_buffer.framebuffer));
@@ -196,9 +375,72 @@ impl Renderer {
         }
     }
 
-    fn render_screen_quad(&self) {
+    /// Draws height fog over the shaded scene and the sky, and with `quality` high enough, shafts
+    /// of light from the sun where its shadow map shows it reaching.
+    pub fn render_fog(
+        &self,
+        camera: &Camera,
+        sun: Option<&SunLight>,
+        sky: &SkyState,
+        quality: FogQuality,
+    ) {
+        if quality == FogQuality::Off {
+            return;
+        }
+        let (sun, ray_steps) = match sun {
+            Some(sun) if sun.cast_shadows => (*sun, quality.ray_steps()),
+            Some(sun) => (*sun, 0),
+            None => (
+                SunLight {
+                    direction: Vec3::NEG_Y,
+                    color: Vec3::ZERO,
+                    cast_shadows: false,
+                },
+                0,
+            ),
+        };
+
         unsafe {
-            self.lighting_shader.set_used(&self.gl);
+            self.fog_shader.set_used(&self.gl);
+            self.gl.active_texture(TEXTURE0);
+            self.gl
+                .bind_texture(TEXTURE_2D, Some(self.g_buffer.position));
+            self.gl.active_texture(TEXTURE1);
+            self.gl.bind_texture(TEXTURE_2D, Some(self.g_buffer.normal));
+            self.gl.active_texture(TEXTURE2);
+            self.gl
+                .bind_texture(TEXTURE_2D_ARRAY, Some(self.shadow_map.depth_map()));
+
+            self.fog_shader.set_vec3(&self.gl, "view_pos", camera.pos());
+            self.fog_shader.set_mat4(&self.gl, "view", camera.view());
+            self.fog_shader.set_mat4(
+                &self.gl,
+                "inverse_projection_view",
+                camera.projection_view().inverse(),
+            );
+            self.fog_shader.set_float(&self.gl, "z_far", camera.far());
+            self.fog_shader
+                .set_vec3(&self.gl, "light_dir", sun.direction);
+            self.fog_shader.set_vec3(&self.gl, "sun_color", sun.color);
+            self.fog_shader
+                .set_vec3(&self.gl, "horizon_color", sky.horizon_color);
+            self.fog_shader.set_int(&self.gl, "ray_steps", ray_steps);
+            self.shadow_map
+                .set_cascade_uniforms(&self.gl, &self.fog_shader);
+
+            // The fog comes out premultiplied by its alpha, the light shafts are added.
+            self.gl.disable(DEPTH_TEST);
+            self.gl.enable(BLEND);
+            self.gl.blend_func(ONE, ONE_MINUS_SRC_ALPHA);
+            self.render_screen_quad(&self.fog_shader);
+            self.gl.disable(BLEND);
+            self.gl.enable(DEPTH_TEST);
+        }
+    }
+
+    fn render_screen_quad(&self, shader: &ShaderProgram) {
+        unsafe {
+            shader.set_used(&self.gl);
 
             self.gl.bind_vertex_array(Some(self.screen_quad.vao()));
             self.gl
@@ -220,8 +462,18 @@ impl Renderer {
         }
     }
 
-    pub fn prepare_shadow_map(&mut self, light_dir: &Vec3) {
-        self.shadow_map.prepare(&self.gl, light_dir);
+    pub fn light_stats(&self) -> LightStats {
+        self.clusters.stats()
+    }
+
+    /// Fits the shadow cascades to `camera`, after `prepare` has updated it. Every cascade is
+    /// rendered separately, from `prepare_shadow_cascade` on.
+    pub fn prepare_shadow_map(&mut self, camera: &Camera, light_dir: &Vec3) {
+        self.shadow_map.prepare(&self.gl, camera, light_dir);
+    }
+
+    pub fn prepare_shadow_cascade(&self, cascade: usize) {
+        self.shadow_map.prepare_cascade(&self.gl, cascade);
     }
 
     pub fn render_shadow_map(&self, model: &Model, transform: &Transform) {
@@ -241,6 +493,60 @@ impl Renderer {
         }
     }
 
+    /// Draws the edges of the block at `pos` over the shaded scene, hidden where something is in
+    /// front of them.
+    pub fn render_block_outline(&self, camera: &Camera, pos: IVec3) {
+        // Slightly larger than the block so the lines don't fight with its faces for depth.
+        let margin = 0.002;
+        let model = Mat4::from_translation(pos.as_vec3() - margin)
+            * Mat4::from_scale(Vec3::splat(1. + 2. * margin));
+
+        unsafe {
+            self.outline_shader.set_used(&self.gl);
+            self.outline_shader
+                .set_mat4(&self.gl, "projection_view", camera.projection_view());
+            self.outline_shader.set_mat4(&self.gl, "model", model);
+
+            self.gl.enable(DEPTH_TEST);
+            self.gl.enable(BLEND);
+            self.gl.blend_func(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
+
+            self.gl.bind_vertex_array(Some(self.box_outline.vao()));
+            self.gl.draw_arrays(LINES, 0, self.box_outline.len() as i32);
+            self.gl.bind_vertex_array(None);
+
+            self.gl.disable(BLEND);
+        }
+    }
+
+    /// Draws `texture` blended over every face of the block at `pos`, like mining cracks.
+    pub fn render_block_overlay(&self, camera: &Camera, pos: IVec3, texture: &GameTexture) {
+        let model =
+            Mat4::from_translation(pos.as_vec3() + 0.5) * Mat4::from_scale(Vec3::splat(1.002));
+
+        unsafe {
+            self.overlay_shader.set_used(&self.gl);
+            self.overlay_shader
+                .set_mat4(&self.gl, "projection_view", camera.projection_view());
+            self.overlay_shader.set_mat4(&self.gl, "model", model);
+            self.overlay_shader.set_int(&self.gl, "overlay", 0);
+            texture.bind(&self.gl, 0);
+
+            self.gl.enable(DEPTH_TEST);
+            self.gl.depth_mask(false);
+            self.gl.enable(BLEND);
+            self.gl.blend_func(SRC_ALPHA, ONE_MINUS_SRC_ALPHA);
+
+            self.gl.bind_vertex_array(Some(self.overlay_cube.vao()));
+            self.gl
+                .draw_elements(TRIANGLES, self.overlay_cube.len() as i32, UNSIGNED_INT, 0);
+            self.gl.bind_vertex_array(None);
+
+            self.gl.disable(BLEND);
+            self.gl.depth_mask(true);
+        }
+    }
+
     pub fn create_skybox(&self) -> Skybox {
         let cube = Mesh::from_cube(2.);
         let model = self.create_model(&cube);
@@ -248,24 +554,40 @@ impl Renderer {
         Skybox::new(&self.gl, model)
     }
 
-    pub fn render_skybox(&self, model: &Model, camera: &Camera, cube_map: &CubeMap) {
+    /// Bakes the procedural sky for lighting the scene when it's due, before the shading pass.
+    pub fn bake_sky(&mut self, skybox: &Skybox, sky: &SkyState) {
+        self.atmosphere.bake(&self.gl, skybox.model(), sky);
+    }
+
+    pub fn render_skybox(&self, skybox: &Skybox, camera: &Camera, sky: &SkyState) {
         unsafe {
             self.gl.depth_func(LEQUAL);
-            self.skybox_shader.set_used(&self.gl);
-            let view = Mat4::from_mat3(Mat3::from_mat4(camera.view()));
-            let projection_view = camera.projection() * view;
-            self.skybox_shader
-                .set_mat4(&self.gl, "projection_view", projection_view);
-
-            cube_map.bind(&self.gl);
-
             self.gl.enable(DEPTH_TEST);
             self.gl.cull_face(FRONT);
 
-            self.gl.bind_vertex_array(Some(model.vao()));
-            self.gl
-                .draw_elements(TRIANGLES, model.len() as i32, UNSIGNED_INT, 0);
-            self.gl.bind_vertex_array(None);
+            let view = Mat4::from_mat3(Mat3::from_mat4(camera.view()));
+            let projection_view = camera.projection() * view;
+            let model = skybox.model();
+            if sky.mode.is_procedural() {
+                self.atmosphere
+                    .render(&self.gl, model, projection_view, sky);
+            } else {
+                self.skybox_shader.set_used(&self.gl);
+                self.skybox_shader
+                    .set_mat4(&self.gl, "projection_view", projection_view);
+                self.skybox_shader
+                    .set_vec3(&self.gl, "sun_dir", sky.sun_direction);
+                self.skybox_shader.set_float(&self.gl, "night", sky.night);
+                self.skybox_shader.set_float(&self.gl, "stars", sky.stars);
+
+                skybox.day().bind(&self.gl, 0);
+                skybox.night().bind(&self.gl, 1);
+
+                self.gl.bind_vertex_array(Some(model.vao()));
+                self.gl
+                    .draw_elements(TRIANGLES, model.len() as i32, UNSIGNED_INT, 0);
+                self.gl.bind_vertex_array(None);
+            }
 
             self.gl.depth_func(LESS);
             self.gl.cull_face(BACK);
@@ -300,6 +622,9 @@ struct GBuffer {
     position: Texture,
     normal: Texture,
     albedo_spec: Texture,
+    /// Sky light in the red channel and red, green and blue block light in the green, blue and
+    /// alpha channels.
+    light: Texture,
 }
 
 unsafe fn create_g_buffer(gl: &Context, dims: &IVec2) -> GBuffer {
@@ -312,8 +637,14 @@ unsafe fn create_g_buffer(gl: &Context, dims: &IVec2) -> GBuffer {
     let g_position = create_color_buffer(gl, dims, RGBA16F, FLOAT, COLOR_ATTACHMENT0);
     let g_normal = create_color_buffer(gl, dims, RGBA16F, FLOAT, COLOR_ATTACHMENT1);
     let g_albedo_spec = create_color_buffer(gl, dims, RGBA, UNSIGNED_BYTE, COLOR_ATTACHMENT2);
+    let g_light = create_color_buffer(gl, dims, RGBA, UNSIGNED_BYTE, COLOR_ATTACHMENT3);
 
-    let attachments = [COLOR_ATTACHMENT0, COLOR_ATTACHMENT1, COLOR_ATTACHMENT2];
+    let attachments = [
+        COLOR_ATTACHMENT0,
+        COLOR_ATTACHMENT1,
+        COLOR_ATTACHMENT2,
+        COLOR_ATTACHMENT3,
+    ];
 
     gl.draw_buffers(&attachments);
 
@@ -343,6 +674,7 @@ unsafe fn create_g_buffer(gl: &Context, dims: &IVec2) -> GBuffer {
         position: g_position,
         normal: g_normal,
         albedo_spec: g_albedo_spec,
+        light: g_light,
     }
 }
 
@@ -377,7 +709,7 @@ unsafe fn create_color_buffer(
     texture
 }
 
-fn create_screen_quad(gl: &Context, quad_indices: &Vec<u32>) -> Model {
+fn create_screen_quad(gl: &Context, quad_indices: &[u32]) -> Model {
     let mut screen_mesh = Mesh::new();
     let temp_normal = Vec3::ZERO;
     screen_mesh.push_quad(Quad::new(
@@ -1,4 +1,4 @@
-use glam::{Mat4, Vec3};
+use glam::{IVec3, Mat4, Vec3};
 use glow::*;
 
 pub struct ShaderProgram {
@@ -22,7 +22,7 @@ impl ShaderProgram {
                     .create_shader(*shader_type)
                     .expect("Couldn't create shader.");
 
-                gl.shader_source(shader, *shader_source);
+                gl.shader_source(shader, shader_source);
                 gl.compile_shader(shader);
                 if !gl.get_shader_compile_status(shader) {
                     panic!(
@@ -100,4 +100,24 @@ impl ShaderProgram {
             }
         }
     }
+
+    pub fn set_float(&self, gl: &Context, name: &str, value: f32) {
+        unsafe {
+            if let Some(location) = gl.get_uniform_location(self.program, name) {
+                gl.uniform_1_f32(Some(&location), value);
+            } else {
+                panic!("Couldn't find uniform: {}", name);
+            }
+        }
+    }
+
+    pub fn set_ivec3(&self, gl: &Context, name: &str, value: IVec3) {
+        unsafe {
+            if let Some(location) = gl.get_uniform_location(self.program, name) {
+                gl.uniform_3_i32(Some(&location), value.x, value.y, value.z);
+            } else {
+                panic!("Couldn't find uniform: {}", name);
+            }
+        }
+    }
 }
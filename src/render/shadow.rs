@@ -3,21 +3,38 @@ use glow::*;
 
 use crate::state::ecs::transform::Transform;
 
-use super::{model::Model, shader::ShaderProgram};
-
-const SHADOW_WIDTH: i32 = 4096;
-const SHADOW_HEIGHT: i32 = 4096;
+use super::{camera::Camera, model::Model, shader::ShaderProgram};
+
+/// Number of shadow maps the view is split into along its depth, `CASCADES` in `lighting.frag`.
+pub const SHADOW_CASCADES: usize = 4;
+/// Width and height of each cascade's shadow map.
+const SHADOW_SIZE: i32 = 2048;
+/// Distance from the camera that shadows reach.
+const SHADOW_DISTANCE: f32 = 128.;
+/// Blend between evenly spaced cascades at 0 and logarithmically spaced ones at 1. Logarithmic
+/// splits keep the texel size on screen about the same but make the nearest cascade tiny.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// How far towards the light from a cascade shadows are still cast from, in blocks.
+const CASTER_DISTANCE: f32 = 32.;
 
 #[derive(Clone, Copy, Debug)]
 pub struct CastShadow;
 
+/// One slice of the view with its own shadow map.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cascade {
+    /// Maps world space to the cascade's light clip space.
+    pub projection_view: Mat4,
+    /// View space depth where the cascade ends.
+    pub split: f32,
+    /// World space size of a shadow map texel.
+    pub texel_size: f32,
+}
+
 pub struct ShadowMap {
     framebuffer: Framebuffer,
     depth_map: Texture,
-    projection: Mat4,
-    view: Mat4,
-    projection_view: Mat4,
-    light_dir: Vec3,
+    cascades: [Cascade; SHADOW_CASCADES],
     shader: ShaderProgram,
 }
 
@@ -29,36 +46,37 @@ impl ShadowMap {
                 .expect("Couldn't create framebuffer.");
 
             let depth_map = gl.create_texture().expect("Couldn't create texture.");
-            gl.bind_texture(TEXTURE_2D, Some(depth_map));
+            gl.bind_texture(TEXTURE_2D_ARRAY, Some(depth_map));
 
-            gl.tex_image_2d(
-                TEXTURE_2D,
+            gl.tex_image_3d(
+                TEXTURE_2D_ARRAY,
                 0,
                 DEPTH_COMPONENT as i32,
-                SHADOW_WIDTH,
-                SHADOW_HEIGHT,
+                SHADOW_SIZE,
+                SHADOW_SIZE,
+                SHADOW_CASCADES as i32,
                 0,
                 DEPTH_COMPONENT,
                 FLOAT,
                 None,
             );
 
-            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_S, REPEAT as i32);
-            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_T, REPEAT as i32);
+            gl.tex_parameter_i32(TEXTURE_2D_ARRAY, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+            gl.tex_parameter_i32(TEXTURE_2D_ARRAY, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
 
             gl.tex_parameter_i32(
-                TEXTURE_2D,
+                TEXTURE_2D_ARRAY,
                 TEXTURE_COMPARE_MODE,
                 COMPARE_REF_TO_TEXTURE as i32,
             );
-            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_COMPARE_FUNC, LEQUAL as i32);
+            gl.tex_parameter_i32(TEXTURE_2D_ARRAY, TEXTURE_COMPARE_FUNC, LEQUAL as i32);
 
-            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, LINEAR as i32);
-            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, LINEAR as i32);
+            gl.tex_parameter_i32(TEXTURE_2D_ARRAY, TEXTURE_MIN_FILTER, LINEAR as i32);
+            gl.tex_parameter_i32(TEXTURE_2D_ARRAY, TEXTURE_MAG_FILTER, LINEAR as i32);
 
             gl.bind_framebuffer(FRAMEBUFFER, Some(framebuffer));
 
-            gl.framebuffer_texture(FRAMEBUFFER, DEPTH_ATTACHMENT, Some(depth_map), 0);
+            gl.framebuffer_texture_layer(FRAMEBUFFER, DEPTH_ATTACHMENT, Some(depth_map), 0, 0);
             gl.draw_buffer(NONE);
             gl.read_buffer(NONE);
 
@@ -68,81 +86,70 @@ impl ShadowMap {
 
             gl.bind_framebuffer(FRAMEBUFFER, None);
 
-            let projection = Mat4::orthographic_rh_gl(-10., 10., -10., 10., 1., 100.);
-            let light_dir = vec3(0.5, -1., -0.8).normalize();
-            let view = Mat4::look_at_rh(
-                vec3(0., 0., 0.) - light_dir * 10.,
-                vec3(0., 0., 0.),
-                vec3(0., 1., 0.),
-            );
-            let projection_view = projection * view;
-
             let shader =
                 ShaderProgram::new(gl, include_str!("shadow.vert"), include_str!("shadow.frag"));
 
             Self {
                 framebuffer,
                 depth_map,
-                projection,
-                view,
-                projection_view,
-                light_dir,
+                cascades: Default::default(),
                 shader,
             }
         }
     }
 
-    pub fn projection_view(&self) -> Mat4 {
-        self.projection_view
-    }
-
-    fn get_view_matrix(&self, light_dir: &Vec3) -> Mat4 {
-        Mat4::look_at_rh(
-            vec3(0., 0., 0.) - *light_dir * 10.,
-            vec3(0., 0., 0.),
-            vec3(0., 1., 0.),
-        )
-    }
-
-    fn get_projection_matrix(&self) -> Mat4 {
-        Mat4::orthographic_rh_gl(-10., 10., -10., 10., 1., 100.)
-    }
-
-    pub fn update_view_matrix(&mut self, light_dir: &Vec3) {
-        self.view = self.get_view_matrix(light_dir);
-    }
-
-    pub fn update_projection_matrix(&mut self) {
-        self.projection = self.get_projection_matrix();
+    pub fn cascades(&self) -> &[Cascade; SHADOW_CASCADES] {
+        &self.cascades
     }
 
-    pub fn update_projection_view_matrix(&mut self, light_dir: &Vec3) {
-        self.update_view_matrix(light_dir);
-        self.update_projection_matrix();
-        self.projection_view = self.projection * self.view;
+    /// Sets the `cascade_projection_views` and `cascade_splits` arrays of `shader`.
+    pub fn set_cascade_uniforms(&self, gl: &Context, shader: &ShaderProgram) {
+        for (i, cascade) in self.cascades.iter().enumerate() {
+            shader.set_mat4(
+                gl,
+                &format!("cascade_projection_views[{}]", i),
+                cascade.projection_view,
+            );
+            shader.set_float(gl, &format!("cascade_splits[{}]", i), cascade.split);
+        }
     }
 
     pub fn depth_map(&self) -> Texture {
         self.depth_map
     }
 
-    pub fn light_dir(&self) -> Vec3 {
-        self.light_dir
-    }
+    /// Fits the cascades to the camera's view, which must be up to date.
+    pub fn prepare(&mut self, gl: &Context, camera: &Camera, light_dir: &Vec3) {
+        self.cascades = fit_cascades(camera, *light_dir);
 
-    pub fn prepare(&mut self, gl: &Context, light_dir: &Vec3) {
         unsafe {
-            gl.viewport(0, 0, SHADOW_WIDTH, SHADOW_HEIGHT);
+            gl.viewport(0, 0, SHADOW_SIZE, SHADOW_SIZE);
             gl.bind_framebuffer(FRAMEBUFFER, Some(self.framebuffer));
-            gl.clear(DEPTH_BUFFER_BIT);
+            gl.cull_face(FRONT);
+            // Casters between the light and the near plane are flattened onto it instead of
+            // clipped away.
+            gl.enable(DEPTH_CLAMP);
+        }
+    }
 
-            self.light_dir = *light_dir;
-            self.update_projection_view_matrix(light_dir);
+    /// Starts rendering the shadow casters into `cascade`.
+    pub fn prepare_cascade(&self, gl: &Context, cascade: usize) {
+        unsafe {
+            gl.framebuffer_texture_layer(
+                FRAMEBUFFER,
+                DEPTH_ATTACHMENT,
+                Some(self.depth_map),
+                0,
+                cascade as i32,
+            );
+            gl.clear(DEPTH_BUFFER_BIT);
 
             self.shader.set_used(gl);
-            self.shader
-                .set_mat4(gl, "projection_view", self.projection_view);
-            gl.cull_face(FRONT);
+            self.shader.set_mat4(
+                gl,
+                "projection_view",
+                self.cascades[cascade].projection_view,
+            );
         }
     }
 
@@ -159,8 +166,158 @@ impl ShadowMap {
 
     pub fn end(&self, gl: &Context) {
         unsafe {
+            gl.disable(DEPTH_CLAMP);
             gl.cull_face(BACK);
             gl.bind_framebuffer(FRAMEBUFFER, None);
         }
     }
 }
+
+/// View space depths where the cascades end, spaced between evenly and logarithmically.
+fn cascade_splits(near: f32, far: f32) -> [f32; SHADOW_CASCADES] {
+    let mut splits = [0.; SHADOW_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate() {
+        let t = (i + 1) as f32 / SHADOW_CASCADES as f32;
+        let uniform = near + (far - near) * t;
+        let logarithmic = near * (far / near).powf(t);
+        *split = uniform + (logarithmic - uniform) * SPLIT_LAMBDA;
+    }
+    splits
+}
+
+/// Splits the camera's view into cascades and fits a shadow map around each.
+///
+/// Each cascade's shadow map covers a sphere around its slice of the view, which keeps the same
+/// size however the camera turns, and is moved in whole texels, so the edges of shadows stay put
+/// instead of crawling as the camera moves.
+fn fit_cascades(camera: &Camera, light_dir: Vec3) -> [Cascade; SHADOW_CASCADES] {
+    let far = camera.far().min(SHADOW_DISTANCE);
+    let splits = cascade_splits(camera.near(), far);
+
+    // Corners of the near and far planes of the camera's view.
+    let inverse = camera.projection_view().inverse();
+    let mut near_corners = [Vec3::ZERO; 4];
+    let mut far_corners = [Vec3::ZERO; 4];
+    for (i, (x, y)) in [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+        .into_iter()
+        .enumerate()
+    {
+        near_corners[i] = inverse.project_point3(vec3(x, y, -1.));
+        far_corners[i] = inverse.project_point3(vec3(x, y, 1.));
+    }
+
+    let light_dir = light_dir.normalize();
+    let up = if light_dir.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    };
+    let light_view = Mat4::look_at_rh(Vec3::ZERO, light_dir, up);
+
+    let depth_range = camera.far() - camera.near();
+    let mut cascades = [Cascade::default(); SHADOW_CASCADES];
+    let mut start = camera.near();
+    for (cascade, &split) in cascades.iter_mut().zip(&splits) {
+        // View space depth changes linearly along the edges of the view.
+        let corners = near_corners
+            .iter()
+            .zip(&far_corners)
+            .flat_map(|(near, far)| {
+                [
+                    near.lerp(*far, (start - camera.near()) / depth_range),
+                    near.lerp(*far, (split - camera.near()) / depth_range),
+                ]
+            });
+        let corners: Vec<Vec3> = corners.collect();
+        let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+        let radius = corners
+            .iter()
+            .map(|corner| corner.distance(center))
+            .fold(0., f32::max);
+        // Rounded up so float error doesn't change the size from frame to frame.
+        let radius = (radius * 16.).ceil() / 16.;
+
+        let texel_size = radius * 2. / SHADOW_SIZE as f32;
+        let mut center = light_view.transform_point3(center);
+        center.x = (center.x / texel_size).floor() * texel_size;
+        center.y = (center.y / texel_size).floor() * texel_size;
+
+        // The light looks down -Z, so depths in front of it are negated.
+        let projection = Mat4::orthographic_rh_gl(
+            center.x - radius,
+            center.x + radius,
+            center.y - radius,
+            center.y + radius,
+            -center.z - radius - CASTER_DISTANCE,
+            -center.z + radius,
+        );
+        *cascade = Cascade {
+            projection_view: projection * light_view,
+            split,
+            texel_size,
+        };
+        start = split;
+    }
+    cascades
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Vec3};
+
+    use crate::render::camera::Camera;
+
+    use super::{cascade_splits, fit_cascades, SHADOW_CASCADES, SHADOW_SIZE};
+
+    fn camera(pos: Vec3) -> Camera {
+        let mut camera = Camera::new(pos);
+        camera.set_rotation(30., -20.);
+        camera.update_projection_view_matrix(16. / 9.);
+        camera
+    }
+
+    #[test]
+    fn splits_grow_up_to_the_shadow_distance() {
+        let splits = cascade_splits(0.1, 128.);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(splits[0] > 0.1);
+        assert!((splits[SHADOW_CASCADES - 1] - 128.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn cascades_cover_their_slice_of_the_view() {
+        let camera = camera(vec3(5., 20., -3.));
+        let light_dir = vec3(0.3, -1., 0.4);
+        let cascades = fit_cascades(&camera, light_dir);
+
+        let inverse_view = camera.view().inverse();
+        let mut start = camera.near();
+        for cascade in cascades {
+            // Points along the view's centre line at the cascade's near and far depth.
+            for depth in [start, cascade.split] {
+                let point = inverse_view.transform_point3(vec3(0., 0., -depth));
+                let clip = cascade.projection_view.project_point3(point);
+                assert!(clip.abs().max_element() <= 1., "{:?} at {}", clip, depth);
+            }
+            start = cascade.split;
+        }
+    }
+
+    #[test]
+    fn cascades_move_in_whole_texels() {
+        let light_dir = vec3(0.3, -1., 0.4);
+        let before = fit_cascades(&camera(vec3(5., 20., -3.)), light_dir);
+        let after = fit_cascades(&camera(vec3(5.37, 20.1, -2.81)), light_dir);
+
+        let point = vec3(11., 17., 4.);
+        for (before, after) in before.iter().zip(&after) {
+            assert_eq!(before.texel_size, after.texel_size);
+            let shift = (after.projection_view.project_point3(point)
+                - before.projection_view.project_point3(point))
+                * SHADOW_SIZE as f32
+                / 2.;
+            assert!((shift.x - shift.x.round()).abs() < 1e-2, "{:?}", shift);
+            assert!((shift.y - shift.y.round()).abs() < 1e-2, "{:?}", shift);
+        }
+    }
+}
@@ -4,15 +4,37 @@ in vec3 tex_coords;
 
 out vec4 color;
 
-uniform samplerCube skybox;
+uniform samplerCube day_sky;
+uniform samplerCube night_sky;
+// Direction towards the sun, the moon is opposite.
+uniform vec3 sun_dir;
+uniform float night;
+uniform float stars;
+
+#define SUN_SIZE 0.9995
+#define MOON_SIZE 0.9997
+#define STAR_DENSITY 0.997
+#define STAR_GRID 200.0
+
+float hash(vec3 p) {
+    return fract(sin(dot(p, vec3(12.9898, 78.233, 37.719))) * 43758.5453);
+}
 
 void main() {
-    color = texture(skybox, tex_coords);
+    vec3 dir = normalize(tex_coords);
+    vec3 lighting = mix(texture(day_sky, tex_coords).rgb, texture(night_sky, tex_coords).rgb, night);
+
+    // Stars are random cells of a grid on the sky, twinkling isn't worth the flicker.
+    float star = step(STAR_DENSITY, hash(floor(dir * STAR_GRID)));
+    lighting += vec3(star * stars * max(dir.y, 0.0));
 
-    vec3 lighting = color.rgb;
+    float sun = smoothstep(SUN_SIZE - 0.0002, SUN_SIZE, dot(dir, sun_dir));
+    lighting += vec3(4.0, 3.6, 3.0) * sun;
+    float moon = smoothstep(MOON_SIZE - 0.0001, MOON_SIZE, dot(dir, -sun_dir));
+    lighting += vec3(0.8, 0.85, 1.0) * moon;
 
     lighting = lighting / (lighting + vec3(1.0));
     lighting = pow(lighting, vec3(1.0 / 2.2));
 
     color = vec4(lighting, 1.0);
-}
\ No newline at end of file
+}
@@ -0,0 +1,59 @@
+#version 330 core
+
+in vec2 tex_coords;
+
+out float occlusion;
+
+uniform sampler2D g_position;
+uniform sampler2D g_normal;
+// Random rotations of the kernel, tiled over the screen.
+uniform sampler2D noise;
+
+#define KERNEL_SIZE 32
+// Offsets in the hemisphere around +Z, see `ssao.rs`.
+uniform vec3 samples[KERNEL_SIZE];
+uniform mat4 view;
+uniform mat4 projection;
+
+// Radius of the hemisphere in blocks.
+#define RADIUS 0.6
+#define BIAS 0.025
+#define NOISE_SIZE 4.0
+
+void main() {
+    vec3 world_normal = texture(g_normal, tex_coords).rgb;
+    // Nothing was drawn here, the sky isn't occluded.
+    if (length(world_normal) < 0.5) {
+        occlusion = 1.0;
+        return;
+    }
+
+    vec3 position = (view * vec4(texture(g_position, tex_coords).rgb, 1.0)).xyz;
+    vec3 normal = normalize(mat3(view) * world_normal);
+
+    vec2 noise_scale = vec2(textureSize(g_position, 0)) / NOISE_SIZE;
+    vec3 random = vec3(texture(noise, tex_coords * noise_scale).xy, 0.0);
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    vec3 bitangent = cross(normal, tangent);
+    mat3 tbn = mat3(tangent, bitangent, normal);
+
+    float occluded = 0.0;
+    for (int i = 0; i < KERNEL_SIZE; i++) {
+        vec3 sample_position = position + tbn * samples[i] * RADIUS;
+
+        vec4 offset = projection * vec4(sample_position, 1.0);
+        vec2 sample_coords = offset.xy / offset.w * 0.5 + 0.5;
+        bool on_screen = all(greaterThanEqual(sample_coords, vec2(0.0))) &&
+            all(lessThanEqual(sample_coords, vec2(1.0)));
+        if (!on_screen || length(texture(g_normal, sample_coords).rgb) < 0.5) {
+            continue;
+        }
+
+        float depth = (view * vec4(texture(g_position, sample_coords).rgb, 1.0)).z;
+        // Geometry much further in front than the radius doesn't occlude, it's just in the way.
+        float in_range = smoothstep(0.0, 1.0, RADIUS / abs(position.z - depth));
+        occluded += (depth >= sample_position.z + BIAS ? 1.0 : 0.0) * in_range;
+    }
+
+    occlusion = 1.0 - occluded / float(KERNEL_SIZE);
+}
@@ -0,0 +1,236 @@
+use glam::{vec3, IVec2, Vec3};
+use glow::*;
+
+use super::{camera::Camera, model::Model, shader::ShaderProgram};
+
+/// Samples in the hemisphere above each pixel, `KERNEL_SIZE` in `ssao.frag`.
+const KERNEL_SIZE: usize = 32;
+/// Width and height of the tiled texture of random kernel rotations. The blur averages the
+/// same square of pixels, which hides the pattern.
+const NOISE_SIZE: i32 = 4;
+
+/// Screen-space ambient occlusion: how much of the sky each pixel of the G-buffer sees past the
+/// geometry around it, blurred without blurring across edges.
+pub struct Ssao {
+    shader: ShaderProgram,
+    blur_shader: ShaderProgram,
+    noise: Texture,
+    framebuffer: Framebuffer,
+    /// Occlusion before the blur.
+    noisy_occlusion: Texture,
+    blur_framebuffer: Framebuffer,
+    occlusion: Texture,
+    dims: IVec2,
+}
+
+impl Ssao {
+    pub fn new(gl: &Context, dims: IVec2) -> Self {
+        let shader =
+            ShaderProgram::new(gl, include_str!("lighting.vert"), include_str!("ssao.frag"));
+        let blur_shader = ShaderProgram::new(
+            gl,
+            include_str!("lighting.vert"),
+            include_str!("ssao_blur.frag"),
+        );
+
+        shader.set_used(gl);
+        shader.set_int(gl, "g_position", 0);
+        shader.set_int(gl, "g_normal", 1);
+        shader.set_int(gl, "noise", 2);
+        for (i, sample) in kernel().iter().enumerate() {
+            shader.set_vec3(gl, &format!("samples[{}]", i), *sample);
+        }
+
+        blur_shader.set_used(gl);
+        blur_shader.set_int(gl, "occlusion", 0);
+        blur_shader.set_int(gl, "g_position", 1);
+
+        unsafe {
+            let noise = gl.create_texture().expect("Couldn't create texture.");
+            let data: Vec<f32> = noise_rotations()
+                .iter()
+                .flat_map(|rotation| [rotation.x, rotation.y])
+                .collect();
+            let bytes = core::slice::from_raw_parts(
+                data.as_ptr() as *const u8,
+                std::mem::size_of_val(data.as_slice()),
+            );
+            gl.bind_texture(TEXTURE_2D, Some(noise));
+            gl.tex_image_2d(
+                TEXTURE_2D,
+                0,
+                RG16F as i32,
+                NOISE_SIZE,
+                NOISE_SIZE,
+                0,
+                RG,
+                FLOAT,
+                Some(bytes),
+            );
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_S, REPEAT as i32);
+            gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_T, REPEAT as i32);
+            gl.bind_texture(TEXTURE_2D, None);
+
+            let (framebuffer, noisy_occlusion) = create_occlusion_buffer(gl, dims);
+            let (blur_framebuffer, occlusion) = create_occlusion_buffer(gl, dims);
+
+            Self {
+                shader,
+                blur_shader,
+                noise,
+                framebuffer,
+                noisy_occlusion,
+                blur_framebuffer,
+                occlusion,
+                dims,
+            }
+        }
+    }
+
+    /// Renders the blurred occlusion of the G-buffer's `position` and `normal` textures with
+    /// `screen_quad`.
+    pub fn render(
+        &self,
+        gl: &Context,
+        screen_quad: &Model,
+        position: Texture,
+        normal: Texture,
+        camera: &Camera,
+    ) {
+        unsafe {
+            gl.viewport(0, 0, self.dims.x, self.dims.y);
+            gl.disable(DEPTH_TEST);
+
+            gl.bind_framebuffer(FRAMEBUFFER, Some(self.framebuffer));
+            self.shader.set_used(gl);
+            self.shader.set_mat4(gl, "view", camera.view());
+            self.shader.set_mat4(gl, "projection", camera.projection());
+            gl.active_texture(TEXTURE0);
+            gl.bind_texture(TEXTURE_2D, Some(position));
+            gl.active_texture(TEXTURE1);
+            gl.bind_texture(TEXTURE_2D, Some(normal));
+            gl.active_texture(TEXTURE2);
+            gl.bind_texture(TEXTURE_2D, Some(self.noise));
+            draw(gl, screen_quad);
+
+            gl.bind_framebuffer(FRAMEBUFFER, Some(self.blur_framebuffer));
+            self.blur_shader.set_used(gl);
+            self.blur_shader.set_mat4(gl, "view", camera.view());
+            gl.active_texture(TEXTURE0);
+            gl.bind_texture(TEXTURE_2D, Some(self.noisy_occlusion));
+            gl.active_texture(TEXTURE1);
+            gl.bind_texture(TEXTURE_2D, Some(position));
+            draw(gl, screen_quad);
+
+            gl.bind_framebuffer(FRAMEBUFFER, None);
+        }
+    }
+
+    /// The blurred occlusion, 1 where nothing occludes the sky.
+    pub fn occlusion(&self) -> Texture {
+        self.occlusion
+    }
+}
+
+unsafe fn create_occlusion_buffer(gl: &Context, dims: IVec2) -> (Framebuffer, Texture) {
+    let framebuffer = gl
+        .create_framebuffer()
+        .expect("Couldn't create framebuffer.");
+    gl.bind_framebuffer(FRAMEBUFFER, Some(framebuffer));
+
+    let texture = gl.create_texture().expect("Couldn't create texture.");
+    gl.bind_texture(TEXTURE_2D, Some(texture));
+    gl.tex_image_2d(
+        TEXTURE_2D,
+        0,
+        R8 as i32,
+        dims.x,
+        dims.y,
+        0,
+        RED,
+        UNSIGNED_BYTE,
+        None,
+    );
+    gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST as i32);
+    gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST as i32);
+    gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE as i32);
+    gl.tex_parameter_i32(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE as i32);
+    gl.framebuffer_texture_2d(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, Some(texture), 0);
+
+    if gl.check_framebuffer_status(FRAMEBUFFER) != FRAMEBUFFER_COMPLETE {
+        panic!("Framebuffer is not complete.");
+    }
+
+    gl.bind_texture(TEXTURE_2D, None);
+    gl.bind_framebuffer(FRAMEBUFFER, None);
+
+    (framebuffer, texture)
+}
+
+fn draw(gl: &Context, model: &Model) {
+    unsafe {
+        gl.bind_vertex_array(Some(model.vao()));
+        gl.draw_elements(TRIANGLES, model.len() as i32, UNSIGNED_INT, 0);
+        gl.bind_vertex_array(None);
+    }
+}
+
+/// Xorshift, so the kernel and noise are the same every run without a random number crate.
+struct Random(u32);
+
+impl Random {
+    /// A number in `0..1`.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Sample offsets in the unit hemisphere around +Z, more of them close to the centre where
+/// occluders matter most.
+fn kernel() -> [Vec3; KERNEL_SIZE] {
+    let mut random = Random(0x9e37_79b9);
+    let mut kernel = [Vec3::ZERO; KERNEL_SIZE];
+    for (i, sample) in kernel.iter_mut().enumerate() {
+        let direction = vec3(
+            random.next() * 2. - 1.,
+            random.next() * 2. - 1.,
+            random.next(),
+        )
+        .normalize_or_zero();
+        let scale = i as f32 / KERNEL_SIZE as f32;
+        *sample = direction * random.next() * (0.1 + 0.9 * scale * scale);
+    }
+    kernel
+}
+
+/// Random rotations of the kernel around the surface normal, in tangent space.
+fn noise_rotations() -> Vec<Vec3> {
+    let mut random = Random(0x85eb_ca6b);
+    (0..NOISE_SIZE * NOISE_SIZE)
+        .map(|_| vec3(random.next() * 2. - 1., random.next() * 2. - 1., 0.))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::kernel;
+
+    #[test]
+    fn kernel_samples_lie_in_the_hemisphere() {
+        let kernel = kernel();
+        assert!(kernel
+            .iter()
+            .all(|sample| sample.z >= 0. && sample.length() <= 1.));
+        // Later samples reach further out.
+        let reach = |samples: &[Vec3]| samples.iter().map(|s| s.length()).sum::<f32>();
+        assert!(reach(&kernel[..8]) < reach(&kernel[kernel.len() - 8..]));
+        assert_eq!(kernel, super::kernel());
+    }
+}
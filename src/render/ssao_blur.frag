@@ -0,0 +1,38 @@
+#version 330 core
+
+in vec2 tex_coords;
+
+out float blurred;
+
+uniform sampler2D occlusion;
+uniform sampler2D g_position;
+uniform mat4 view;
+
+// Covers one tile of the noise texture, which averages its pattern away.
+#define BLUR_SIZE 2
+// How quickly pixels stop counting as their depth differs from the centre's, per block.
+#define DEPTH_SHARPNESS 4.0
+
+float view_depth(vec2 coords) {
+    return (view * vec4(texture(g_position, coords).rgb, 1.0)).z;
+}
+
+// Averages the occlusion around the pixel, weighting down pixels at other depths so it doesn't
+// bleed over the edges of blocks.
+void main() {
+    vec2 texel_size = 1.0 / vec2(textureSize(occlusion, 0));
+    float depth = view_depth(tex_coords);
+
+    float sum = 0.0;
+    float weights = 0.0;
+    for (int y = -BLUR_SIZE; y < BLUR_SIZE; y++) {
+        for (int x = -BLUR_SIZE; x < BLUR_SIZE; x++) {
+            vec2 coords = tex_coords + vec2(x, y) * texel_size;
+            float weight = exp(-abs(view_depth(coords) - depth) * DEPTH_SHARPNESS);
+            sum += texture(occlusion, coords).r * weight;
+            weights += weight;
+        }
+    }
+
+    blurred = sum / weights;
+}
@@ -92,7 +92,7 @@ impl CubeMap {
 
             for (i, p) in path.iter().enumerate() {
                 let image =
-                    load_image(*p).unwrap_or_else(|_| panic!("Couldn't load the image: {}", *p));
+                    load_image(p).unwrap_or_else(|_| panic!("Couldn't load the image: {}", *p));
                 let width = image.width();
                 let height = image.height();
 
@@ -128,16 +128,18 @@ impl CubeMap {
         }
     }
 
-    pub fn bind(&self, gl: &Context) {
+    pub fn bind(&self, gl: &Context, unit_index: i32) {
         unsafe {
-            gl.active_texture(TEXTURE0);
+            gl.active_texture(TEXTURE0 + unit_index as u32);
             gl.bind_texture(TEXTURE_CUBE_MAP, Some(self.id));
         }
     }
 }
 
+/// Day and night sky cube maps, blended by the time of day.
 pub struct Skybox {
-    cube_map: CubeMap,
+    day: CubeMap,
+    night: CubeMap,
     model: Model,
 }
 
@@ -168,13 +170,27 @@ impl Skybox {
         //     "assets/skybox3/back.png",
         // ];
 
-        let cube_map = CubeMap::new(gl, &images);
+        let night_images = [
+            "assets/skybox_night/right.png",
+            "assets/skybox_night/left.png",
+            "assets/skybox_night/top.png",
+            "assets/skybox_night/bottom.png",
+            "assets/skybox_night/front.png",
+            "assets/skybox_night/back.png",
+        ];
+
+        let day = CubeMap::new(gl, &images);
+        let night = CubeMap::new(gl, &night_images);
+
+        Self { day, night, model }
+    }
 
-        Self { cube_map, model }
+    pub fn day(&self) -> &CubeMap {
+        &self.day
     }
 
-    pub fn cube_map(&self) -> &CubeMap {
-        &self.cube_map
+    pub fn night(&self) -> &CubeMap {
+        &self.night
     }
 
     pub fn model(&self) -> &Model {
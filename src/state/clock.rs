@@ -0,0 +1,161 @@
+use std::f32::consts::TAU;
+
+use glam::{vec3, Vec3};
+
+/// Seconds a full day and night take unless the world says otherwise.
+pub const DEFAULT_DAY_LENGTH: f32 = 1200.;
+/// Time of day new worlds start at, shortly after sunrise.
+pub const START_TIME: f32 = 0.05;
+
+/// How far the sun's path is tilted from passing straight overhead, in radians. Keeps noon
+/// shadows from collapsing under the blocks.
+const SUN_TILT: f32 = 0.4;
+const NOON_COLOR: Vec3 = vec3(1., 0.97, 0.9);
+const SUNSET_COLOR: Vec3 = vec3(1., 0.5, 0.25);
+const MOON_COLOR: Vec3 = vec3(0.55, 0.65, 1.);
+const MOON_INTENSITY: f32 = 0.15;
+const DAY_HORIZON: Vec3 = vec3(0.6, 0.72, 0.9);
+const SUNSET_HORIZON: Vec3 = vec3(0.85, 0.5, 0.3);
+const NIGHT_HORIZON: Vec3 = vec3(0.02, 0.025, 0.05);
+const DAY_AMBIENT: f32 = 0.2;
+const NIGHT_AMBIENT: f32 = 0.03;
+
+/// The time of day of a world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldClock {
+    /// Fraction of the day that has passed since sunrise, in `0..1`. Noon is at 0.25 and
+    /// midnight at 0.75.
+    pub time: f32,
+    /// Seconds a full day and night take.
+    pub day_length: f32,
+}
+
+/// Lighting and sky at some time of day.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Daylight {
+    /// Direction towards the sun. The moon is always opposite.
+    pub sun_direction: Vec3,
+    /// Direction the main light travels in: from the sun by day and from the moon by night.
+    pub light_direction: Vec3,
+    pub light_color: Vec3,
+    pub light_intensity: f32,
+    /// Strength of the light coming from the whole sky.
+    pub ambient: f32,
+    /// Colour of the sky near the horizon, which fog takes on.
+    pub horizon_color: Vec3,
+    /// How far the sky has turned from the day to the night sky, from 0 to 1.
+    pub night: f32,
+    /// How visible the stars are, from 0 to 1.
+    pub stars: f32,
+}
+
+impl WorldClock {
+    pub fn new(time: f32, day_length: f32) -> Self {
+        Self {
+            time: time.rem_euclid(1.),
+            day_length,
+        }
+    }
+
+    pub fn advance(&mut self, delta: f32) {
+        self.time = (self.time + delta / self.day_length).rem_euclid(1.);
+    }
+
+    /// Direction towards the sun. It rises in +X and sets in -X.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = self.time * TAU;
+        vec3(
+            angle.cos(),
+            angle.sin() * SUN_TILT.cos(),
+            angle.sin() * SUN_TILT.sin(),
+        )
+    }
+
+    pub fn moon_direction(&self) -> Vec3 {
+        -self.sun_direction()
+    }
+
+    pub fn daylight(&self) -> Daylight {
+        let sun = self.sun_direction();
+        let elevation = sun.y;
+        let day = smoothstep(-0.1, 0.1, elevation);
+
+        let (light_direction, light_color, light_intensity) = if elevation >= 0. {
+            let color = SUNSET_COLOR.lerp(NOON_COLOR, smoothstep(0., 0.4, elevation));
+            (-sun, color, smoothstep(0., 0.15, elevation))
+        } else {
+            let moon = self.moon_direction();
+            let intensity = MOON_INTENSITY * smoothstep(0., 0.15, moon.y);
+            (-moon, MOON_COLOR, intensity)
+        };
+
+        let horizon_color = SUNSET_HORIZON
+            .lerp(DAY_HORIZON, smoothstep(0., 0.3, elevation))
+            .lerp(NIGHT_HORIZON, 1. - day);
+
+        Daylight {
+            sun_direction: sun,
+            light_direction,
+            light_color,
+            light_intensity,
+            ambient: NIGHT_AMBIENT + (DAY_AMBIENT - NIGHT_AMBIENT) * day,
+            horizon_color,
+            night: 1. - day,
+            stars: 1. - smoothstep(-0.25, -0.05, elevation),
+        }
+    }
+}
+
+impl Default for WorldClock {
+    fn default() -> Self {
+        Self::new(START_TIME, DEFAULT_DAY_LENGTH)
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WorldClock;
+
+    fn at(time: f32) -> WorldClock {
+        WorldClock::new(time, 100.)
+    }
+
+    #[test]
+    fn advances_and_wraps_around() {
+        let mut clock = at(0.9);
+        clock.advance(20.);
+        assert!((clock.time - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sun_and_moon_follow_the_clock() {
+        assert!(at(0.25).sun_direction().y > 0.9);
+        assert!(at(0.75).sun_direction().y < -0.9);
+        assert!(at(0.75).moon_direction().y > 0.9);
+        assert!(at(0.).sun_direction().x > 0.99);
+    }
+
+    #[test]
+    fn days_are_bright_and_nights_dark() {
+        let noon = at(0.25).daylight();
+        let dusk = at(0.49).daylight();
+        let midnight = at(0.75).daylight();
+
+        assert!(noon.light_direction.y < 0.);
+        assert!(midnight.light_direction.y < 0.);
+        assert!(noon.light_intensity > dusk.light_intensity);
+        assert!(dusk.light_intensity > midnight.light_intensity);
+        assert!(noon.ambient > midnight.ambient);
+        assert!(noon.horizon_color.min_element() > midnight.horizon_color.max_element());
+
+        assert_eq!((noon.night, noon.stars), (0., 0.));
+        assert_eq!((midnight.night, midnight.stars), (1., 1.));
+        // Sunlight turns red as the sun goes down.
+        assert!(dusk.light_color.z < noon.light_color.z);
+    }
+}
@@ -0,0 +1,23 @@
+/// Describes how to build the GPU side components (`Model` and `GameTexture`) of an entity.
+///
+/// GPU handles can't be saved, so persistent entities keep this instead and get their model
+/// and texture recreated after loading.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderAsset {
+    pub mesh: MeshAsset,
+    pub texture: String,
+}
+
+impl RenderAsset {
+    pub fn new(mesh: MeshAsset, texture: &str) -> Self {
+        Self {
+            mesh,
+            texture: texture.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeshAsset {
+    Cube(f32),
+}
@@ -0,0 +1,5 @@
+use glam::IVec3;
+
+/// Marks a model entity as (part of) the mesh of the chunk at the given chunk position.
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkMesh(pub IVec3);
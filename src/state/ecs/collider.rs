@@ -0,0 +1,21 @@
+use glam::Vec3;
+
+use crate::state::physics::aabb::Aabb;
+
+/// Box that collides with the terrain, centered on the entity's translation.
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub half_extents: Vec3,
+}
+
+impl Collider {
+    pub fn new(size: Vec3) -> Self {
+        Self {
+            half_extents: size / 2.,
+        }
+    }
+
+    pub fn aabb(&self, center: Vec3) -> Aabb {
+        Aabb::from_center(center, self.half_extents)
+    }
+}
@@ -0,0 +1,19 @@
+use crate::state::voxel::block::Block;
+
+/// A gravity-affected block that lost its support, falling until it lands. Its `Transform` is
+/// the center of the block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FallingBlock {
+    pub block: Block,
+    /// Vertical velocity in blocks per second.
+    pub velocity: f32,
+}
+
+impl FallingBlock {
+    pub fn new(block: Block) -> Self {
+        Self {
+            block,
+            velocity: 0.,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use super::transform::Transform;
+
+/// Transform of an entity moved in the fixed step as it was before the last step, so it can be
+/// drawn between the last two steps instead of jumping from one to the next.
+#[derive(Clone, Copy)]
+pub struct Interpolation {
+    pub previous: Transform,
+}
+
+impl Interpolation {
+    pub fn new(transform: Transform) -> Self {
+        Self {
+            previous: transform,
+        }
+    }
+
+    /// The transform to draw, `alpha` of the way from the previous step to `current`.
+    pub fn interpolate(&self, current: &Transform, alpha: f32) -> Transform {
+        self.previous.lerp(current, alpha)
+    }
+}
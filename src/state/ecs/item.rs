@@ -0,0 +1,7 @@
+use crate::state::voxel::block::Block;
+
+/// A block lying in the world as an item, like a falling block that couldn't land.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemDrop {
+    pub block: Block,
+}
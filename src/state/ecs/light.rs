@@ -0,0 +1,36 @@
+use glam::Vec3;
+
+/// Light from infinitely far away shining along the -Z axis of the entity's transform, like the
+/// sun. Only the first directional light found is shaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirectionalLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    pub cast_shadows: bool,
+}
+
+/// Light shining in every direction from the entity's position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance in blocks at which the light fades out completely.
+    pub range: f32,
+    /// Only directional lights have shadow maps so far, this is kept for when point lights do.
+    pub cast_shadows: bool,
+}
+
+/// Light shining in a cone along the -Z axis of the entity's transform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpotLight {
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance in blocks at which the light fades out completely.
+    pub range: f32,
+    /// Angle from the axis in radians inside which the light is at full strength.
+    pub inner_angle: f32,
+    /// Angle from the axis in radians at which the light has faded out.
+    pub outer_angle: f32,
+    /// Only directional lights have shadow maps so far, this is kept for when spot lights do.
+    pub cast_shadows: bool,
+}
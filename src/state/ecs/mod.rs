@@ -1 +1,11 @@
+pub mod asset;
+pub mod chunk;
+pub mod collider;
+pub mod falling_block;
+pub mod interpolation;
+pub mod item;
+pub mod light;
+pub mod persistent;
+pub mod player;
+pub mod rigid_body;
 pub mod transform;
@@ -0,0 +1,6 @@
+/// Marks an entity to be stored with the chunk it is in when the world is saved.
+///
+/// Only components registered in the save component registry are stored, everything else is
+/// dropped or rebuilt after loading.
+#[derive(Clone, Copy, Debug)]
+pub struct Persistent;
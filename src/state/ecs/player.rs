@@ -0,0 +1,46 @@
+use glam::{vec3, Vec3};
+
+/// Size of the player's bounding box.
+pub const PLAYER_SIZE: Vec3 = vec3(0.6, 1.8, 0.6);
+
+/// Height of the camera above the player's feet.
+pub const EYE_HEIGHT: f32 = 1.62;
+
+/// How far away the player can reach blocks.
+pub const REACH: f32 = 5.;
+
+/// Highest ledge the player walks onto without jumping.
+pub const STEP_HEIGHT: f32 = 1.;
+
+/// How the player moves. Swimming and climbing aren't modes, walking players do them when they're
+/// in a fluid or a climbable block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MovementMode {
+    #[default]
+    Walk,
+    /// Flying without gravity, toggled by double-tapping jump. Landing switches back to walking.
+    Fly,
+    /// Flying through blocks.
+    Spectator,
+}
+
+/// The entity controlled by the local player. Its `Transform` is the center of its `Collider`.
+#[derive(Default)]
+pub struct Player {
+    /// Velocity in blocks per second.
+    pub velocity: Vec3,
+    pub mode: MovementMode,
+    pub on_ground: bool,
+    /// Whether the player was in a fluid or a climbable block during the last step.
+    pub swimming: bool,
+    pub climbing: bool,
+    pub sprinting: bool,
+    pub crouching: bool,
+}
+
+impl Player {
+    /// Offset from the center of the player to the camera.
+    pub fn eye_offset() -> Vec3 {
+        vec3(0., EYE_HEIGHT - PLAYER_SIZE.y / 2., 0.)
+    }
+}
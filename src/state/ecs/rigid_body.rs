@@ -0,0 +1,39 @@
+use glam::Vec3;
+
+/// Lets an entity with a `Transform` and a `Collider` move on its own, bouncing off and sliding
+/// along the terrain until it comes to rest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RigidBody {
+    /// Velocity in blocks per second.
+    pub velocity: Vec3,
+    /// How quickly sliding on the ground slows the body down, as a fraction of gravity.
+    pub friction: f32,
+    /// Fraction of the speed kept when bouncing off a block.
+    pub restitution: f32,
+    /// Sleeping bodies aren't simulated until something wakes them up.
+    pub sleeping: bool,
+    /// Seconds the body has been resting almost still.
+    pub still_time: f32,
+}
+
+impl RigidBody {
+    pub fn new(friction: f32, restitution: f32) -> Self {
+        Self {
+            velocity: Vec3::ZERO,
+            friction,
+            restitution,
+            sleeping: false,
+            still_time: 0.,
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.still_time = 0.;
+    }
+}
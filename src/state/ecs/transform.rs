@@ -24,15 +24,56 @@ impl Transform {
         }
     }
 
+    /// Moves the transform by `offset` in world space.
+    pub fn translate(&self, offset: Vec3) -> Self {
+        Self {
+            mat: Mat4::from_translation(offset) * self.mat,
+        }
+    }
+
     pub fn flip(&self) -> Self {
         Self {
             mat: self.mat * Mat4::from_rotation_x(PI),
         }
     }
 
+    pub fn from_matrix(mat: Mat4) -> Self {
+        Self { mat }
+    }
+
     pub fn matrix(&self) -> Mat4 {
         self.mat
     }
+
+    pub fn translation(&self) -> Vec3 {
+        self.mat.w_axis.truncate()
+    }
+
+    /// A transform at the origin whose -Z axis points along `direction`.
+    pub fn from_direction(direction: Vec3) -> Self {
+        Self {
+            mat: Mat4::look_at_rh(Vec3::ZERO, direction, Vec3::Y).inverse(),
+        }
+    }
+
+    /// The -Z axis of the transform, where lights attached to it point.
+    pub fn direction(&self) -> Vec3 {
+        self.mat.transform_vector3(Vec3::NEG_Z).normalize()
+    }
+
+    /// Blends translation, rotation and scale separately, `t` of the way to `other`.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Self {
+        let (scale_a, rotation_a, translation_a) = self.mat.to_scale_rotation_translation();
+        let (scale_b, rotation_b, translation_b) = other.mat.to_scale_rotation_translation();
+
+        Self {
+            mat: Mat4::from_scale_rotation_translation(
+                scale_a.lerp(scale_b, t),
+                rotation_a.slerp(rotation_b, t),
+                translation_a.lerp(translation_b, t),
+            ),
+        }
+    }
 }
 
 impl Default for Transform {
@@ -40,3 +81,47 @@ impl Default for Transform {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{vec3, Mat4, Quat, Vec3};
+
+    use super::Transform;
+
+    fn assert_transform(transform: Transform, scale: f32, angle: f32, translation: Vec3) {
+        let (actual_scale, rotation, actual_translation) =
+            transform.matrix().to_scale_rotation_translation();
+        assert!(
+            actual_scale.abs_diff_eq(Vec3::splat(scale), 1e-5),
+            "{:?}",
+            actual_scale
+        );
+        assert!(
+            rotation.abs_diff_eq(Quat::from_rotation_y(angle), 1e-5),
+            "{:?}",
+            rotation
+        );
+        assert!(
+            actual_translation.abs_diff_eq(translation, 1e-5),
+            "{:?}",
+            actual_translation
+        );
+    }
+
+    #[test]
+    fn lerp_blends_scale_rotation_and_translation() {
+        let from = Transform::from_translation(vec3(1., 0., 0.));
+        let to = Transform::from_matrix(Mat4::from_scale_rotation_translation(
+            Vec3::splat(3.),
+            Quat::from_rotation_y(FRAC_PI_2),
+            vec3(3., 4., -6.),
+        ));
+
+        assert_transform(from.lerp(&to, 0.), 1., 0., vec3(1., 0., 0.));
+        // Rotation is blended along the arc, a matrix lerp would shrink the halfway point.
+        assert_transform(from.lerp(&to, 0.5), 2., FRAC_PI_2 / 2., vec3(2., 2., -3.));
+        assert_transform(from.lerp(&to, 1.), 3., FRAC_PI_2, vec3(3., 4., -6.));
+    }
+}
@@ -1,8 +1,63 @@
+/// Longest time between two presses of forward that starts sprinting, in milliseconds.
+const SPRINT_DOUBLE_TAP: u32 = 300;
+
+/// Longest time between two presses of jump that toggles flying, in milliseconds.
+const FLY_DOUBLE_TAP: u32 = 300;
+
 #[derive(Default)]
 pub struct InputState {
     pub forward: bool,
     pub backward: bool,
     pub right: bool,
     pub left: bool,
-    pub space_toggle: bool,
+    pub jump: bool,
+    pub crouch: bool,
+    /// Set by double-tapping forward or holding the sprint key, lasts until forward is released.
+    pub sprint: bool,
+    /// Held to mine the targeted block.
+    pub mine: bool,
+    /// Set by double-tapping jump, cleared once the next physics step has switched modes.
+    pub toggle_fly: bool,
+    /// Set by the spectator key, cleared like `toggle_fly`.
+    pub toggle_spectator: bool,
+    pub last_forward_press: Option<u32>,
+    pub last_jump_press: Option<u32>,
+}
+
+impl InputState {
+    /// Presses forward at `timestamp` (SDL event time in milliseconds).
+    pub fn press_forward(&mut self, timestamp: u32) {
+        if is_double_tap(self.last_forward_press, timestamp, SPRINT_DOUBLE_TAP) {
+            self.sprint = true;
+        }
+        self.last_forward_press = Some(timestamp);
+        self.forward = true;
+    }
+
+    pub fn release_forward(&mut self) {
+        self.forward = false;
+        self.sprint = false;
+    }
+
+    /// Presses jump at `timestamp`, a second press soon after the first toggles flying.
+    pub fn press_jump(&mut self, timestamp: u32) {
+        if is_double_tap(self.last_jump_press, timestamp, FLY_DOUBLE_TAP) {
+            self.toggle_fly = true;
+            // A third quick press starts a new double tap instead of toggling back.
+            self.last_jump_press = None;
+        } else {
+            self.last_jump_press = Some(timestamp);
+        }
+        self.jump = true;
+    }
+
+    /// Clears the one-shot mode toggles after a physics step has handled them.
+    pub fn consume_toggles(&mut self) {
+        self.toggle_fly = false;
+        self.toggle_spectator = false;
+    }
+}
+
+fn is_double_tap(last: Option<u32>, timestamp: u32, window: u32) -> bool {
+    last.is_some_and(|last| timestamp.wrapping_sub(last) <= window)
 }
@@ -4,13 +4,17 @@ use sdl2::{
     video::{GLContext, Window},
 };
 
-use crate::render::renderer::Renderer;
+use crate::render::{cluster::LightStats, renderer::Renderer};
 
 use self::screen::Screen;
 
+pub mod clock;
 pub mod ecs;
 pub mod input;
+pub mod physics;
+pub mod save;
 pub mod screen;
+pub mod voxel;
 pub mod world;
 
 pub struct GameState {
@@ -33,8 +37,16 @@ impl GameState {
         self.screen.update(delta);
     }
 
-    pub fn draw(&mut self) {
-        self.screen.draw(&mut self.renderer);
+    pub fn draw(&mut self, alpha: f32) {
+        self.screen.draw(&mut self.renderer, alpha);
+    }
+
+    pub fn light_stats(&self) -> LightStats {
+        self.renderer.light_stats()
+    }
+
+    pub fn save(&mut self) {
+        self.screen.save();
     }
 
     pub fn handle_input(&mut self, event: Event) {
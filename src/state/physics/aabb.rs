@@ -0,0 +1,63 @@
+use glam::{IVec3, Vec3};
+
+/// Axis-aligned bounding box in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center(center: Vec3, half_extents: Vec3) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    /// The box of the block at `pos`.
+    pub fn block(pos: IVec3) -> Self {
+        Self::new(pos.as_vec3(), pos.as_vec3() + 1.)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn translate(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Grows the box in the direction of `motion`, covering everything it passes through.
+    pub fn expand(&self, motion: Vec3) -> Self {
+        Self::new(
+            self.min + motion.min(Vec3::ZERO),
+            self.max + motion.max(Vec3::ZERO),
+        )
+    }
+
+    /// Shrinks the box by `amount` on every side.
+    pub fn shrink(&self, amount: f32) -> Self {
+        Self::new(self.min + amount, self.max - amount)
+    }
+
+    /// Whether the boxes overlap, touching faces don't count.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmplt(other.max).all() && self.max.cmpgt(other.min).all()
+    }
+
+    /// Positions of the blocks the box overlaps.
+    pub fn blocks(&self) -> impl Iterator<Item = IVec3> {
+        let min = self.min.floor().as_ivec3();
+        let max = (self.max.ceil().as_ivec3() - 1).max(min);
+
+        (min.y..=max.y).flat_map(move |y| {
+            (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+        })
+    }
+}
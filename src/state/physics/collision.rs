@@ -0,0 +1,231 @@
+use glam::{vec2, vec3, BVec3, IVec3, Vec3};
+
+use crate::state::voxel::{block::Block, terrain::Terrain};
+
+use super::aabb::Aabb;
+
+/// Tolerance for boxes that end exactly on a block boundary, so a box resting on the ground or
+/// against a wall doesn't count as overlapping it.
+const EPSILON: f32 = 1e-4;
+
+/// How far below a box is searched for ground.
+const GROUND_PROBE: f32 = 1e-3;
+
+/// Anything that can tell which block is at a position.
+pub trait VoxelGrid {
+    fn block(&self, pos: IVec3) -> Block;
+
+    fn is_solid(&self, pos: IVec3) -> bool {
+        self.block(pos).is_solid()
+    }
+}
+
+impl VoxelGrid for Terrain {
+    fn block(&self, pos: IVec3) -> Block {
+        self.get_block(pos)
+    }
+}
+
+/// Result of moving a box through the voxel grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Movement {
+    /// How far the box actually moved.
+    pub offset: Vec3,
+    /// The axes on which the movement was cut short by a block.
+    pub blocked: BVec3,
+    /// Whether the box ended up standing on a block.
+    pub on_ground: bool,
+}
+
+/// Moves `aabb` by `motion`, stopping at solid blocks.
+///
+/// Movement is resolved one axis at a time, vertical first, against every block in the swept
+/// volume, so fast boxes can't pass through thin walls. When horizontal movement is blocked
+/// and `step_height` is positive, the box also tries to climb onto the obstacle and keeps
+/// whichever result gets it further.
+pub fn move_aabb(grid: &impl VoxelGrid, aabb: Aabb, motion: Vec3, step_height: f32) -> Movement {
+    let (offset, blocked) = sweep(grid, aabb, motion);
+    let on_ground = blocked.y && motion.y < 0.;
+
+    if step_height > 0. && (blocked.x || blocked.z) {
+        let up = clip_axis(grid, &aabb, 1, step_height);
+        let raised = aabb.translate(Vec3::Y * up);
+        let (horizontal, stepped_blocked) = sweep(grid, raised, vec3(motion.x, 0., motion.z));
+        let down = clip_axis(grid, &raised.translate(horizontal), 1, -up);
+
+        let stepped = vec2(horizontal.x, horizontal.z).length_squared();
+        if stepped > vec2(offset.x, offset.z).length_squared() + EPSILON {
+            return Movement {
+                offset: vec3(horizontal.x, up + down, horizontal.z),
+                blocked: BVec3::new(stepped_blocked.x, false, stepped_blocked.z),
+                on_ground: true,
+            };
+        }
+    }
+
+    Movement {
+        offset,
+        blocked,
+        on_ground,
+    }
+}
+
+/// Whether the box is standing on a solid block.
+pub fn is_on_ground(grid: &impl VoxelGrid, aabb: &Aabb) -> bool {
+    clip_axis(grid, aabb, 1, -GROUND_PROBE) > -GROUND_PROBE
+}
+
+/// Whether any solid block overlaps the box.
+pub fn intersects_blocks(grid: &impl VoxelGrid, aabb: &Aabb) -> bool {
+    aabb.shrink(EPSILON).blocks().any(|pos| grid.is_solid(pos))
+}
+
+/// Moves along Y, X and Z in turn, returning the offset and which axes were blocked.
+fn sweep(grid: &impl VoxelGrid, mut aabb: Aabb, motion: Vec3) -> (Vec3, BVec3) {
+    let mut offset = Vec3::ZERO;
+    let mut blocked = [false; 3];
+
+    for axis in [1, 0, 2] {
+        let distance = clip_axis(grid, &aabb, axis, motion[axis]);
+        blocked[axis] = distance != motion[axis];
+        offset[axis] = distance;
+
+        let mut step = Vec3::ZERO;
+        step[axis] = distance;
+        aabb = aabb.translate(step);
+    }
+
+    (offset, BVec3::new(blocked[0], blocked[1], blocked[2]))
+}
+
+/// Shortens `distance` along `axis` so the box stops at the first solid block in its way.
+///
+/// Blocks the box already overlaps are ignored, so a box stuck inside terrain can move out.
+fn clip_axis(grid: &impl VoxelGrid, aabb: &Aabb, axis: usize, mut distance: f32) -> f32 {
+    if distance == 0. {
+        return 0.;
+    }
+
+    let mut motion = Vec3::ZERO;
+    motion[axis] = distance;
+
+    for pos in aabb.shrink(EPSILON).expand(motion).blocks() {
+        if !grid.is_solid(pos) {
+            continue;
+        }
+
+        let block = Aabb::block(pos);
+        if distance > 0. {
+            let gap = block.min[axis] - aabb.max[axis];
+            if gap >= -EPSILON {
+                distance = distance.min(gap.max(0.));
+            }
+        } else {
+            let gap = block.max[axis] - aabb.min[axis];
+            if gap <= EPSILON {
+                distance = distance.max(gap.min(0.));
+            }
+        }
+    }
+
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use glam::{ivec3, vec3, IVec3, Vec3};
+
+    use super::{is_on_ground, move_aabb, Aabb, Block, VoxelGrid};
+
+    impl VoxelGrid for HashSet<IVec3> {
+        fn block(&self, pos: IVec3) -> Block {
+            if self.contains(&pos) {
+                Block::Stone
+            } else {
+                Block::Air
+            }
+        }
+    }
+
+    /// A 16x16 floor whose top is at y = 0, plus the given blocks.
+    fn grid(blocks: &[IVec3]) -> HashSet<IVec3> {
+        let mut grid: HashSet<IVec3> = (-8..8)
+            .flat_map(|x| (-8..8).map(move |z| ivec3(x, -1, z)))
+            .collect();
+        grid.extend(blocks);
+        grid
+    }
+
+    /// A player sized box standing on the floor at `x, z`.
+    fn player(x: f32, z: f32) -> Aabb {
+        Aabb::from_center(vec3(x, 0.9, z), vec3(0.3, 0.9, 0.3))
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).abs().max_element() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn lands_on_the_floor_without_tunnelling() {
+        let grid = grid(&[]);
+        let aabb = player(0.5, 0.5).translate(vec3(0., 10., 0.));
+
+        let movement = move_aabb(&grid, aabb, vec3(0., -100., 0.), 0.);
+        assert_close(movement.offset, vec3(0., -10., 0.));
+        assert!(movement.blocked.y && movement.on_ground);
+        assert!(is_on_ground(&grid, &aabb.translate(movement.offset)));
+    }
+
+    #[test]
+    fn fast_boxes_stop_at_thin_walls() {
+        let grid = grid(&[ivec3(3, 0, 0), ivec3(3, 1, 0)]);
+
+        let movement = move_aabb(&grid, player(0.5, 0.5), vec3(50., 0., 0.), 0.);
+        assert_close(movement.offset, vec3(2.2, 0., 0.));
+        assert!(movement.blocked.x);
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let grid = grid(&[
+            ivec3(1, 0, 0),
+            ivec3(1, 1, 0),
+            ivec3(1, 0, 1),
+            ivec3(1, 1, 1),
+        ]);
+
+        let movement = move_aabb(&grid, player(0.5, 0.5), vec3(1., 0., 1.), 0.);
+        assert_close(movement.offset, vec3(0.2, 0., 1.));
+        assert!(movement.blocked.x && !movement.blocked.z);
+    }
+
+    #[test]
+    fn steps_up_onto_single_blocks() {
+        let grid = grid(&[ivec3(1, 0, 0)]);
+
+        let movement = move_aabb(&grid, player(0.5, 0.5), vec3(0.5, 0., 0.), 1.);
+        assert_close(movement.offset, vec3(0.5, 1., 0.));
+        assert!(movement.on_ground);
+
+        let movement = move_aabb(&grid, player(0.5, 0.5), vec3(0.5, 0., 0.), 0.);
+        assert_close(movement.offset, vec3(0.2, 0., 0.));
+    }
+
+    #[test]
+    fn doesnt_step_up_walls() {
+        let grid = grid(&[ivec3(1, 0, 0), ivec3(1, 1, 0)]);
+
+        let movement = move_aabb(&grid, player(0.5, 0.5), vec3(0.5, 0., 0.), 1.);
+        assert_close(movement.offset, vec3(0.2, 0., 0.));
+    }
+
+    #[test]
+    fn needs_headroom_to_step_up() {
+        let grid = grid(&[ivec3(1, 0, 0), ivec3(0, 2, 0)]);
+
+        let movement = move_aabb(&grid, player(0.5, 0.5), vec3(0.5, 0., 0.), 1.);
+        assert_close(movement.offset, vec3(0.2, 0., 0.));
+    }
+}
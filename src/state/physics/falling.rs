@@ -0,0 +1,147 @@
+use glam::{IVec3, Vec3};
+use hecs::{Entity, World};
+
+use crate::state::{
+    ecs::{falling_block::FallingBlock, transform::Transform},
+    voxel::{block::Block, chunk::chunk_pos, terrain::Terrain},
+};
+
+use super::{
+    aabb::Aabb,
+    collision::move_aabb,
+    movement::{GRAVITY, PHYSICS_TIMESTEP, TERMINAL_VELOCITY},
+};
+
+/// Falling blocks are slightly smaller than a block so they fit through one block wide gaps.
+pub const FALLING_BLOCK_SIZE: f32 = 0.98;
+
+/// What happened to a falling block that stopped falling.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Landing {
+    /// The block can be placed back into the terrain here.
+    Place(IVec3, Block),
+    /// The block landed where it can't be placed and becomes an item at this position.
+    Drop(Vec3, Block),
+    /// The block fell out of the loaded terrain.
+    Lost,
+}
+
+/// Whether the block at `pos` is affected by gravity and has nothing under it.
+pub fn is_unsupported(terrain: &Terrain, pos: IVec3) -> bool {
+    let below = pos - IVec3::Y;
+    terrain.get_block(pos).has_gravity()
+        && terrain.is_loaded(chunk_pos(below))
+        && !terrain.get_block(below).is_solid()
+}
+
+/// Moves every falling block one physics step and returns the ones that stopped.
+pub fn step_falling_blocks(world: &mut World, terrain: &Terrain) -> Vec<(Entity, Landing)> {
+    let dt = PHYSICS_TIMESTEP;
+    let half_extents = Vec3::splat(FALLING_BLOCK_SIZE / 2.);
+    let mut landings = Vec::new();
+
+    for (entity, (transform, falling)) in world.query_mut::<(&mut Transform, &mut FallingBlock)>() {
+        let center = transform.translation();
+        if !terrain.is_loaded(chunk_pos(center.floor().as_ivec3())) {
+            landings.push((entity, Landing::Lost));
+            continue;
+        }
+
+        falling.velocity = (falling.velocity - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        let aabb = Aabb::from_center(center, half_extents);
+        let movement = move_aabb(terrain, aabb, Vec3::Y * falling.velocity * dt, 0.);
+        let center = center + movement.offset;
+        *transform = Transform::from_translation(center);
+
+        if movement.on_ground {
+            landings.push((entity, landing(terrain, center, falling.block)));
+        }
+    }
+
+    landings
+}
+
+fn landing(terrain: &Terrain, center: Vec3, block: Block) -> Landing {
+    let pos = center.floor().as_ivec3();
+    let target = terrain.get_block(pos);
+    let below = terrain.get_block(pos - IVec3::Y);
+
+    if target.is_replaceable() && below.is_full_cube() {
+        Landing::Place(pos, block)
+    } else {
+        Landing::Drop(center, block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, vec3};
+    use hecs::World;
+
+    use crate::state::{
+        ecs::{falling_block::FallingBlock, transform::Transform},
+        voxel::{block::Block, terrain::Terrain},
+    };
+
+    use super::{is_unsupported, landing, step_falling_blocks, Landing};
+
+    /// Drops a block from `y` above (3, _, 3) and returns where it stopped.
+    fn drop_block(terrain: &Terrain, y: f32) -> Landing {
+        let mut world = World::new();
+        world.spawn((
+            Transform::from_translation(vec3(3.5, y, 3.5)),
+            FallingBlock::new(Block::Sand),
+        ));
+
+        for _ in 0..200 {
+            if let Some((_, landing)) = step_falling_blocks(&mut world, terrain).pop() {
+                return landing;
+            }
+        }
+        panic!("The block never landed.");
+    }
+
+    #[test]
+    fn only_gravity_blocks_without_support_fall() {
+        let mut terrain = Terrain::stone_floor(1);
+        terrain.set_block(ivec3(3, 1, 3), Block::Sand);
+        terrain.set_block(ivec3(3, 5, 3), Block::Sand);
+        terrain.set_block(ivec3(4, 5, 3), Block::Dirt);
+        terrain.set_block(ivec3(5, 5, 3), Block::Gravel);
+        terrain.set_block(ivec3(5, 4, 3), Block::Water);
+
+        assert!(!is_unsupported(&terrain, ivec3(3, 1, 3)));
+        assert!(is_unsupported(&terrain, ivec3(3, 5, 3)));
+        assert!(!is_unsupported(&terrain, ivec3(4, 5, 3)));
+        assert!(is_unsupported(&terrain, ivec3(5, 5, 3)));
+    }
+
+    #[test]
+    fn lands_on_the_ground() {
+        let terrain = Terrain::stone_floor(1);
+        assert_eq!(
+            drop_block(&terrain, 10.5),
+            Landing::Place(ivec3(3, 1, 3), Block::Sand)
+        );
+    }
+
+    #[test]
+    fn falls_through_fluids_and_replaces_them() {
+        let mut terrain = Terrain::stone_floor(1);
+        terrain.set_block(ivec3(3, 1, 3), Block::Water);
+        assert_eq!(
+            drop_block(&terrain, 10.5),
+            Landing::Place(ivec3(3, 1, 3), Block::Sand)
+        );
+    }
+
+    #[test]
+    fn drops_when_the_cell_was_taken() {
+        let mut terrain = Terrain::stone_floor(1);
+        terrain.set_block(ivec3(3, 1, 3), Block::Planks);
+        assert!(matches!(
+            landing(&terrain, vec3(3.5, 1.49, 3.5), Block::Sand),
+            Landing::Drop(..)
+        ));
+    }
+}
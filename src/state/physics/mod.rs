@@ -0,0 +1,6 @@
+pub mod aabb;
+pub mod collision;
+pub mod falling;
+pub mod movement;
+pub mod raycast;
+pub mod rigid_body;
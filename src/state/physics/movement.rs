@@ -0,0 +1,503 @@
+use glam::{ivec3, vec2, vec3, BVec3, Vec2, Vec3};
+
+use crate::state::{
+    ecs::player::{MovementMode, Player, STEP_HEIGHT},
+    input::InputState,
+    voxel::fluid::Fluid,
+};
+
+use super::{
+    aabb::Aabb,
+    collision::{is_on_ground, move_aabb, VoxelGrid},
+};
+
+/// Fixed physics steps per second.
+pub const PHYSICS_RATE: u64 = 40;
+pub const PHYSICS_TIMESTEP: f32 = 1. / PHYSICS_RATE as f32;
+
+/// Horizontal speeds in blocks per second.
+pub const WALK_SPEED: f32 = 4.3;
+pub const SPRINT_SPEED: f32 = 5.6;
+pub const CROUCH_SPEED: f32 = 1.3;
+
+/// How quickly the horizontal velocity approaches the wanted speed, in blocks per second².
+pub const GROUND_ACCELERATION: f32 = 50.;
+pub const AIR_ACCELERATION: f32 = 10.;
+
+/// Downward acceleration in blocks per second².
+pub const GRAVITY: f32 = 28.;
+pub const TERMINAL_VELOCITY: f32 = 60.;
+
+/// Upward velocity of a jump, high enough to clear a single block.
+pub const JUMP_VELOCITY: f32 = 8.7;
+
+/// Step by which crouching movement is shortened until the player stays on the ground.
+const EDGE_STEP: f32 = 0.05;
+
+/// Shrinks the player's box when looking for ladders, so touching one from the side doesn't count.
+const EPSILON: f32 = 1e-3;
+
+/// Flying speeds, the vertical one is used while holding jump or crouch.
+pub const FLY_SPEED: f32 = 10.9;
+pub const FLY_SPRINT_SPEED: f32 = 21.6;
+pub const FLY_VERTICAL_SPEED: f32 = 7.5;
+pub const FLY_ACCELERATION: f32 = 40.;
+
+/// Spectators fly faster and through blocks.
+pub const SPECTATOR_SPEED: f32 = 15.;
+pub const SPECTATOR_SPRINT_SPEED: f32 = 30.;
+pub const SPECTATOR_ACCELERATION: f32 = 60.;
+
+/// Horizontal swimming speed and acceleration.
+pub const SWIM_SPEED: f32 = 2.2;
+pub const SWIM_ACCELERATION: f32 = 20.;
+
+/// Upward acceleration of a fully submerged player, a bit less than gravity so they slowly sink.
+pub const BUOYANCY: f32 = 26.;
+/// Extra upward acceleration while holding jump in a fluid.
+pub const SWIM_UP_ACCELERATION: f32 = 20.;
+
+/// Fraction of the vertical velocity fluids take away per second.
+pub const WATER_DRAG: f32 = 4.;
+pub const LAVA_DRAG: f32 = 8.;
+
+/// Speed of climbing up ladders and vines, and the fastest the player slides down them.
+pub const CLIMB_SPEED: f32 = 2.35;
+
+/// Runs one fixed step of player movement and returns how far the player moved.
+///
+/// `yaw` is the camera yaw in degrees, movement input is relative to it.
+pub fn update_player(
+    grid: &impl VoxelGrid,
+    player: &mut Player,
+    aabb: Aabb,
+    input: &InputState,
+    yaw: f32,
+) -> Vec3 {
+    switch_mode(player, input);
+
+    match player.mode {
+        MovementMode::Walk => walk(grid, player, aabb, input, yaw),
+        MovementMode::Fly => fly(grid, player, aabb, input, yaw),
+        MovementMode::Spectator => spectate(player, input, yaw),
+    }
+}
+
+fn switch_mode(player: &mut Player, input: &InputState) {
+    let mode = match player.mode {
+        MovementMode::Spectator if input.toggle_spectator => MovementMode::Walk,
+        _ if input.toggle_spectator => MovementMode::Spectator,
+        MovementMode::Walk if input.toggle_fly => MovementMode::Fly,
+        MovementMode::Fly if input.toggle_fly => MovementMode::Walk,
+        mode => mode,
+    };
+
+    if mode != player.mode {
+        player.mode = mode;
+        player.velocity.y = 0.;
+        player.on_ground = false;
+        player.swimming = false;
+        player.climbing = false;
+    }
+}
+
+/// Walking with gravity, which turns into swimming in fluids and climbing in ladders and vines.
+fn walk(
+    grid: &impl VoxelGrid,
+    player: &mut Player,
+    aabb: Aabb,
+    input: &InputState,
+    yaw: f32,
+) -> Vec3 {
+    let dt = PHYSICS_TIMESTEP;
+
+    let submersion = submersion(grid, &aabb);
+    player.swimming = submersion.is_some();
+    player.climbing = !player.swimming
+        && aabb
+            .shrink(EPSILON)
+            .blocks()
+            .any(|pos| grid.block(pos).is_climbable());
+
+    player.crouching = input.crouch && !player.swimming;
+    player.sprinting = input.sprint && input.forward && !input.backward && !player.crouching;
+    let speed = if player.swimming {
+        SWIM_SPEED
+    } else if player.crouching {
+        CROUCH_SPEED
+    } else if player.sprinting {
+        SPRINT_SPEED
+    } else {
+        WALK_SPEED
+    };
+
+    let wish = wish_direction(input, yaw) * speed;
+    let acceleration = if player.swimming {
+        SWIM_ACCELERATION
+    } else if player.on_ground {
+        GROUND_ACCELERATION
+    } else {
+        AIR_ACCELERATION
+    };
+    let horizontal = approach(
+        vec3(player.velocity.x, 0., player.velocity.z),
+        vec3(wish.x, 0., wish.y),
+        acceleration * dt,
+    );
+    player.velocity.x = horizontal.x;
+    player.velocity.z = horizontal.z;
+
+    if player.on_ground && input.jump && !player.climbing {
+        player.velocity.y = JUMP_VELOCITY;
+        player.on_ground = false;
+    }
+
+    if let Some((fluid, submerged)) = submersion {
+        let mut acceleration = BUOYANCY * submerged - GRAVITY;
+        if input.jump {
+            acceleration += SWIM_UP_ACCELERATION;
+        }
+        let drag = match fluid {
+            Fluid::Water => WATER_DRAG,
+            Fluid::Lava => LAVA_DRAG,
+        };
+        player.velocity.y = (player.velocity.y + acceleration * dt) * (1. - drag * dt).max(0.);
+    } else if player.climbing {
+        player.velocity.y = if input.jump {
+            CLIMB_SPEED
+        } else if input.crouch {
+            0.
+        } else {
+            (player.velocity.y - GRAVITY * dt).max(-CLIMB_SPEED)
+        };
+    } else {
+        player.velocity.y = (player.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+    }
+
+    let mut motion = player.velocity * dt;
+    if player.crouching && player.on_ground {
+        motion = stay_on_edges(grid, &aabb, motion);
+    }
+
+    // Swimmers can step onto the shore without having to touch the bottom.
+    let step_height = if player.on_ground || player.swimming {
+        STEP_HEIGHT
+    } else {
+        0.
+    };
+    let movement = move_aabb(grid, aabb, motion, step_height);
+
+    stop_blocked(player, movement.blocked);
+    player.on_ground = movement.on_ground
+        || (player.velocity.y <= 0. && is_on_ground(grid, &aabb.translate(movement.offset)));
+
+    movement.offset
+}
+
+/// Flying without gravity, landing on the ground goes back to walking.
+fn fly(
+    grid: &impl VoxelGrid,
+    player: &mut Player,
+    aabb: Aabb,
+    input: &InputState,
+    yaw: f32,
+) -> Vec3 {
+    player.crouching = false;
+    player.sprinting = input.sprint && input.forward && !input.backward;
+    let speed = if player.sprinting {
+        FLY_SPRINT_SPEED
+    } else {
+        FLY_SPEED
+    };
+
+    let wish = flying_velocity(input, yaw, speed, FLY_VERTICAL_SPEED);
+    player.velocity = approach(player.velocity, wish, FLY_ACCELERATION * PHYSICS_TIMESTEP);
+
+    let movement = move_aabb(grid, aabb, player.velocity * PHYSICS_TIMESTEP, 0.);
+    stop_blocked(player, movement.blocked);
+    if movement.on_ground {
+        player.mode = MovementMode::Walk;
+    }
+    player.on_ground = movement.on_ground;
+
+    movement.offset
+}
+
+/// Flying through blocks.
+fn spectate(player: &mut Player, input: &InputState, yaw: f32) -> Vec3 {
+    player.crouching = false;
+    player.on_ground = false;
+    player.sprinting = input.sprint && input.forward && !input.backward;
+    let speed = if player.sprinting {
+        SPECTATOR_SPRINT_SPEED
+    } else {
+        SPECTATOR_SPEED
+    };
+
+    let wish = flying_velocity(input, yaw, speed, speed);
+    player.velocity = approach(
+        player.velocity,
+        wish,
+        SPECTATOR_ACCELERATION * PHYSICS_TIMESTEP,
+    );
+
+    player.velocity * PHYSICS_TIMESTEP
+}
+
+/// The fluid the box is in and the fraction of its height under it, measured along the column
+/// through its center.
+fn submersion(grid: &impl VoxelGrid, aabb: &Aabb) -> Option<(Fluid, f32)> {
+    let center = aabb.center().floor().as_ivec3();
+    let mut fluid = None;
+    let mut depth = 0.;
+
+    for y in aabb.min.y.floor() as i32..=aabb.max.y.floor() as i32 {
+        let overlap = aabb.max.y.min(y as f32 + 1.) - aabb.min.y.max(y as f32);
+        if let Some(found) = Fluid::from_block(grid.block(ivec3(center.x, y, center.z))) {
+            if overlap > 0. {
+                fluid.get_or_insert(found);
+                depth += overlap;
+            }
+        }
+    }
+
+    fluid.map(|fluid| (fluid, depth / aabb.size().y))
+}
+
+fn stop_blocked(player: &mut Player, blocked: BVec3) {
+    if blocked.x {
+        player.velocity.x = 0.;
+    }
+    if blocked.y {
+        player.velocity.y = 0.;
+    }
+    if blocked.z {
+        player.velocity.z = 0.;
+    }
+}
+
+/// Velocity the input asks for while flying, jump and crouch move up and down.
+fn flying_velocity(input: &InputState, yaw: f32, speed: f32, vertical_speed: f32) -> Vec3 {
+    let horizontal = wish_direction(input, yaw) * speed;
+    let vertical = (input.jump as i32 - input.crouch as i32) as f32 * vertical_speed;
+    vec3(horizontal.x, vertical, horizontal.y)
+}
+
+/// Unit direction the input asks to move in on the horizontal plane, or zero.
+fn wish_direction(input: &InputState, yaw: f32) -> Vec2 {
+    let forward = vec2(yaw.to_radians().cos(), yaw.to_radians().sin());
+    let right = vec2(-forward.y, forward.x);
+
+    let mut direction = Vec2::ZERO;
+    if input.forward {
+        direction += forward;
+    }
+    if input.backward {
+        direction -= forward;
+    }
+    if input.right {
+        direction += right;
+    }
+    if input.left {
+        direction -= right;
+    }
+
+    direction.normalize_or_zero()
+}
+
+/// Moves `current` towards `target` by at most `max_delta`.
+fn approach(current: Vec3, target: Vec3, max_delta: f32) -> Vec3 {
+    let difference = target - current;
+    if difference.length() <= max_delta {
+        target
+    } else {
+        current + difference.normalize() * max_delta
+    }
+}
+
+/// Shortens horizontal movement that would take the player off the block they stand on.
+fn stay_on_edges(grid: &impl VoxelGrid, aabb: &Aabb, mut motion: Vec3) -> Vec3 {
+    let supported = |x: f32, z: f32| is_on_ground(grid, &aabb.translate(vec3(x, 0., z)));
+
+    while motion.x != 0. && !supported(motion.x, 0.) {
+        motion.x = shorten(motion.x);
+    }
+    while motion.z != 0. && !supported(0., motion.z) {
+        motion.z = shorten(motion.z);
+    }
+    while motion.x != 0. && motion.z != 0. && !supported(motion.x, motion.z) {
+        motion.x = shorten(motion.x);
+        motion.z = shorten(motion.z);
+    }
+
+    motion
+}
+
+fn shorten(distance: f32) -> f32 {
+    if distance.abs() <= EDGE_STEP {
+        0.
+    } else {
+        distance - EDGE_STEP * distance.signum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use glam::{ivec3, vec3, IVec3};
+
+    use crate::state::{
+        ecs::player::{MovementMode, Player, PLAYER_SIZE},
+        input::InputState,
+        physics::{aabb::Aabb, collision::VoxelGrid},
+        voxel::block::Block,
+    };
+
+    use super::update_player;
+
+    impl VoxelGrid for HashMap<IVec3, Block> {
+        fn block(&self, pos: IVec3) -> Block {
+            self.get(&pos).copied().unwrap_or_default()
+        }
+    }
+
+    /// A single row of blocks along +X with its top at y = 0.
+    fn ledge() -> HashSet<IVec3> {
+        (0..4).map(|x| ivec3(x, -1, 0)).collect()
+    }
+
+    /// A stone floor with its top at y = 0 and a column of `block` 5 high on top of it.
+    fn column(block: Block) -> HashMap<IVec3, Block> {
+        let mut grid: HashMap<_, _> = (-2..3)
+            .flat_map(|x| (-2..3).map(move |z| (ivec3(x, -1, z), Block::Stone)))
+            .collect();
+        grid.extend((0..5).map(|y| (ivec3(0, y, 0), block)));
+        grid
+    }
+
+    /// Runs `steps` physics steps facing +X with the input for each step, returns the final box
+    /// and the highest the player got.
+    fn simulate(
+        grid: &impl VoxelGrid,
+        player: &mut Player,
+        input: impl Fn(usize) -> InputState,
+        steps: usize,
+    ) -> (Aabb, f32) {
+        let mut aabb = Aabb::from_center(vec3(0.5, PLAYER_SIZE.y / 2., 0.5), PLAYER_SIZE / 2.);
+        let mut highest = aabb.min.y;
+
+        for step in 0..steps {
+            aabb = aabb.translate(update_player(grid, player, aabb, &input(step), 0.));
+            highest = highest.max(aabb.min.y);
+        }
+
+        (aabb, highest)
+    }
+
+    fn standing() -> Player {
+        Player {
+            on_ground: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn jumps_higher_than_a_block_and_lands() {
+        let input = |step| InputState {
+            jump: step == 0,
+            ..Default::default()
+        };
+        let (aabb, highest) = simulate(&ledge(), &mut standing(), input, 40);
+        assert!(highest > 1.2);
+        assert!(aabb.min.y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn crouching_stops_at_edges() {
+        let input = |_| InputState {
+            forward: true,
+            crouch: true,
+            ..Default::default()
+        };
+        let (aabb, _) = simulate(&ledge(), &mut standing(), input, 200);
+        assert!(aabb.min.y.abs() < 1e-3);
+        assert!(aabb.min.x < 4.);
+
+        let input = |_| InputState {
+            forward: true,
+            ..Default::default()
+        };
+        let (aabb, _) = simulate(&ledge(), &mut standing(), input, 200);
+        assert!(aabb.min.y < -1.);
+    }
+
+    #[test]
+    fn sinks_in_water_and_swims_up_to_the_surface() {
+        let water = column(Block::Water);
+
+        let mut player = standing();
+        let (aabb, highest) = simulate(&water, &mut player, |_| InputState::default(), 80);
+        assert!(player.swimming);
+        assert!(aabb.min.y.abs() < 1e-3 && highest < 1e-3);
+
+        let input = |_| InputState {
+            jump: true,
+            ..Default::default()
+        };
+        let (aabb, _) = simulate(&water, &mut standing(), input, 200);
+        // Floating with the head above the surface at y = 5.
+        assert!(aabb.min.y > 3. && aabb.min.y < 5.);
+        assert!(aabb.max.y > 5.);
+    }
+
+    #[test]
+    fn climbs_ladders_and_holds_on_while_crouching() {
+        let input = |step| InputState {
+            jump: step < 40,
+            crouch: (40..80).contains(&step),
+            ..Default::default()
+        };
+        let mut player = standing();
+        let (aabb, highest) = simulate(&column(Block::Ladder), &mut player, input, 90);
+        assert!(player.climbing);
+        assert!(highest > 2.);
+
+        // Letting go slides down, slower than falling.
+        let slid = highest - aabb.min.y;
+        assert!(slid > 0.1 && slid < 0.7);
+    }
+
+    #[test]
+    fn flies_after_toggling_and_lands_back_walking() {
+        let input = |step| InputState {
+            toggle_fly: step == 0,
+            jump: step < 20,
+            crouch: step >= 80,
+            ..Default::default()
+        };
+        let mut player = standing();
+        let (aabb, highest) = simulate(&ledge(), &mut player, input, 80);
+        assert_eq!(player.mode, MovementMode::Fly);
+        assert!(highest > 2. && (aabb.min.y - highest).abs() < 1e-3);
+
+        let mut player = standing();
+        let (aabb, _) = simulate(&ledge(), &mut player, input, 160);
+        assert_eq!(player.mode, MovementMode::Walk);
+        assert!(player.on_ground && aabb.min.y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn spectators_fly_through_blocks() {
+        let mut player = Player {
+            mode: MovementMode::Spectator,
+            ..Default::default()
+        };
+        let input = |_| InputState {
+            crouch: true,
+            ..Default::default()
+        };
+        let (aabb, _) = simulate(&ledge(), &mut player, input, 40);
+        assert!(aabb.min.y < -5.);
+    }
+}
@@ -0,0 +1,116 @@
+use glam::{IVec3, Vec3};
+
+use super::collision::VoxelGrid;
+
+/// A block found by `raycast`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub pos: IVec3,
+    /// Normal of the face the ray entered through, zero if the ray started inside the block.
+    pub normal: IVec3,
+    /// Distance from the ray origin to where it entered the block.
+    pub distance: f32,
+}
+
+/// Finds the first block that isn't replaceable along a ray within `reach`, stepping through the
+/// grid one block boundary at a time (Amanatides & Woo DDA).
+pub fn raycast(grid: &impl VoxelGrid, origin: Vec3, direction: Vec3, reach: f32) -> Option<RayHit> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    let mut pos = origin.floor().as_ivec3();
+    let mut step = IVec3::ZERO;
+    // Distance along the ray to the next boundary on each axis, and between boundaries.
+    let mut next = Vec3::splat(f32::INFINITY);
+    let mut delta = Vec3::splat(f32::INFINITY);
+
+    for axis in 0..3 {
+        if direction[axis] > 0. {
+            step[axis] = 1;
+            next[axis] = (pos[axis] as f32 + 1. - origin[axis]) / direction[axis];
+        } else if direction[axis] < 0. {
+            step[axis] = -1;
+            next[axis] = (origin[axis] - pos[axis] as f32) / -direction[axis];
+        }
+        if step[axis] != 0 {
+            delta[axis] = 1. / direction[axis].abs();
+        }
+    }
+
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.;
+    loop {
+        if !grid.block(pos).is_replaceable() {
+            return Some(RayHit {
+                pos,
+                normal,
+                distance,
+            });
+        }
+
+        let axis = if next.x < next.y && next.x < next.z {
+            0
+        } else if next.y < next.z {
+            1
+        } else {
+            2
+        };
+
+        distance = next[axis];
+        if distance > reach {
+            return None;
+        }
+
+        pos[axis] += step[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+        next[axis] += delta[axis];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use glam::{ivec3, vec3, IVec3};
+
+    use super::raycast;
+
+    fn grid(blocks: &[IVec3]) -> HashSet<IVec3> {
+        blocks.iter().copied().collect()
+    }
+
+    #[test]
+    fn hits_the_face_facing_the_ray() {
+        let grid = grid(&[ivec3(3, 0, 0), ivec3(5, 0, 0)]);
+
+        let hit = raycast(&grid, vec3(0.5, 0.5, 0.5), vec3(1., 0., 0.), 5.).unwrap();
+        assert_eq!(hit.pos, ivec3(3, 0, 0));
+        assert_eq!(hit.normal, ivec3(-1, 0, 0));
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+
+        let hit = raycast(&grid, vec3(4.5, 0.5, 0.5), vec3(-1., 0., 0.), 5.).unwrap();
+        assert_eq!((hit.pos, hit.normal), (ivec3(3, 0, 0), ivec3(1, 0, 0)));
+    }
+
+    #[test]
+    fn finds_blocks_diagonally() {
+        let grid = grid(&[ivec3(2, 2, -2)]);
+        let direction = vec3(1., 1.2, -1.);
+
+        let hit = raycast(&grid, vec3(0.3, 0.5, 0.6), direction, 5.).unwrap();
+        assert_eq!((hit.pos, hit.normal), (ivec3(2, 2, -2), ivec3(-1, 0, 0)));
+        assert!((hit.distance - 1.7 * direction.length()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn stops_at_reach() {
+        let grid = grid(&[ivec3(0, -6, 0)]);
+
+        assert!(raycast(&grid, vec3(0.5, 0.5, 0.5), vec3(0., -1., 0.), 5.).is_none());
+        assert!(raycast(&grid, vec3(0.5, 0.5, 0.5), vec3(0., -1., 0.), 6.).is_some());
+        assert!(raycast(&grid, vec3(0.5, 0.5, 0.5), vec3(0., 0., 0.), 6.).is_none());
+    }
+}
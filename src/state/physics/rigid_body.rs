@@ -0,0 +1,178 @@
+use glam::{vec2, IVec3, Vec3};
+use hecs::World;
+
+use crate::state::{
+    ecs::{collider::Collider, rigid_body::RigidBody, transform::Transform},
+    voxel::{chunk::chunk_pos, terrain::Terrain},
+};
+
+use super::{
+    aabb::Aabb,
+    collision::{is_on_ground, move_aabb},
+    movement::{GRAVITY, PHYSICS_TIMESTEP, TERMINAL_VELOCITY},
+};
+
+/// Fraction of the velocity air takes away per second.
+const AIR_DRAG: f32 = 0.2;
+
+/// Slowest impact in blocks per second that still bounces, slower ones just stop.
+const BOUNCE_THRESHOLD: f32 = 1.;
+
+/// Bodies resting on the ground slower than this, in blocks per second, for `SLEEP_TIME`
+/// seconds fall asleep.
+const SLEEP_VELOCITY: f32 = 0.05;
+const SLEEP_TIME: f32 = 0.5;
+
+/// How far around a changed block sleeping bodies are woken up.
+const WAKE_MARGIN: f32 = 0.1;
+
+/// Moves every awake rigid body one physics step. Bodies in unloaded chunks stay where they are.
+pub fn step_rigid_bodies(world: &mut World, terrain: &Terrain) {
+    let dt = PHYSICS_TIMESTEP;
+
+    for (_entity, (transform, collider, body)) in
+        world.query_mut::<(&mut Transform, &Collider, &mut RigidBody)>()
+    {
+        let center = transform.translation();
+        if body.sleeping || !terrain.is_loaded(chunk_pos(center.floor().as_ivec3())) {
+            continue;
+        }
+
+        body.velocity.y = (body.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        body.velocity *= 1. - AIR_DRAG * dt;
+
+        let aabb = collider.aabb(center);
+        let movement = move_aabb(terrain, aabb, body.velocity * dt, 0.);
+        let blocked = [movement.blocked.x, movement.blocked.y, movement.blocked.z];
+        for (axis, blocked) in blocked.into_iter().enumerate() {
+            if blocked {
+                let bounced = -body.velocity[axis] * body.restitution;
+                body.velocity[axis] = if bounced.abs() < BOUNCE_THRESHOLD {
+                    0.
+                } else {
+                    bounced
+                };
+            }
+        }
+
+        let on_ground = movement.on_ground
+            || (body.velocity.y <= 0. && is_on_ground(terrain, &aabb.translate(movement.offset)));
+        if on_ground {
+            let horizontal = vec2(body.velocity.x, body.velocity.z);
+            let speed = horizontal.length();
+            if speed > 0. {
+                let slowed = (speed - body.friction * GRAVITY * dt).max(0.);
+                let horizontal = horizontal * slowed / speed;
+                body.velocity.x = horizontal.x;
+                body.velocity.z = horizontal.y;
+            }
+        }
+
+        if on_ground && body.velocity.length() < SLEEP_VELOCITY {
+            body.still_time += dt;
+            if body.still_time >= SLEEP_TIME {
+                body.sleeping = true;
+                body.velocity = Vec3::ZERO;
+            }
+        } else {
+            body.still_time = 0.;
+        }
+
+        *transform = transform.translate(movement.offset);
+    }
+}
+
+/// Wakes the sleeping bodies touching the block at `pos`, so they react to it changing.
+pub fn wake_rigid_bodies(world: &mut World, pos: IVec3) {
+    let area = Aabb::block(pos).shrink(-WAKE_MARGIN);
+
+    for (_entity, (transform, collider, body)) in
+        world.query_mut::<(&Transform, &Collider, &mut RigidBody)>()
+    {
+        if body.sleeping && collider.aabb(transform.translation()).intersects(&area) {
+            body.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, vec3, Vec3};
+    use hecs::{Entity, World};
+
+    use crate::state::{
+        ecs::{collider::Collider, rigid_body::RigidBody, transform::Transform},
+        voxel::{block::Block, terrain::Terrain},
+    };
+
+    use super::{step_rigid_bodies, wake_rigid_bodies};
+
+    fn spawn(world: &mut World, center: Vec3, body: RigidBody) -> Entity {
+        world.spawn((
+            Transform::from_translation(center),
+            Collider::new(Vec3::splat(0.5)),
+            body,
+        ))
+    }
+
+    fn state(world: &World, entity: Entity) -> (Vec3, RigidBody) {
+        let transform = world.get::<&Transform>(entity).unwrap();
+        let body = world.get::<&RigidBody>(entity).unwrap();
+        (transform.translation(), *body)
+    }
+
+    #[test]
+    fn bounces_and_falls_asleep_on_the_ground() {
+        let terrain = Terrain::stone_floor(1);
+        let mut world = World::new();
+        let entity = spawn(&mut world, vec3(8., 6., 8.), RigidBody::new(0.5, 0.5));
+
+        let mut bounced = false;
+        for _ in 0..400 {
+            step_rigid_bodies(&mut world, &terrain);
+            let (center, body) = state(&world, entity);
+            bounced |= center.y < 3. && body.velocity.y > 0.;
+        }
+
+        let (center, body) = state(&world, entity);
+        assert!(bounced);
+        assert!(body.sleeping);
+        assert!((center.y - 1.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn friction_stops_sliding() {
+        let terrain = Terrain::stone_floor(1);
+        let mut world = World::new();
+        let body = RigidBody::new(0.5, 0.).with_velocity(vec3(5., 0., 0.));
+        let entity = spawn(&mut world, vec3(4., 1.25, 8.), body);
+
+        for _ in 0..200 {
+            step_rigid_bodies(&mut world, &terrain);
+        }
+
+        // Sliding at 5 blocks per second stops after 5² / (2 * 0.5 * 28) blocks.
+        let (center, body) = state(&world, entity);
+        assert!(body.sleeping);
+        assert!((center.x - 4. - 25. / 28.).abs() < 0.2);
+    }
+
+    #[test]
+    fn wakes_up_when_the_ground_changes() {
+        let mut terrain = Terrain::stone_floor(1);
+        let mut world = World::new();
+        let mut body = RigidBody::new(0.5, 0.);
+        body.sleeping = true;
+        let entity = spawn(&mut world, vec3(8.5, 1.25, 8.5), body);
+
+        terrain.set_block(ivec3(8, 0, 8), Block::Air);
+        step_rigid_bodies(&mut world, &terrain);
+        assert_eq!(state(&world, entity).0.y, 1.25);
+
+        wake_rigid_bodies(&mut world, ivec3(8, 0, 8));
+        for _ in 0..40 {
+            step_rigid_bodies(&mut world, &terrain);
+        }
+        assert!(state(&world, entity).0.y < 0.);
+    }
+}
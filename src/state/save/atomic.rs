@@ -0,0 +1,108 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+pub const TEMP_EXTENSION: &str = "tmp";
+
+/// Writes `bytes` to `path` so that a crash at any point leaves either the old or the new
+/// contents on disk, never a partially written file.
+///
+/// The data is written and synced to a temporary file next to the target, which is then renamed
+/// over it. Renames within a directory are atomic on the platforms we support.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path);
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+
+    // Persist the rename itself. Directories can't be opened for syncing on every platform,
+    // so this is best effort.
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes temporary files left behind by writes that were interrupted by a crash.
+pub fn remove_stale_temp_files(dir: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == TEMP_EXTENSION) {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(TEMP_EXTENSION);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{remove_stale_temp_files, temp_path, write_atomic};
+
+    fn test_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("minerust-atomic-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_the_file_without_leaving_temp_files() {
+        let dir = test_dir("replace");
+        let path = dir.join("level.dat");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_write_keeps_the_old_file() {
+        let dir = test_dir("fail");
+        let path = dir.join("level.dat");
+        write_atomic(&path, b"old").unwrap();
+
+        // A directory in the way of the temporary file makes creating it fail.
+        fs::create_dir(temp_path(&path)).unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+    }
+
+    #[test]
+    fn stale_temp_files_are_removed() {
+        let dir = test_dir("stale");
+        let path = dir.join("level.dat");
+        write_atomic(&path, b"old").unwrap();
+        // A crash after the temporary file was written but before the rename.
+        fs::write(temp_path(&path), b"new").unwrap();
+
+        remove_stale_temp_files(&dir).unwrap();
+        assert!(!temp_path(&path).exists());
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+    }
+}
@@ -0,0 +1,393 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use glam::IVec3;
+
+use super::{
+    atomic::{write_atomic, TEMP_EXTENSION},
+    region::region_file_name,
+    LEVEL_FILE, REGION_DIR,
+};
+
+pub const BACKUP_DIR: &str = "backups";
+
+/// Length of a `YYYY-MM-DDTHH-MM-SS` snapshot id.
+const TIMESTAMP_LEN: usize = 19;
+
+/// A snapshot of a world directory, named after the UTC time it was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: String,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapshotStats {
+    pub snapshot: Snapshot,
+    /// Files hard linked from the previous snapshot because they didn't change.
+    pub linked: usize,
+    pub copied: usize,
+}
+
+/// Snapshots of one world, stored in `backups/<world name>` next to the world directory.
+///
+/// Snapshots are incremental: files that are identical to the ones in the previous snapshot are
+/// hard linked instead of copied, so a snapshot only costs the space of the regions that changed.
+/// This is safe because world files are never modified in place, saving always replaces them.
+pub struct BackupStore {
+    dir: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn for_world(world_dir: &Path) -> Self {
+        let name = world_dir.file_name().unwrap_or_default();
+        let parent = world_dir.parent().unwrap_or_else(|| Path::new(""));
+        Self::new(parent.join(BACKUP_DIR).join(name))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Lists the finished snapshots, oldest first.
+    pub fn snapshots(&self) -> io::Result<Vec<Snapshot>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let id = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() && !is_temp(&entry.path()) {
+                snapshots.push(Snapshot {
+                    id,
+                    path: entry.path(),
+                });
+            }
+        }
+
+        snapshots.sort_by(|a, b| snapshot_order(&a.id).cmp(&snapshot_order(&b.id)));
+        Ok(snapshots)
+    }
+
+    pub fn snapshot(&self, id: &str) -> io::Result<Snapshot> {
+        self.snapshots()?
+            .into_iter()
+            .find(|s| s.id == id)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No snapshot named {}.", id),
+                )
+            })
+    }
+
+    /// Takes a snapshot of the world as it is on disk.
+    ///
+    /// The snapshot is built in a temporary directory and renamed into place once complete, so
+    /// an interrupted backup never shows up as a snapshot.
+    pub fn create(&self, world_dir: &Path) -> io::Result<SnapshotStats> {
+        fs::create_dir_all(&self.dir)?;
+        self.remove_unfinished()?;
+
+        let previous = self.snapshots()?.pop();
+        let id = self.unused_id(&utc_timestamp(now()));
+        let temp = self.dir.join(format!("{}.{}", id, TEMP_EXTENSION));
+        fs::create_dir_all(temp.join(REGION_DIR))?;
+
+        let mut linked = 0;
+        let mut copied = 0;
+        for file in world_files(world_dir)? {
+            let source = world_dir.join(&file);
+            let target = temp.join(&file);
+
+            let unchanged = previous
+                .as_ref()
+                .map(|p| p.path.join(&file))
+                .filter(|p| same_contents(p, &source));
+
+            match unchanged {
+                Some(previous) if fs::hard_link(&previous, &target).is_ok() => linked += 1,
+                _ => {
+                    fs::copy(&source, &target)?;
+                    copied += 1;
+                }
+            }
+        }
+
+        let path = self.dir.join(&id);
+        fs::rename(&temp, &path)?;
+
+        Ok(SnapshotStats {
+            snapshot: Snapshot { id, path },
+            linked,
+            copied,
+        })
+    }
+
+    /// Deletes the oldest snapshots so that at most `keep` remain.
+    ///
+    /// Hard linked files stay on disk as long as a newer snapshot still uses them.
+    pub fn prune(&self, keep: usize) -> io::Result<usize> {
+        let snapshots = self.snapshots()?;
+        let remove = snapshots.len().saturating_sub(keep);
+        for snapshot in &snapshots[..remove] {
+            fs::remove_dir_all(&snapshot.path)?;
+        }
+        Ok(remove)
+    }
+
+    /// Replaces the whole world with the snapshot.
+    ///
+    /// The world must not be open in the game while it is restored.
+    pub fn restore_world(&self, snapshot: &Snapshot, world_dir: &Path) -> io::Result<()> {
+        let snapshot_files = world_files(&snapshot.path)?;
+
+        fs::create_dir_all(world_dir.join(REGION_DIR))?;
+        for file in &snapshot_files {
+            write_atomic(&world_dir.join(file), &fs::read(snapshot.path.join(file))?)?;
+        }
+
+        for file in world_files(world_dir)? {
+            if !snapshot_files.contains(&file) {
+                fs::remove_file(world_dir.join(file))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces a single region file with its version in the snapshot, leaving the rest of the
+    /// world as it is. If the region didn't exist when the snapshot was taken, it is removed so
+    /// the terrain generator recreates it.
+    pub fn restore_region(
+        &self,
+        snapshot: &Snapshot,
+        world_dir: &Path,
+        region_pos: IVec3,
+    ) -> io::Result<()> {
+        let file = Path::new(REGION_DIR).join(region_file_name(region_pos));
+        let target = world_dir.join(&file);
+
+        match fs::read(snapshot.path.join(&file)) {
+            Ok(bytes) => write_atomic(&target, &bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => match fs::remove_file(&target) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    fn unused_id(&self, base: &str) -> String {
+        let mut id = base.to_string();
+        let mut n = 1;
+        while self.dir.join(&id).exists() {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        id
+    }
+
+    fn remove_unfinished(&self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_dir() && is_temp(&path) {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The files that make up a world, relative to its directory.
+fn world_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if dir.join(LEVEL_FILE).is_file() {
+        files.push(PathBuf::from(LEVEL_FILE));
+    }
+
+    match fs::read_dir(dir.join(REGION_DIR)) {
+        Ok(entries) => {
+            for entry in entries {
+                let path = entry?.path();
+                if path.is_file() && path.extension().is_some_and(|e| e == "dat") {
+                    files.push(Path::new(REGION_DIR).join(path.file_name().unwrap()));
+                }
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Sorts snapshots taken in the same second by the number appended to their id.
+fn snapshot_order(id: &str) -> (&str, u32) {
+    let suffix = id.get(TIMESTAMP_LEN + 1..).map(str::parse);
+    match (id.get(..TIMESTAMP_LEN), suffix) {
+        (Some(time), Some(Ok(n))) => (time, n),
+        _ => (id, 0),
+    }
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(ma), Ok(mb)) if ma.len() == mb.len() => {
+            matches!((fs::read(a), fs::read(b)), (Ok(a), Ok(b)) if a == b)
+        }
+        _ => false,
+    }
+}
+
+fn is_temp(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == TEMP_EXTENSION)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DDTHH-MM-SS` in UTC, which sorts
+/// chronologically and is a valid file name everywhere.
+fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let secs_of_day = secs % 86400;
+
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use glam::ivec3;
+
+    use crate::state::save::{region::region_file_name, LEVEL_FILE, REGION_DIR};
+
+    use super::{utc_timestamp, BackupStore};
+
+    fn test_world(test: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("minerust-backup-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let world = root.join("world");
+        fs::create_dir_all(world.join(REGION_DIR)).unwrap();
+        fs::write(world.join(LEVEL_FILE), b"level").unwrap();
+        write_region(&world, 0, b"a");
+        write_region(&world, 1, b"b");
+        world
+    }
+
+    fn write_region(world: &Path, x: i32, bytes: &[u8]) {
+        let path = world
+            .join(REGION_DIR)
+            .join(region_file_name(ivec3(x, 0, 0)));
+        fs::write(path, bytes).unwrap();
+    }
+
+    fn read_region(world: &Path, x: i32) -> Option<Vec<u8>> {
+        fs::read(
+            world
+                .join(REGION_DIR)
+                .join(region_file_name(ivec3(x, 0, 0))),
+        )
+        .ok()
+    }
+
+    #[test]
+    fn snapshots_link_unchanged_files() {
+        let world = test_world("link");
+        let store = BackupStore::for_world(&world);
+
+        let first = store.create(&world).unwrap();
+        assert_eq!((first.copied, first.linked), (3, 0));
+
+        write_region(&world, 1, b"changed");
+        let second = store.create(&world).unwrap();
+        assert_eq!((second.copied, second.linked), (1, 2));
+        assert_ne!(first.snapshot.id, second.snapshot.id);
+        assert_eq!(store.snapshots().unwrap().len(), 2);
+
+        assert_eq!(store.prune(1).unwrap(), 1);
+        assert_eq!(store.snapshots().unwrap(), vec![second.snapshot.clone()]);
+        assert_eq!(
+            fs::read(second.snapshot.path.join(LEVEL_FILE)).unwrap(),
+            b"level"
+        );
+
+        fs::remove_dir_all(world.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn restores_world_and_single_regions() {
+        let world = test_world("restore");
+        let store = BackupStore::for_world(&world);
+        let snapshot = store.create(&world).unwrap().snapshot;
+
+        write_region(&world, 0, b"changed");
+        write_region(&world, 1, b"changed");
+        write_region(&world, 2, b"new");
+
+        store
+            .restore_region(&snapshot, &world, ivec3(0, 0, 0))
+            .unwrap();
+        assert_eq!(read_region(&world, 0).unwrap(), b"a");
+        assert_eq!(read_region(&world, 1).unwrap(), b"changed");
+
+        store
+            .restore_region(&snapshot, &world, ivec3(2, 0, 0))
+            .unwrap();
+        assert_eq!(read_region(&world, 2), None);
+
+        write_region(&world, 2, b"new");
+        store.restore_world(&snapshot, &world).unwrap();
+        assert_eq!(read_region(&world, 1).unwrap(), b"b");
+        assert_eq!(read_region(&world, 2), None);
+
+        fs::remove_dir_all(world.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00-00-00");
+        assert_eq!(utc_timestamp(951_827_696), "2000-02-29T12-34-56");
+    }
+}
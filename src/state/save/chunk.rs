@@ -0,0 +1,166 @@
+use std::io;
+
+use crate::state::voxel::{
+    block::Block,
+    chunk::{Chunk, CHUNK_VOLUME},
+};
+
+use super::{
+    codec::{invalid_data, ByteReader, ByteWriter},
+    entity::SavedEntity,
+    migration::{migrate_chunk, DATA_VERSION},
+    region::ChunkEntry,
+};
+
+/// Block section of the chunk payload since data version 2.
+///
+/// Since data version 4 the payload is the length of this section, the section itself, and then
+/// the chunk's saved entities. Since version 5 they are followed by the fluid state bytes of
+/// flowing fluids as `(block index, state)` pairs.
+///
+/// Blocks are stored by name through a per-chunk palette, followed by run-length encoded
+/// `(count, palette index)` pairs. Names keep saves independent of the numeric block ids.
+pub struct PaletteChunk {
+    pub palette: Vec<String>,
+    pub indices: Vec<u8>,
+}
+
+impl PaletteChunk {
+    pub fn read(payload: &[u8]) -> io::Result<Self> {
+        let mut reader = ByteReader::new(payload);
+
+        let palette_len = reader.u8()?;
+        let mut palette = Vec::with_capacity(palette_len as usize);
+        for _ in 0..palette_len {
+            let len = reader.u8()?;
+            let name = std::str::from_utf8(reader.bytes(len as usize)?)
+                .map_err(|_| invalid_data("Block name isn't valid UTF-8."))?;
+            palette.push(name.to_string());
+        }
+
+        let mut indices = Vec::with_capacity(CHUNK_VOLUME);
+        while !reader.is_at_end() {
+            let run = reader.u16()? as usize;
+            let index = reader.u8()?;
+            if index as usize >= palette.len() {
+                return Err(invalid_data("Palette index out of range."));
+            }
+            if indices.len() + run > CHUNK_VOLUME {
+                return Err(invalid_data("Chunk has too many blocks."));
+            }
+            indices.extend(std::iter::repeat_n(index, run));
+        }
+
+        if indices.len() != CHUNK_VOLUME {
+            return Err(invalid_data("Chunk has too few blocks."));
+        }
+
+        Ok(Self { palette, indices })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+
+        writer.put_u8(self.palette.len() as u8);
+        for name in &self.palette {
+            writer.put_u8(name.len() as u8);
+            writer.put_bytes(name.as_bytes());
+        }
+
+        let mut i = 0;
+        while i < self.indices.len() {
+            let index = self.indices[i];
+            let run = self.indices[i..]
+                .iter()
+                .take_while(|b| **b == index)
+                .count();
+            writer.put_u16(run as u16);
+            writer.put_u8(index);
+            i += run;
+        }
+
+        writer.into_bytes()
+    }
+}
+
+pub fn encode_chunk(chunk: &Chunk, entities: &[SavedEntity]) -> ChunkEntry {
+    let mut palette = Vec::<Block>::new();
+    let indices = chunk
+        .blocks()
+        .iter()
+        .map(|block| match palette.iter().position(|b| b == block) {
+            Some(index) => index as u8,
+            None => {
+                palette.push(*block);
+                (palette.len() - 1) as u8
+            }
+        })
+        .collect();
+
+    let palette = palette.iter().map(|b| b.name().to_string()).collect();
+    let blocks = PaletteChunk { palette, indices }.to_bytes();
+
+    let mut writer = ByteWriter::new();
+    writer.put_u32(blocks.len() as u32);
+    writer.put_bytes(&blocks);
+    writer.put_u32(entities.len() as u32);
+    for entity in entities {
+        entity.write(&mut writer);
+    }
+
+    writer.put_u32(chunk.fluid_levels().len() as u32);
+    for (index, level) in chunk.fluid_levels() {
+        writer.put_u16(*index);
+        writer.put_u8(*level);
+    }
+
+    ChunkEntry::current(writer.into_bytes())
+}
+
+/// Decodes a chunk entry of any supported version, migrating it to the current one first.
+pub fn decode_chunk(entry: &ChunkEntry) -> io::Result<(Chunk, Vec<SavedEntity>)> {
+    if entry.version > DATA_VERSION {
+        return Err(invalid_data(format!(
+            "Chunk was saved with data version {}, newer than {}.",
+            entry.version, DATA_VERSION
+        )));
+    }
+
+    let payload = migrate_chunk(entry.version, entry.payload.clone())?;
+    let mut reader = ByteReader::new(&payload);
+
+    let len = reader.u32()?;
+    let blocks = PaletteChunk::read(reader.bytes(len as usize)?)?;
+
+    let palette = blocks
+        .palette
+        .iter()
+        .map(|name| {
+            Block::from_name(name).ok_or_else(|| invalid_data(format!("Unknown block {}.", name)))
+        })
+        .collect::<io::Result<Vec<Block>>>()?;
+    let mut chunk = Chunk::from_blocks(
+        blocks
+            .indices
+            .iter()
+            .map(|i| palette[*i as usize])
+            .collect(),
+    );
+
+    let count = reader.u32()?;
+    let entities = (0..count)
+        .map(|_| SavedEntity::read(&mut reader))
+        .collect::<io::Result<_>>()?;
+
+    let count = reader.u32()?;
+    for _ in 0..count {
+        let index = reader.u16()? as usize;
+        let level = reader.u8()?;
+        if index >= CHUNK_VOLUME {
+            return Err(invalid_data("Fluid level index out of range."));
+        }
+        chunk.set_fluid_level(Chunk::local_from_index(index), level);
+    }
+
+    Ok((chunk, entities))
+}
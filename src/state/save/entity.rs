@@ -0,0 +1,552 @@
+use std::{collections::HashMap, io};
+
+use glam::{vec3, IVec3, Mat4, Vec3};
+use hecs::{Component, Entity, EntityBuilder, EntityRef, World};
+
+use crate::{
+    render::shadow::CastShadow,
+    state::{
+        ecs::{
+            asset::{MeshAsset, RenderAsset},
+            collider::Collider,
+            falling_block::FallingBlock,
+            item::ItemDrop,
+            light::{DirectionalLight, PointLight, SpotLight},
+            persistent::Persistent,
+            rigid_body::RigidBody,
+            transform::Transform,
+        },
+        voxel::{block::Block, chunk::chunk_pos},
+    },
+};
+
+use super::codec::{invalid_data, ByteReader, ByteWriter};
+
+/// A component that can be written to and read from a world save.
+///
+/// `NAME` identifies the component in the save, so it must never change once worlds have been
+/// saved with it.
+pub trait SaveComponent: Component + Sized {
+    const NAME: &'static str;
+
+    fn save(&self, writer: &mut ByteWriter);
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self>;
+}
+
+/// A saved entity: the encoded data of each of its registered components, by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SavedEntity {
+    pub components: Vec<(String, Vec<u8>)>,
+}
+
+impl SavedEntity {
+    pub fn write(&self, writer: &mut ByteWriter) {
+        writer.put_u8(self.components.len() as u8);
+        for (name, data) in &self.components {
+            writer.put_u8(name.len() as u8);
+            writer.put_bytes(name.as_bytes());
+            writer.put_u32(data.len() as u32);
+            writer.put_bytes(data);
+        }
+    }
+
+    pub fn read(reader: &mut ByteReader) -> io::Result<Self> {
+        let count = reader.u8()?;
+        let mut components = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = reader.u8()?;
+            let name = std::str::from_utf8(reader.bytes(len as usize)?)
+                .map_err(|_| invalid_data("Component name isn't valid UTF-8."))?
+                .to_string();
+            let len = reader.u32()?;
+            components.push((name, reader.bytes(len as usize)?.to_vec()));
+        }
+
+        Ok(Self { components })
+    }
+}
+
+struct RegisteredComponent {
+    name: &'static str,
+    save: fn(&EntityRef) -> Option<Vec<u8>>,
+    load: fn(&[u8], &mut EntityBuilder) -> io::Result<()>,
+}
+
+/// The set of components that are stored when [`Persistent`] entities are saved.
+///
+/// Components that aren't registered, like GPU handles, are skipped when saving. Components in a
+/// save that aren't registered anymore are skipped with a warning when loading.
+#[derive(Default)]
+pub struct ComponentRegistry {
+    components: Vec<RegisteredComponent>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with all of the game's saveable components.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register::<Persistent>();
+        registry.register::<Transform>();
+        registry.register::<CastShadow>();
+        registry.register::<RenderAsset>();
+        registry.register::<FallingBlock>();
+        registry.register::<ItemDrop>();
+        registry.register::<Collider>();
+        registry.register::<RigidBody>();
+        registry.register::<DirectionalLight>();
+        registry.register::<PointLight>();
+        registry.register::<SpotLight>();
+        registry
+    }
+
+    pub fn register<T: SaveComponent>(&mut self) {
+        assert!(
+            self.components.iter().all(|c| c.name != T::NAME),
+            "Component {} is already registered.",
+            T::NAME
+        );
+
+        self.components.push(RegisteredComponent {
+            name: T::NAME,
+            save: |entity| {
+                let component = entity.get::<&T>()?;
+                let mut writer = ByteWriter::new();
+                component.save(&mut writer);
+                Some(writer.into_bytes())
+            },
+            load: |data, builder| {
+                builder.add(T::load(&mut ByteReader::new(data))?);
+                Ok(())
+            },
+        });
+    }
+
+    pub fn save_entity(&self, entity: &EntityRef) -> SavedEntity {
+        let components = self
+            .components
+            .iter()
+            .filter_map(|c| Some((c.name.to_string(), (c.save)(entity)?)))
+            .collect();
+
+        SavedEntity { components }
+    }
+
+    /// Saves every persistent entity, grouped by the chunk its transform is in.
+    pub fn save_world(&self, world: &World) -> HashMap<IVec3, Vec<SavedEntity>> {
+        let mut chunks = HashMap::<IVec3, Vec<SavedEntity>>::new();
+
+        for (entity, transform) in world.query::<&Transform>().with::<&Persistent>().iter() {
+            let entity = world.entity(entity).unwrap();
+            let chunk = chunk_pos(transform.translation().floor().as_ivec3());
+            chunks
+                .entry(chunk)
+                .or_default()
+                .push(self.save_entity(&entity));
+        }
+
+        chunks
+    }
+
+    pub fn spawn(&self, world: &mut World, saved: &SavedEntity) -> io::Result<Entity> {
+        let mut builder = EntityBuilder::new();
+
+        for (name, data) in &saved.components {
+            match self.components.iter().find(|c| c.name == name) {
+                Some(component) => (component.load)(data, &mut builder)?,
+                None => eprintln!("Warning: skipping unknown saved component {}.", name),
+            }
+        }
+
+        Ok(world.spawn(builder.build()))
+    }
+}
+
+impl SaveComponent for Persistent {
+    const NAME: &'static str = "persistent";
+
+    fn save(&self, _writer: &mut ByteWriter) {}
+
+    fn load(_reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(Persistent)
+    }
+}
+
+impl SaveComponent for CastShadow {
+    const NAME: &'static str = "cast_shadow";
+
+    fn save(&self, _writer: &mut ByteWriter) {}
+
+    fn load(_reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(CastShadow)
+    }
+}
+
+impl SaveComponent for Transform {
+    const NAME: &'static str = "transform";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        for value in self.matrix().to_cols_array() {
+            writer.put_f32(value);
+        }
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        let mut cols = [0.; 16];
+        for value in &mut cols {
+            *value = reader.f32()?;
+        }
+        Ok(Transform::from_matrix(Mat4::from_cols_array(&cols)))
+    }
+}
+
+impl SaveComponent for RenderAsset {
+    const NAME: &'static str = "render_asset";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        match self.mesh {
+            MeshAsset::Cube(size) => {
+                writer.put_u8(0);
+                writer.put_f32(size);
+            }
+        }
+        writer.put_u8(self.texture.len() as u8);
+        writer.put_bytes(self.texture.as_bytes());
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        let mesh = match reader.u8()? {
+            0 => MeshAsset::Cube(reader.f32()?),
+            kind => return Err(invalid_data(format!("Unknown mesh asset {}.", kind))),
+        };
+        let len = reader.u8()?;
+        let texture = std::str::from_utf8(reader.bytes(len as usize)?)
+            .map_err(|_| invalid_data("Texture path isn't valid UTF-8."))?
+            .to_string();
+
+        Ok(RenderAsset { mesh, texture })
+    }
+}
+
+impl SaveComponent for FallingBlock {
+    const NAME: &'static str = "falling_block";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_block(writer, self.block);
+        writer.put_f32(self.velocity);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(FallingBlock {
+            block: read_block(reader)?,
+            velocity: reader.f32()?,
+        })
+    }
+}
+
+impl SaveComponent for ItemDrop {
+    const NAME: &'static str = "item_drop";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_block(writer, self.block);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(ItemDrop {
+            block: read_block(reader)?,
+        })
+    }
+}
+
+impl SaveComponent for Collider {
+    const NAME: &'static str = "collider";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_vec3(writer, self.half_extents);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(Collider {
+            half_extents: read_vec3(reader)?,
+        })
+    }
+}
+
+impl SaveComponent for RigidBody {
+    const NAME: &'static str = "rigid_body";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_vec3(writer, self.velocity);
+        writer.put_f32(self.friction);
+        writer.put_f32(self.restitution);
+        writer.put_u8(self.sleeping as u8);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        let velocity = read_vec3(reader)?;
+        let mut body = RigidBody::new(reader.f32()?, reader.f32()?).with_velocity(velocity);
+        body.sleeping = reader.u8()? != 0;
+        Ok(body)
+    }
+}
+
+impl SaveComponent for DirectionalLight {
+    const NAME: &'static str = "directional_light";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_vec3(writer, self.color);
+        writer.put_f32(self.intensity);
+        writer.put_u8(self.cast_shadows as u8);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(DirectionalLight {
+            color: read_vec3(reader)?,
+            intensity: reader.f32()?,
+            cast_shadows: reader.u8()? != 0,
+        })
+    }
+}
+
+impl SaveComponent for PointLight {
+    const NAME: &'static str = "point_light";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_vec3(writer, self.color);
+        writer.put_f32(self.intensity);
+        writer.put_f32(self.range);
+        writer.put_u8(self.cast_shadows as u8);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(PointLight {
+            color: read_vec3(reader)?,
+            intensity: reader.f32()?,
+            range: reader.f32()?,
+            cast_shadows: reader.u8()? != 0,
+        })
+    }
+}
+
+impl SaveComponent for SpotLight {
+    const NAME: &'static str = "spot_light";
+
+    fn save(&self, writer: &mut ByteWriter) {
+        put_vec3(writer, self.color);
+        writer.put_f32(self.intensity);
+        writer.put_f32(self.range);
+        writer.put_f32(self.inner_angle);
+        writer.put_f32(self.outer_angle);
+        writer.put_u8(self.cast_shadows as u8);
+    }
+
+    fn load(reader: &mut ByteReader) -> io::Result<Self> {
+        Ok(SpotLight {
+            color: read_vec3(reader)?,
+            intensity: reader.f32()?,
+            range: reader.f32()?,
+            inner_angle: reader.f32()?,
+            outer_angle: reader.f32()?,
+            cast_shadows: reader.u8()? != 0,
+        })
+    }
+}
+
+fn put_vec3(writer: &mut ByteWriter, value: Vec3) {
+    for value in value.to_array() {
+        writer.put_f32(value);
+    }
+}
+
+fn read_vec3(reader: &mut ByteReader) -> io::Result<Vec3> {
+    Ok(vec3(reader.f32()?, reader.f32()?, reader.f32()?))
+}
+
+/// Blocks are saved by name, like in chunks.
+fn put_block(writer: &mut ByteWriter, block: Block) {
+    writer.put_u8(block.name().len() as u8);
+    writer.put_bytes(block.name().as_bytes());
+}
+
+fn read_block(reader: &mut ByteReader) -> io::Result<Block> {
+    let len = reader.u8()?;
+    let name = std::str::from_utf8(reader.bytes(len as usize)?)
+        .map_err(|_| invalid_data("Block name isn't valid UTF-8."))?;
+    Block::from_name(name).ok_or_else(|| invalid_data(format!("Unknown block {}.", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Mat4, Quat};
+    use hecs::World;
+
+    use crate::{
+        render::shadow::CastShadow,
+        state::{
+            ecs::{
+                asset::{MeshAsset, RenderAsset},
+                collider::Collider,
+                falling_block::FallingBlock,
+                item::ItemDrop,
+                light::{DirectionalLight, PointLight, SpotLight},
+                persistent::Persistent,
+                rigid_body::RigidBody,
+                transform::Transform,
+            },
+            save::codec::{ByteReader, ByteWriter},
+            voxel::block::Block,
+        },
+    };
+
+    use super::{ComponentRegistry, SaveComponent, SavedEntity};
+
+    fn round_trip<T: SaveComponent>(component: &T) -> T {
+        let mut writer = ByteWriter::new();
+        component.save(&mut writer);
+        let bytes = writer.into_bytes();
+        let mut reader = ByteReader::new(&bytes);
+        let loaded = T::load(&mut reader).unwrap();
+        assert!(reader.is_at_end(), "{} left bytes unread", T::NAME);
+        loaded
+    }
+
+    fn directional_light() -> DirectionalLight {
+        DirectionalLight {
+            color: vec3(1., 0.9, 0.7),
+            intensity: 3.5,
+            cast_shadows: true,
+        }
+    }
+
+    fn point_light() -> PointLight {
+        PointLight {
+            color: vec3(1., 0.6, 0.2),
+            intensity: 2.,
+            range: 12.,
+            cast_shadows: false,
+        }
+    }
+
+    fn spot_light() -> SpotLight {
+        SpotLight {
+            color: vec3(0.8, 0.8, 1.),
+            intensity: 4.,
+            range: 20.,
+            inner_angle: 0.3,
+            outer_angle: 0.5,
+            cast_shadows: true,
+        }
+    }
+
+    fn rigid_body() -> RigidBody {
+        let mut body = RigidBody::new(0.4, 0.25).with_velocity(vec3(1., -2.5, 0.75));
+        body.sleeping = true;
+        body
+    }
+
+    fn transform() -> Transform {
+        Transform::from_matrix(Mat4::from_scale_rotation_translation(
+            vec3(0.5, 0.5, 0.5),
+            Quat::from_rotation_y(1.2),
+            vec3(10.5, 64., -3.25),
+        ))
+    }
+
+    fn render_asset() -> RenderAsset {
+        RenderAsset {
+            mesh: MeshAsset::Cube(0.25),
+            texture: "assets/blocks/sand.png".to_string(),
+        }
+    }
+
+    #[test]
+    fn components_round_trip() {
+        round_trip(&Persistent);
+        round_trip(&CastShadow);
+        assert_eq!(round_trip(&transform()).matrix(), transform().matrix());
+        assert_eq!(round_trip(&render_asset()), render_asset());
+
+        let falling = FallingBlock {
+            block: Block::Sand,
+            velocity: -4.5,
+        };
+        assert_eq!(round_trip(&falling), falling);
+        let drop = ItemDrop {
+            block: Block::Gravel,
+        };
+        assert_eq!(round_trip(&drop), drop);
+
+        let collider = Collider::new(vec3(0.6, 1.8, 0.6));
+        assert_eq!(round_trip(&collider).half_extents, collider.half_extents);
+        assert_eq!(round_trip(&rigid_body()), rigid_body());
+
+        assert_eq!(round_trip(&directional_light()), directional_light());
+        assert_eq!(round_trip(&point_light()), point_light());
+        assert_eq!(round_trip(&spot_light()), spot_light());
+    }
+
+    #[test]
+    fn registered_components_round_trip_through_the_registry() {
+        let registry = ComponentRegistry::with_defaults();
+        let mut world = World::new();
+        let entity = world.spawn((Persistent, CastShadow, transform(), render_asset()));
+        world
+            .insert(
+                entity,
+                (
+                    FallingBlock {
+                        block: Block::Sand,
+                        velocity: -1.,
+                    },
+                    ItemDrop { block: Block::Sand },
+                    Collider::new(vec3(0.25, 0.25, 0.25)),
+                    rigid_body(),
+                ),
+            )
+            .unwrap();
+        world
+            .insert(entity, (directional_light(), point_light(), spot_light()))
+            .unwrap();
+
+        let saved = registry.save_entity(&world.entity(entity).unwrap());
+        assert_eq!(saved.components.len(), 11);
+        let mut writer = ByteWriter::new();
+        saved.write(&mut writer);
+        let bytes = writer.into_bytes();
+        let saved = SavedEntity::read(&mut ByteReader::new(&bytes)).unwrap();
+
+        let mut loaded_world = World::new();
+        let loaded = registry.spawn(&mut loaded_world, &saved).unwrap();
+        let loaded = loaded_world.entity(loaded).unwrap();
+        assert!(loaded.has::<Persistent>());
+        assert!(loaded.has::<CastShadow>());
+        assert_eq!(
+            loaded.get::<&Transform>().unwrap().matrix(),
+            transform().matrix()
+        );
+        assert_eq!(*loaded.get::<&RenderAsset>().unwrap(), render_asset());
+        assert_eq!(
+            *loaded.get::<&FallingBlock>().unwrap(),
+            *world.get::<&FallingBlock>(entity).unwrap()
+        );
+        assert_eq!(
+            *loaded.get::<&ItemDrop>().unwrap(),
+            ItemDrop { block: Block::Sand }
+        );
+        assert_eq!(
+            loaded.get::<&Collider>().unwrap().half_extents,
+            vec3(0.125, 0.125, 0.125)
+        );
+        assert_eq!(*loaded.get::<&RigidBody>().unwrap(), rigid_body());
+        assert_eq!(
+            *loaded.get::<&DirectionalLight>().unwrap(),
+            directional_light()
+        );
+        assert_eq!(*loaded.get::<&PointLight>().unwrap(), point_light());
+        assert_eq!(*loaded.get::<&SpotLight>().unwrap(), spot_light());
+    }
+}
@@ -0,0 +1,136 @@
+use std::io;
+
+use glam::{ivec3, vec3, Vec3};
+
+use crate::state::{clock::WorldClock, voxel::tick::ScheduledTick};
+
+use super::{
+    codec::{invalid_data, ByteReader, ByteWriter},
+    migration::{migrate_metadata, DATA_VERSION},
+};
+
+/// Level files written before metadata carried a data version. They are version 1.
+const LEGACY_METADATA_MAGIC: &[u8; 4] = b"MRLV";
+const METADATA_MAGIC: &[u8; 4] = b"MRL2";
+
+/// World-wide state stored in `level.dat`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldMetadata {
+    pub seed: u32,
+    pub player_pos: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// Physics steps since the world was created.
+    pub tick: u64,
+    pub scheduled_ticks: Vec<ScheduledTick>,
+    pub clock: WorldClock,
+}
+
+impl WorldMetadata {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = ByteWriter::new();
+        payload.put_u32(self.seed);
+        payload.put_f32(self.player_pos.x);
+        payload.put_f32(self.player_pos.y);
+        payload.put_f32(self.player_pos.z);
+        payload.put_f32(self.yaw);
+        payload.put_f32(self.pitch);
+        payload.put_u64(self.tick);
+        payload.put_u32(self.scheduled_ticks.len() as u32);
+        for tick in &self.scheduled_ticks {
+            payload.put_i32(tick.pos.x);
+            payload.put_i32(tick.pos.y);
+            payload.put_i32(tick.pos.z);
+            payload.put_u64(tick.due);
+            payload.put_u8(tick.priority as u8);
+        }
+        payload.put_f32(self.clock.time);
+        payload.put_f32(self.clock.day_length);
+        let payload = payload.into_bytes();
+
+        let mut writer = ByteWriter::new();
+        writer.put_bytes(METADATA_MAGIC);
+        writer.put_u32(DATA_VERSION);
+        writer.put_u32(record_checksum(DATA_VERSION, &payload));
+        writer.put_u32(payload.len() as u32);
+        writer.put_bytes(&payload);
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let (version, payload) = read_record(bytes)?;
+        if version > DATA_VERSION {
+            return Err(invalid_data(format!(
+                "Level was saved with data version {}, newer than {}.",
+                version, DATA_VERSION
+            )));
+        }
+
+        let payload = migrate_metadata(version, payload.to_vec())?;
+
+        let mut reader = ByteReader::new(&payload);
+        let seed = reader.u32()?;
+        let player_pos = vec3(reader.f32()?, reader.f32()?, reader.f32()?);
+        let (yaw, pitch) = (reader.f32()?, reader.f32()?);
+        let tick = reader.u64()?;
+
+        let count = reader.u32()?;
+        let scheduled_ticks = (0..count)
+            .map(|_| {
+                Ok(ScheduledTick {
+                    pos: ivec3(reader.i32()?, reader.i32()?, reader.i32()?),
+                    due: reader.u64()?,
+                    priority: reader.u8()? as i8,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+        let clock = WorldClock::new(reader.f32()?, reader.f32()?);
+
+        Ok(Self {
+            seed,
+            player_pos,
+            yaw,
+            pitch,
+            tick,
+            scheduled_ticks,
+            clock,
+        })
+    }
+
+    /// Reads only the data version of a level file, without migrating it.
+    pub fn version(bytes: &[u8]) -> io::Result<u32> {
+        read_record(bytes).map(|(version, _)| version)
+    }
+}
+
+fn read_record(bytes: &[u8]) -> io::Result<(u32, &[u8])> {
+    let mut reader = ByteReader::new(bytes);
+    let legacy = match reader.bytes(4)? {
+        magic if magic == LEGACY_METADATA_MAGIC => true,
+        magic if magic == METADATA_MAGIC => false,
+        _ => return Err(invalid_data("Not a level file.")),
+    };
+
+    let version = if legacy { 1 } else { reader.u32()? };
+    let crc = reader.u32()?;
+    let len = reader.u32()?;
+    let payload = reader.bytes(len as usize)?;
+
+    let valid = if legacy {
+        crc32fast::hash(payload) == crc
+    } else {
+        record_checksum(version, payload) == crc
+    };
+    if !valid {
+        return Err(invalid_data("Level file checksum mismatch."));
+    }
+
+    Ok((version, payload))
+}
+
+fn record_checksum(version: u32, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&version.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
@@ -0,0 +1,420 @@
+use std::io;
+
+use crate::state::voxel::chunk::CHUNK_VOLUME;
+
+use super::{
+    chunk::PaletteChunk,
+    codec::{invalid_data, ByteReader, ByteWriter},
+};
+
+/// Data version written with every chunk and metadata record.
+///
+/// Changing the save layout means bumping this, appending a migration to both tables below and
+/// adding a fixture world saved with the new version under `tests/fixtures/worlds`.
+///
+/// History:
+/// 1. Unversioned saves, chunks are run-length encoded numeric block ids.
+/// 2. Chunks store blocks by name through a per-chunk palette.
+/// 3. The `wood` block was renamed to `planks`.
+/// 4. Chunks store the persistent entities inside them after the blocks.
+/// 5. Chunks store the levels of flowing fluids after the entities.
+/// 6. Metadata stores the world tick and the pending scheduled block ticks after the rotation.
+/// 7. Metadata stores the time of day and the day length after the scheduled ticks.
+pub const DATA_VERSION: u32 = 7;
+
+/// Upgrades a payload from one data version to the next.
+type Migration = fn(Vec<u8>) -> io::Result<Vec<u8>>;
+
+/// `CHUNK_MIGRATIONS[i]` upgrades a chunk payload from version `i + 1` to `i + 2`.
+const CHUNK_MIGRATIONS: [Migration; DATA_VERSION as usize - 1] = [
+    chunk_ids_to_palette,
+    chunk_rename_wood,
+    chunk_add_entities,
+    chunk_add_fluid_levels,
+    unchanged,
+    unchanged,
+];
+
+/// `METADATA_MIGRATIONS[i]` upgrades a metadata payload from version `i + 1` to `i + 2`.
+const METADATA_MIGRATIONS: [Migration; DATA_VERSION as usize - 1] = [
+    unchanged,
+    unchanged,
+    unchanged,
+    unchanged,
+    metadata_add_ticks,
+    metadata_add_clock,
+];
+
+/// Block names by numeric id as they were in version 1 saves.
+const V1_BLOCK_NAMES: [&str; 7] = ["air", "stone", "dirt", "grass", "sand", "gravel", "wood"];
+
+pub fn migrate_chunk(version: u32, payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    migrate(&CHUNK_MIGRATIONS, version, payload)
+}
+
+pub fn migrate_metadata(version: u32, payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    migrate(&METADATA_MIGRATIONS, version, payload)
+}
+
+/// Runs the migrations from `version` to the end of the chain, the current version.
+fn migrate(migrations: &[Migration], version: u32, mut payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    if version == 0 || version as usize > migrations.len() + 1 {
+        return Err(invalid_data(format!(
+            "Unsupported data version {}.",
+            version
+        )));
+    }
+
+    for migration in &migrations[version as usize - 1..] {
+        payload = migration(payload)?;
+    }
+
+    Ok(payload)
+}
+
+fn unchanged(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    Ok(payload)
+}
+
+/// 1 -> 2: numeric block ids are replaced by a palette of block names.
+fn chunk_ids_to_palette(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut reader = ByteReader::new(&payload);
+    let mut palette = Vec::<String>::new();
+    let mut indices = Vec::with_capacity(CHUNK_VOLUME);
+
+    while !reader.is_at_end() {
+        let run = reader.u16()? as usize;
+        let id = reader.u8()?;
+        let name = *V1_BLOCK_NAMES
+            .get(id as usize)
+            .ok_or_else(|| invalid_data(format!("Unknown block id {}.", id)))?;
+
+        let index = match palette.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                palette.push(name.to_string());
+                palette.len() - 1
+            }
+        };
+
+        if indices.len() + run > CHUNK_VOLUME {
+            return Err(invalid_data("Chunk has too many blocks."));
+        }
+        indices.extend(std::iter::repeat_n(index as u8, run));
+    }
+
+    Ok(PaletteChunk { palette, indices }.to_bytes())
+}
+
+/// 2 -> 3: `wood` was renamed to `planks`.
+fn chunk_rename_wood(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    rename_blocks(payload, &[("wood", "planks")])
+}
+
+/// 3 -> 4: the block section gets a length prefix and is followed by an empty entity list.
+fn chunk_add_entities(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut writer = ByteWriter::new();
+    writer.put_u32(payload.len() as u32);
+    writer.put_bytes(&payload);
+    writer.put_u32(0);
+    Ok(writer.into_bytes())
+}
+
+/// 4 -> 5: an empty list of fluid levels is appended, older saves had no fluids.
+fn chunk_add_fluid_levels(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut writer = ByteWriter::new();
+    writer.put_bytes(&payload);
+    writer.put_u32(0);
+    Ok(writer.into_bytes())
+}
+
+/// 5 -> 6: the world clock starts at zero with nothing scheduled.
+fn metadata_add_ticks(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut writer = ByteWriter::new();
+    writer.put_bytes(&payload);
+    writer.put_u64(0);
+    writer.put_u32(0);
+    Ok(writer.into_bytes())
+}
+
+/// 6 -> 7: older worlds start over shortly after sunrise, with 20 minute days.
+fn metadata_add_clock(payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut writer = ByteWriter::new();
+    writer.put_bytes(&payload);
+    writer.put_f32(0.05);
+    writer.put_f32(1200.);
+    Ok(writer.into_bytes())
+}
+
+fn rename_blocks(payload: Vec<u8>, renames: &[(&str, &str)]) -> io::Result<Vec<u8>> {
+    let mut chunk = PaletteChunk::read(&payload)?;
+    for name in &mut chunk.palette {
+        if let Some((_, new)) = renames.iter().find(|(old, _)| old == name) {
+            *name = new.to_string();
+        }
+    }
+    Ok(chunk.to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    use glam::{ivec3, vec3};
+
+    use crate::state::{
+        clock::WorldClock,
+        ecs::{
+            asset::{MeshAsset, RenderAsset},
+            transform::Transform,
+        },
+        save::{
+            chunk::{decode_chunk, PaletteChunk},
+            codec::ByteWriter,
+            entity::{ComponentRegistry, SavedEntity},
+            region::ChunkEntry,
+            region::RegionFile,
+            WorldSave, REGION_DIR,
+        },
+        voxel::{
+            block::Block,
+            chunk::{Chunk, CHUNK_SIZE, CHUNK_VOLUME},
+            fluid::{Fluid, FluidState},
+            terrain::Terrain,
+            tick::ScheduledTick,
+        },
+    };
+
+    use super::{chunk_ids_to_palette, migrate, Migration, DATA_VERSION};
+
+    /// Rows the made up layout change of [`raise_blocks`] moves blocks up by.
+    const RAISE: usize = 2;
+
+    /// A made up layout change from version 2 to 3 for the test chain: the bottom of the world
+    /// moves down, so every block of a chunk moves up by `RAISE` rows over a floor of stone.
+    /// Blocks are stored a horizontal layer at a time from the bottom, so this prepends layers
+    /// and drops the ones pushed past the top.
+    fn raise_blocks(payload: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        let mut chunk = PaletteChunk::read(&payload)?;
+        let stone = match chunk.palette.iter().position(|name| name == "stone") {
+            Some(index) => index,
+            None => {
+                chunk.palette.push("stone".to_string());
+                chunk.palette.len() - 1
+            }
+        };
+
+        let layer = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        let mut indices = vec![stone as u8; RAISE * layer];
+        indices.extend_from_slice(&chunk.indices[..CHUNK_VOLUME - RAISE * layer]);
+        chunk.indices = indices;
+        Ok(chunk.to_bytes())
+    }
+
+    /// Copies the fixture world saved with `version` to a temporary directory, so loading it
+    /// can't modify the checked in files.
+    fn fixture_world(version: u32, test: &str) -> PathBuf {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/worlds")
+            .join(format!("v{}", version));
+        assert!(
+            fixture.exists(),
+            "Missing fixture world for data version {}.",
+            version
+        );
+
+        let dir = std::env::temp_dir().join(format!(
+            "minerust-{}-v{}-{}",
+            test,
+            version,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(REGION_DIR)).unwrap();
+
+        fs::copy(fixture.join("level.dat"), dir.join("level.dat")).unwrap();
+        for entry in fs::read_dir(fixture.join(REGION_DIR)).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, dir.join(REGION_DIR).join(path.file_name().unwrap())).unwrap();
+        }
+
+        dir
+    }
+
+    fn load(dir: &PathBuf, version: u32) -> (Terrain, Vec<SavedEntity>) {
+        let save = WorldSave::open(dir).unwrap();
+        let metadata = save.load_metadata().expect("Fixture metadata should load.");
+        assert_eq!(metadata.seed, 42);
+        assert_eq!(metadata.player_pos, vec3(1.5, 3., 2.5));
+        assert_eq!((metadata.yaw, metadata.pitch), (45., -10.));
+
+        // Ticks are saved since version 6, the fixtures have a pending tick of the sand block.
+        if version >= 6 {
+            assert_eq!(metadata.tick, 1234);
+            assert_eq!(
+                metadata.scheduled_ticks,
+                [ScheduledTick {
+                    pos: ivec3(4, 1, 0),
+                    due: 1240,
+                    priority: -1,
+                }]
+            );
+        } else {
+            assert_eq!(metadata.tick, 0);
+            assert!(metadata.scheduled_ticks.is_empty());
+        }
+
+        // The clock is saved since version 7, the fixtures are at dusk with 10 minute days.
+        if version >= 7 {
+            assert_eq!(metadata.clock, WorldClock::new(0.45, 600.));
+        } else {
+            assert_eq!(metadata.clock, WorldClock::default());
+        }
+
+        let mut terrain = Terrain::new(metadata.seed);
+        let entities = save.load_area(&mut terrain, ivec3(-1, -1, -1), ivec3(1, 0, 0));
+        (terrain, entities)
+    }
+
+    fn assert_fixture_blocks(terrain: &Terrain) {
+        assert_eq!(terrain.chunks().count(), 3);
+        assert_eq!(terrain.get_block(ivec3(0, 0, 0)), Block::Stone);
+        assert_eq!(terrain.get_block(ivec3(15, 0, 15)), Block::Stone);
+        assert_eq!(terrain.get_block(ivec3(16, 0, 0)), Block::Dirt);
+        assert_eq!(terrain.get_block(ivec3(-1, -1, -1)), Block::Planks);
+        assert_eq!(terrain.get_block(ivec3(0, 2, 0)), Block::Air);
+
+        // The fixtures have every block that existed in version 1 in a row, ordered by id.
+        let row = [
+            Block::Air,
+            Block::Stone,
+            Block::Dirt,
+            Block::Grass,
+            Block::Sand,
+            Block::Gravel,
+            Block::Planks,
+        ];
+        for (x, block) in row.iter().enumerate() {
+            assert_eq!(terrain.get_block(ivec3(x as i32, 1, 0)), *block);
+        }
+    }
+
+    /// Fluids are saved since version 5, the fixtures have a source and flowing water.
+    fn assert_fixture_fluids(terrain: &Terrain, version: u32) {
+        if version >= 5 {
+            assert_eq!(
+                terrain.get_fluid(ivec3(6, 2, 5)),
+                Some(FluidState::source(Fluid::Water))
+            );
+            assert_eq!(
+                terrain.get_fluid(ivec3(5, 2, 5)),
+                Some(FluidState::flowing(Fluid::Water, 3))
+            );
+        }
+    }
+
+    #[test]
+    fn loads_fixture_worlds_from_every_version() {
+        for version in 1..=DATA_VERSION {
+            let dir = fixture_world(version, "load");
+            let (terrain, entities) = load(&dir, version);
+            assert_fixture_blocks(&terrain);
+            assert!(!dir.join("quarantine").exists());
+            assert_fixture_fluids(&terrain, version);
+
+            // Entities are saved since version 4, the fixtures have a single crate.
+            if version >= 4 {
+                assert_eq!(entities.len(), 1);
+
+                let mut world = hecs::World::new();
+                let entity = ComponentRegistry::with_defaults()
+                    .spawn(&mut world, &entities[0])
+                    .unwrap();
+                let transform = world.get::<&Transform>(entity).unwrap();
+                assert_eq!(transform.translation(), vec3(2.5, 2.5, 3.5));
+                let asset = world.get::<&RenderAsset>(entity).unwrap();
+                assert_eq!(
+                    *asset,
+                    RenderAsset::new(MeshAsset::Cube(1.), "assets/wood.png")
+                );
+            } else {
+                assert!(entities.is_empty());
+            }
+
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn resaving_writes_current_version() {
+        for version in 1..=DATA_VERSION {
+            let dir = fixture_world(version, "resave");
+            let (mut terrain, entities) = load(&dir, version);
+            let positions: Vec<_> = terrain.chunks().map(|(p, _)| *p).collect();
+            for pos in positions {
+                terrain.mark_dirty(pos);
+            }
+
+            let save = WorldSave::open(&dir).unwrap();
+            let mut by_chunk = HashMap::new();
+            by_chunk.insert(ivec3(0, 0, 0), entities.clone());
+            save.save_terrain(&mut terrain, &by_chunk).unwrap();
+
+            let bytes = fs::read(save.region_path(ivec3(0, 0, 0))).unwrap();
+            let read = RegionFile::read(ivec3(0, 0, 0), &bytes).unwrap();
+            for pos in read.region.positions() {
+                assert_eq!(read.region.get(pos).unwrap().version, DATA_VERSION);
+            }
+
+            let (terrain, reloaded) = load(&dir, version);
+            assert_fixture_blocks(&terrain);
+            assert_fixture_fluids(&terrain, version);
+            assert_eq!(reloaded, entities);
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_newer_chunks() {
+        let entry = ChunkEntry {
+            version: DATA_VERSION + 1,
+            payload: Vec::new(),
+        };
+        assert!(decode_chunk(&entry).is_err());
+    }
+
+    #[test]
+    fn migrates_layout_changes_through_the_chain() {
+        const CHAIN: [Migration; 2] = [chunk_ids_to_palette, raise_blocks];
+        let layer = (CHUNK_SIZE * CHUNK_SIZE) as u16;
+        let column = |payload: &[u8]| -> Vec<String> {
+            let chunk = PaletteChunk::read(payload).unwrap();
+            (0..6)
+                .map(|y| {
+                    chunk.palette[chunk.indices[Chunk::index(ivec3(5, y, 9))] as usize].clone()
+                })
+                .collect()
+        };
+
+        // A version 1 chunk with a layer of grass over a layer of dirt, as runs of block ids.
+        let mut writer = ByteWriter::new();
+        for (run, id) in [(layer, 2), (layer, 3), (CHUNK_VOLUME as u16 - 2 * layer, 0)] {
+            writer.put_u16(run);
+            writer.put_u8(id);
+        }
+        let migrated = migrate(&CHAIN, 1, writer.into_bytes()).unwrap();
+        assert_eq!(
+            column(&migrated),
+            ["stone", "stone", "dirt", "grass", "air", "air"]
+        );
+
+        // Read back as version 2, it only goes through the layout change again.
+        let migrated = migrate(&CHAIN, 2, migrated).unwrap();
+        assert_eq!(
+            column(&migrated),
+            ["stone", "stone", "stone", "stone", "dirt", "grass"]
+        );
+
+        assert!(migrate(&CHAIN, 4, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use glam::{ivec3, IVec3};
+
+use self::{
+    atomic::{remove_stale_temp_files, write_atomic},
+    chunk::{decode_chunk, encode_chunk},
+    codec::invalid_data,
+    entity::SavedEntity,
+    metadata::WorldMetadata,
+    migration::DATA_VERSION,
+    region::{region_file_name, region_pos, RegionFile},
+};
+
+use super::voxel::terrain::Terrain;
+
+pub mod atomic;
+pub mod backup;
+pub mod chunk;
+pub mod codec;
+pub mod entity;
+pub mod metadata;
+pub mod migration;
+pub mod region;
+
+pub const LEVEL_FILE: &str = "level.dat";
+pub const REGION_DIR: &str = "region";
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// A world directory on disk: `level.dat` plus one file per region of chunks.
+///
+/// Every write goes through [`write_atomic`], and every chunk carries a checksum. Damaged data
+/// found while loading is moved to the `quarantine` directory with a warning, and the affected
+/// chunks are left unloaded so the terrain generator recreates them.
+///
+/// Records from older data versions are migrated when they are read, see [`migration`].
+pub struct WorldSave {
+    dir: PathBuf,
+}
+
+impl WorldSave {
+    /// Fails if the world was saved by a newer version of the game, since saving to it would
+    /// throw away data this version doesn't understand.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let save = Self { dir: dir.into() };
+
+        if let Ok(bytes) = fs::read(save.dir.join(LEVEL_FILE)) {
+            if let Ok(version) = WorldMetadata::version(&bytes) {
+                if version > DATA_VERSION {
+                    return Err(invalid_data(format!(
+                        "World was saved with data version {}, this version supports up to {}.",
+                        version, DATA_VERSION
+                    )));
+                }
+            }
+        }
+
+        fs::create_dir_all(save.region_dir())?;
+        remove_stale_temp_files(&save.dir)?;
+        remove_stale_temp_files(&save.region_dir())?;
+
+        Ok(save)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn region_dir(&self) -> PathBuf {
+        self.dir.join(REGION_DIR)
+    }
+
+    pub fn region_path(&self, region_pos: IVec3) -> PathBuf {
+        self.region_dir().join(region_file_name(region_pos))
+    }
+
+    /// Returns `None` for a new world, or if the level file is damaged.
+    pub fn load_metadata(&self) -> Option<WorldMetadata> {
+        let path = self.dir.join(LEVEL_FILE);
+        let bytes = fs::read(&path).ok()?;
+
+        match WorldMetadata::from_bytes(&bytes) {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                eprintln!(
+                    "Warning: {} is damaged ({}), using defaults.",
+                    path.display(),
+                    e
+                );
+                self.quarantine_file(&path);
+                None
+            }
+        }
+    }
+
+    pub fn save_metadata(&self, metadata: &WorldMetadata) -> io::Result<()> {
+        write_atomic(&self.dir.join(LEVEL_FILE), &metadata.to_bytes())
+    }
+
+    /// Loads every saved chunk in the inclusive chunk range `min..=max` that isn't loaded yet,
+    /// and returns the entities saved in them.
+    pub fn load_area(&self, terrain: &mut Terrain, min: IVec3, max: IVec3) -> Vec<SavedEntity> {
+        let mut entities = Vec::new();
+
+        let (region_min, region_max) = (region_pos(min), region_pos(max));
+
+        for y in region_min.y..=region_max.y {
+            for z in region_min.z..=region_max.z {
+                for x in region_min.x..=region_max.x {
+                    let region = match self.read_region(ivec3(x, y, z), &[]) {
+                        Some(region) => region,
+                        None => continue,
+                    };
+
+                    for pos in region.positions() {
+                        if pos.cmplt(min).any() || pos.cmpgt(max).any() || terrain.is_loaded(pos) {
+                            continue;
+                        }
+
+                        let entry = region.get(pos).unwrap();
+                        match decode_chunk(entry) {
+                            Ok((chunk, chunk_entities)) => {
+                                terrain.insert_chunk(pos, chunk);
+                                entities.extend(chunk_entities);
+                            }
+                            Err(e) => {
+                                eprintln!(
+                                    "Warning: chunk ({}, {}, {}) couldn't be decoded ({}), regenerating it.",
+                                    pos.x, pos.y, pos.z, e
+                                );
+                                self.quarantine_chunk(pos, &entry.payload);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        entities
+    }
+
+    /// Writes every region that contains a modified chunk, storing `entities` with the chunk
+    /// they are in.
+    ///
+    /// Only modified chunks are written, so chunks whose entities changed have to be marked as
+    /// modified, including chunks that entities left. Chunks of those regions that aren't loaded
+    /// are carried over from the existing file. Entities in chunks that aren't loaded are stored
+    /// with the closest loaded chunk instead, and load from there. If a write fails, the chunks
+    /// are marked as modified again so the next save retries them.
+    pub fn save_terrain(
+        &self,
+        terrain: &mut Terrain,
+        entities: &HashMap<IVec3, Vec<SavedEntity>>,
+    ) -> io::Result<()> {
+        let entities = in_loaded_chunks(terrain, entities);
+
+        let mut regions = HashMap::<IVec3, Vec<IVec3>>::new();
+        for pos in terrain.take_dirty() {
+            regions.entry(region_pos(pos)).or_default().push(pos);
+        }
+
+        let mut result = Ok(());
+        for (region_pos, chunks) in regions {
+            let mut region = self.read_region(region_pos, &chunks).unwrap_or_default();
+            for pos in &chunks {
+                if let Some(chunk) = terrain.chunk(*pos) {
+                    let chunk_entities = entities.get(pos).map_or(&[][..], Vec::as_slice);
+                    region.insert(*pos, encode_chunk(chunk, chunk_entities));
+                }
+            }
+
+            if let Err(e) = write_atomic(&self.region_path(region_pos), &region.to_bytes()) {
+                for pos in chunks {
+                    terrain.mark_dirty(pos);
+                }
+                result = Err(e);
+            }
+        }
+
+        result
+    }
+
+    /// Reads a region file, quarantining damaged chunks unless they are listed in `replaced`.
+    fn read_region(&self, region_pos: IVec3, replaced: &[IVec3]) -> Option<RegionFile> {
+        let path = self.region_path(region_pos);
+        let bytes = fs::read(&path).ok()?;
+
+        match RegionFile::read(region_pos, &bytes) {
+            Ok(read) => {
+                if read.truncated {
+                    eprintln!(
+                        "Warning: region file {} is truncated, regenerating its missing chunks.",
+                        path.display()
+                    );
+                }
+                for chunk in read.corrupt {
+                    let pos = chunk.pos;
+                    if replaced.contains(&pos) {
+                        continue;
+                    }
+                    eprintln!(
+                        "Warning: chunk ({}, {}, {}) is damaged, regenerating it.",
+                        pos.x, pos.y, pos.z
+                    );
+                    self.quarantine_chunk(pos, &chunk.payload);
+                }
+                Some(read.region)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: region file {} is damaged ({}), regenerating its chunks.",
+                    path.display(),
+                    e
+                );
+                self.quarantine_file(&path);
+                None
+            }
+        }
+    }
+
+    fn quarantine_dir(&self) -> PathBuf {
+        let dir = self.dir.join(QUARANTINE_DIR);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Warning: couldn't create {}: {}", dir.display(), e);
+        }
+        dir
+    }
+
+    fn quarantine_file(&self, path: &Path) {
+        let name = format!(
+            "{}.{}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            timestamp()
+        );
+        let target = self.quarantine_dir().join(name);
+        if let Err(e) = fs::rename(path, &target) {
+            eprintln!("Warning: couldn't quarantine {}: {}", path.display(), e);
+        }
+    }
+
+    fn quarantine_chunk(&self, pos: IVec3, payload: &[u8]) {
+        let name = format!("c.{}.{}.{}.{}.bin", pos.x, pos.y, pos.z, timestamp());
+        let target = self.quarantine_dir().join(name);
+        if let Err(e) = fs::write(&target, payload) {
+            eprintln!("Warning: couldn't quarantine {}: {}", target.display(), e);
+        }
+    }
+}
+
+/// Moves the entities of chunks that aren't loaded to the closest loaded chunk and marks it as
+/// modified. Only loaded chunks are written, so they would be lost otherwise.
+fn in_loaded_chunks(
+    terrain: &mut Terrain,
+    entities: &HashMap<IVec3, Vec<SavedEntity>>,
+) -> HashMap<IVec3, Vec<SavedEntity>> {
+    let mut moved = HashMap::<IVec3, Vec<SavedEntity>>::new();
+
+    for (pos, chunk_entities) in entities {
+        let target = if terrain.is_loaded(*pos) {
+            *pos
+        } else {
+            let closest = terrain
+                .chunks()
+                .map(|(chunk, _)| *chunk)
+                .min_by_key(|chunk| {
+                    let offset = *chunk - *pos;
+                    (offset.dot(offset), chunk.x, chunk.y, chunk.z)
+                });
+            match closest {
+                Some(closest) => closest,
+                None => continue,
+            }
+        };
+
+        if target != *pos {
+            terrain.mark_dirty(target);
+        }
+        moved
+            .entry(target)
+            .or_default()
+            .extend(chunk_entities.iter().cloned());
+    }
+
+    moved
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::PathBuf};
+
+    use glam::{ivec3, IVec3};
+
+    use crate::state::voxel::{block::Block, terrain::Terrain};
+
+    use super::{entity::SavedEntity, WorldSave, LEVEL_FILE, QUARANTINE_DIR};
+
+    const SEED: u32 = 7;
+
+    /// A world with a stone block saved in chunk (0, 0, 0) and in chunk (1, 0, 0), the last
+    /// entry of its region file.
+    fn saved_world(test: &str) -> WorldSave {
+        let dir =
+            std::env::temp_dir().join(format!("minerust-save-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let save = WorldSave::open(dir).unwrap();
+
+        let mut terrain = Terrain::new(SEED);
+        terrain.set_block(ivec3(1, 2, 3), Block::Stone);
+        terrain.set_block(ivec3(17, 2, 3), Block::Stone);
+        save.save_terrain(&mut terrain, &HashMap::new()).unwrap();
+        save
+    }
+
+    fn quarantined(save: &WorldSave) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(save.dir().join(QUARANTINE_DIR))
+            .map(|entries| {
+                entries
+                    .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    /// Loads chunks (0, 0, 0) and (1, 0, 0) and generates whichever couldn't be loaded.
+    fn load(save: &WorldSave) -> Terrain {
+        let mut terrain = Terrain::new(SEED);
+        save.load_area(&mut terrain, IVec3::ZERO, ivec3(1, 0, 0));
+        terrain.generate_area(IVec3::ZERO, ivec3(1, 0, 0));
+        terrain
+    }
+
+    fn is_generated(terrain: &Terrain, pos: IVec3) -> bool {
+        terrain.chunk(pos).unwrap().blocks() == terrain.generator().generate(pos).blocks()
+    }
+
+    fn region_path(save: &WorldSave) -> PathBuf {
+        save.region_path(IVec3::ZERO)
+    }
+
+    #[test]
+    fn chunk_failing_its_checksum_is_quarantined_and_regenerated() {
+        let save = saved_world("checksum");
+        let mut bytes = fs::read(region_path(&save)).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xff;
+        fs::write(region_path(&save), bytes).unwrap();
+
+        let terrain = load(&save);
+        assert_eq!(terrain.get_block(ivec3(1, 2, 3)), Block::Stone);
+        assert!(is_generated(&terrain, ivec3(1, 0, 0)));
+
+        let quarantined = quarantined(&save);
+        assert_eq!(quarantined.len(), 1);
+        assert!(quarantined[0].starts_with("c.1.0.0."), "{:?}", quarantined);
+    }
+
+    #[test]
+    fn truncated_region_keeps_the_chunks_before_the_cut() {
+        let save = saved_world("truncated");
+        let bytes = fs::read(region_path(&save)).unwrap();
+        fs::write(region_path(&save), &bytes[..bytes.len() - 10]).unwrap();
+
+        let terrain = load(&save);
+        assert_eq!(terrain.get_block(ivec3(1, 2, 3)), Block::Stone);
+        assert!(is_generated(&terrain, ivec3(1, 0, 0)));
+    }
+
+    #[test]
+    fn unreadable_region_is_quarantined_and_regenerated() {
+        let save = saved_world("unreadable");
+        fs::write(region_path(&save), b"garbage").unwrap();
+
+        let terrain = load(&save);
+        assert!(is_generated(&terrain, IVec3::ZERO));
+        assert!(is_generated(&terrain, ivec3(1, 0, 0)));
+
+        assert!(!region_path(&save).exists());
+        let quarantined = quarantined(&save);
+        assert_eq!(quarantined.len(), 1);
+        assert!(
+            quarantined[0].starts_with("r.0.0.0.dat."),
+            "{:?}",
+            quarantined
+        );
+    }
+
+    #[test]
+    fn damaged_level_file_is_quarantined() {
+        let save = saved_world("level");
+        let path = save.dir().join(LEVEL_FILE);
+        fs::write(&path, b"MRL2 not really a level file").unwrap();
+
+        assert!(save.load_metadata().is_none());
+        assert!(!path.exists());
+        let quarantined = quarantined(&save);
+        assert_eq!(quarantined.len(), 1);
+        assert!(
+            quarantined[0].starts_with("level.dat."),
+            "{:?}",
+            quarantined
+        );
+    }
+
+    #[test]
+    fn entities_outside_loaded_chunks_are_kept() {
+        let save = saved_world("unloaded-entities");
+        let mut terrain = load(&save);
+        let entity = SavedEntity {
+            components: vec![("persistent".to_string(), Vec::new())],
+        };
+        let mut entities = HashMap::new();
+        entities.insert(ivec3(4, 0, 0), vec![entity.clone()]);
+
+        // Saving twice shouldn't store the entity twice.
+        for _ in 0..2 {
+            terrain.mark_dirty(ivec3(4, 0, 0));
+            save.save_terrain(&mut terrain, &entities).unwrap();
+
+            let mut reloaded = Terrain::new(SEED);
+            let loaded = save.load_area(&mut reloaded, IVec3::ZERO, ivec3(4, 0, 0));
+            assert_eq!(loaded, vec![entity.clone()]);
+            assert!(!reloaded.is_loaded(ivec3(4, 0, 0)));
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::{collections::HashMap, io};
+
+use glam::{ivec3, IVec3};
+
+use super::{
+    codec::{invalid_data, ByteReader, ByteWriter},
+    migration::DATA_VERSION,
+};
+
+/// Number of chunks stored in a region file along each axis.
+pub const REGION_SIZE: i32 = 8;
+
+/// Region files written before chunks carried a data version. All their chunks are version 1.
+const LEGACY_REGION_MAGIC: &[u8; 4] = b"MRRG";
+/// Region files whose checksums only cover each entry's version and payload.
+const UNPOSITIONED_REGION_MAGIC: &[u8; 4] = b"MRR2";
+const REGION_MAGIC: &[u8; 4] = b"MRR3";
+
+/// How the entries of a region file are laid out and checked, from its magic.
+#[derive(Clone, Copy)]
+enum RegionFormat {
+    Legacy,
+    Unpositioned,
+    Current,
+}
+
+pub fn region_pos(chunk_pos: IVec3) -> IVec3 {
+    ivec3(
+        chunk_pos.x.div_euclid(REGION_SIZE),
+        chunk_pos.y.div_euclid(REGION_SIZE),
+        chunk_pos.z.div_euclid(REGION_SIZE),
+    )
+}
+
+pub fn region_file_name(region_pos: IVec3) -> String {
+    format!("r.{}.{}.{}.dat", region_pos.x, region_pos.y, region_pos.z)
+}
+
+/// An encoded chunk together with the data version it was written with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChunkEntry {
+    pub version: u32,
+    pub payload: Vec<u8>,
+}
+
+impl ChunkEntry {
+    pub fn current(payload: Vec<u8>) -> Self {
+        Self {
+            version: DATA_VERSION,
+            payload,
+        }
+    }
+}
+
+/// A chunk entry whose checksum didn't match its contents, or whose position is outside the
+/// region or already taken by an earlier entry.
+pub struct CorruptChunk {
+    pub pos: IVec3,
+    pub payload: Vec<u8>,
+}
+
+pub struct RegionRead {
+    pub region: RegionFile,
+    pub corrupt: Vec<CorruptChunk>,
+    /// The file ended in the middle of an entry, so any chunks after it are missing.
+    pub truncated: bool,
+}
+
+/// The chunks stored in one region file, kept as encoded payloads.
+///
+/// Each entry is stored as `x, y, z, version, crc32, length, payload`, where the checksum covers
+/// everything else in the entry, so a chunk can't load at a damaged position. A damaged entry can
+/// be detected and skipped without losing the rest of the region. Entries keep their version
+/// when the region is rewritten, so chunks that were never loaded are migrated whenever they are
+/// eventually read.
+#[derive(Default)]
+pub struct RegionFile {
+    chunks: HashMap<IVec3, ChunkEntry>,
+}
+
+impl RegionFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the file of the region at `region_pos`, setting aside entries that fail their
+    /// checksum or claim a position outside the region or one that was already read.
+    ///
+    /// Only fails if the file header itself is unreadable. A truncated file keeps every entry
+    /// before the point of truncation.
+    pub fn read(region_pos: IVec3, bytes: &[u8]) -> io::Result<RegionRead> {
+        let mut reader = ByteReader::new(bytes);
+        let format = match reader.bytes(4)? {
+            magic if magic == LEGACY_REGION_MAGIC => RegionFormat::Legacy,
+            magic if magic == UNPOSITIONED_REGION_MAGIC => RegionFormat::Unpositioned,
+            magic if magic == REGION_MAGIC => RegionFormat::Current,
+            _ => return Err(invalid_data("Not a region file.")),
+        };
+        let count = reader.u32()?;
+
+        let mut region = Self::new();
+        let mut corrupt = Vec::new();
+        let mut truncated = false;
+
+        for _ in 0..count {
+            let entry = (|| {
+                let pos = ivec3(reader.i32()?, reader.i32()?, reader.i32()?);
+                let version = match format {
+                    RegionFormat::Legacy => 1,
+                    _ => reader.u32()?,
+                };
+                let crc = reader.u32()?;
+                let len = reader.u32()?;
+                let payload = reader.bytes(len as usize)?;
+                Ok::<_, io::Error>((pos, version, crc, payload))
+            })();
+
+            let (pos, version, crc, payload) = match entry {
+                Ok(entry) => entry,
+                Err(_) => {
+                    truncated = true;
+                    break;
+                }
+            };
+
+            let checksum = match format {
+                RegionFormat::Legacy => crc32fast::hash(payload),
+                RegionFormat::Unpositioned => unpositioned_checksum(version, payload),
+                RegionFormat::Current => entry_checksum(pos, version, payload),
+            };
+            let valid = checksum == crc
+                && self::region_pos(pos) == region_pos
+                && !region.chunks.contains_key(&pos);
+
+            if valid {
+                let entry = ChunkEntry {
+                    version,
+                    payload: payload.to_vec(),
+                };
+                region.chunks.insert(pos, entry);
+            } else {
+                corrupt.push(CorruptChunk {
+                    pos,
+                    payload: payload.to_vec(),
+                });
+            }
+        }
+
+        Ok(RegionRead {
+            region,
+            corrupt,
+            truncated,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Sorted so that saving the same chunks twice produces identical files.
+        let mut positions: Vec<&IVec3> = self.chunks.keys().collect();
+        positions.sort_by_key(|p| (p.x, p.y, p.z));
+
+        let mut writer = ByteWriter::new();
+        writer.put_bytes(REGION_MAGIC);
+        writer.put_u32(positions.len() as u32);
+
+        for pos in positions {
+            let entry = &self.chunks[pos];
+            writer.put_i32(pos.x);
+            writer.put_i32(pos.y);
+            writer.put_i32(pos.z);
+            writer.put_u32(entry.version);
+            writer.put_u32(entry_checksum(*pos, entry.version, &entry.payload));
+            writer.put_u32(entry.payload.len() as u32);
+            writer.put_bytes(&entry.payload);
+        }
+
+        writer.into_bytes()
+    }
+
+    pub fn get(&self, pos: IVec3) -> Option<&ChunkEntry> {
+        self.chunks.get(&pos)
+    }
+
+    pub fn insert(&mut self, pos: IVec3, entry: ChunkEntry) {
+        self.chunks.insert(pos, entry);
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+}
+
+/// Checksum of an entry's position, version, payload length and payload.
+fn entry_checksum(pos: IVec3, version: u32, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for value in pos.to_array() {
+        hasher.update(&value.to_le_bytes());
+    }
+    hasher.update(&version.to_le_bytes());
+    hasher.update(&(payload.len() as u32).to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn unpositioned_checksum(version: u32, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&version.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, IVec3};
+
+    use super::{ChunkEntry, RegionFile};
+
+    fn region() -> RegionFile {
+        let mut region = RegionFile::new();
+        region.insert(ivec3(0, 0, 0), ChunkEntry::current(vec![1; 16]));
+        region.insert(ivec3(1, 0, 0), ChunkEntry::current(vec![2; 16]));
+        region.insert(ivec3(2, 0, 0), ChunkEntry::current(vec![3; 16]));
+        region
+    }
+
+    /// Offset of the `index`th entry, entries are sorted by position.
+    fn entry_offset(index: usize) -> usize {
+        let header = 8;
+        let entry_header = 6 * 4;
+        header + index * (entry_header + 16)
+    }
+
+    /// Offset of the first payload byte of the `index`th entry.
+    fn payload_offset(index: usize) -> usize {
+        entry_offset(index) + 6 * 4
+    }
+
+    #[test]
+    fn corrupt_entries_are_set_aside() {
+        let mut bytes = region().to_bytes();
+        bytes[payload_offset(1) + 3] ^= 0xff;
+
+        let read = RegionFile::read(IVec3::ZERO, &bytes).unwrap();
+        assert!(!read.truncated);
+        assert_eq!(read.corrupt.len(), 1);
+        assert_eq!(read.corrupt[0].pos, ivec3(1, 0, 0));
+        assert_eq!(read.region.get(ivec3(0, 0, 0)).unwrap().payload, [1; 16]);
+        assert!(read.region.get(ivec3(1, 0, 0)).is_none());
+        assert_eq!(read.region.get(ivec3(2, 0, 0)).unwrap().payload, [3; 16]);
+    }
+
+    #[test]
+    fn truncated_file_keeps_the_entries_before_the_cut() {
+        let bytes = region().to_bytes();
+        let read = RegionFile::read(IVec3::ZERO, &bytes[..payload_offset(1) + 5]).unwrap();
+
+        assert!(read.truncated);
+        assert!(read.corrupt.is_empty());
+        assert_eq!(read.region.positions().count(), 1);
+        assert!(read.region.get(ivec3(0, 0, 0)).is_some());
+    }
+
+    #[test]
+    fn unknown_header_fails() {
+        assert!(RegionFile::read(IVec3::ZERO, b"nope\0\0\0\0").is_err());
+    }
+
+    #[test]
+    fn entries_with_a_damaged_position_are_set_aside() {
+        let mut bytes = region().to_bytes();
+        // The low byte of the second entry's x, moving it onto the third.
+        bytes[entry_offset(1)] ^= 0x03;
+
+        let read = RegionFile::read(IVec3::ZERO, &bytes).unwrap();
+        assert_eq!(read.corrupt.len(), 1);
+        assert_eq!(read.corrupt[0].pos, ivec3(2, 0, 0));
+        assert!(read.region.get(ivec3(1, 0, 0)).is_none());
+        assert_eq!(read.region.get(ivec3(2, 0, 0)).unwrap().payload, [3; 16]);
+    }
+
+    #[test]
+    fn entries_outside_the_region_are_set_aside() {
+        let mut region = RegionFile::new();
+        region.insert(ivec3(0, 0, 0), ChunkEntry::current(vec![1; 16]));
+        region.insert(ivec3(8, 0, 0), ChunkEntry::current(vec![2; 16]));
+
+        let read = RegionFile::read(IVec3::ZERO, &region.to_bytes()).unwrap();
+        assert_eq!(read.corrupt.len(), 1);
+        assert_eq!(read.corrupt[0].pos, ivec3(8, 0, 0));
+        assert_eq!(read.region.positions().count(), 1);
+    }
+
+    #[test]
+    fn repeated_entries_are_set_aside() {
+        let mut bytes = region().to_bytes();
+        let first = bytes[entry_offset(0)..entry_offset(1)].to_vec();
+        bytes.truncate(entry_offset(2));
+        bytes.extend(first);
+
+        let read = RegionFile::read(IVec3::ZERO, &bytes).unwrap();
+        assert_eq!(read.corrupt.len(), 1);
+        assert_eq!(read.corrupt[0].pos, ivec3(0, 0, 0));
+        assert_eq!(read.region.get(ivec3(0, 0, 0)).unwrap().payload, [1; 16]);
+        assert_eq!(read.region.positions().count(), 2);
+    }
+}
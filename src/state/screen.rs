@@ -27,9 +27,15 @@ impl Screen {
         }
     }
 
-    pub fn draw(&mut self, renderer: &mut Renderer) {
+    pub fn draw(&mut self, renderer: &mut Renderer, alpha: f32) {
         if let Some(w) = &mut self.world {
-            w.draw(renderer);
+            w.draw(renderer, alpha);
+        }
+    }
+
+    pub fn save(&mut self) {
+        if let Some(w) = &mut self.world {
+            w.save();
         }
     }
 
@@ -0,0 +1,203 @@
+use super::light::MAX_LIGHT;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Block {
+    #[default]
+    Air = 0,
+    Stone = 1,
+    Dirt = 2,
+    Grass = 3,
+    Sand = 4,
+    Gravel = 5,
+    Planks = 6,
+    Water = 7,
+    Lava = 8,
+    Ladder = 9,
+    Vine = 10,
+    Glowstone = 11,
+    RedstoneLamp = 12,
+    RedGlass = 13,
+    GreenGlass = 14,
+    BlueGlass = 15,
+}
+
+impl Block {
+    pub const ALL: [Block; 16] = [
+        Block::Air,
+        Block::Stone,
+        Block::Dirt,
+        Block::Grass,
+        Block::Sand,
+        Block::Gravel,
+        Block::Planks,
+        Block::Water,
+        Block::Lava,
+        Block::Ladder,
+        Block::Vine,
+        Block::Glowstone,
+        Block::RedstoneLamp,
+        Block::RedGlass,
+        Block::GreenGlass,
+        Block::BlueGlass,
+    ];
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|b| b.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Block::Air => "air",
+            Block::Stone => "stone",
+            Block::Dirt => "dirt",
+            Block::Grass => "grass",
+            Block::Sand => "sand",
+            Block::Gravel => "gravel",
+            Block::Planks => "planks",
+            Block::Water => "water",
+            Block::Lava => "lava",
+            Block::Ladder => "ladder",
+            Block::Vine => "vine",
+            Block::Glowstone => "glowstone",
+            Block::RedstoneLamp => "redstone_lamp",
+            Block::RedGlass => "red_glass",
+            Block::GreenGlass => "green_glass",
+            Block::BlueGlass => "blue_glass",
+        }
+    }
+
+    pub fn is_air(self) -> bool {
+        self == Block::Air
+    }
+
+    pub fn is_fluid(self) -> bool {
+        matches!(self, Block::Water | Block::Lava)
+    }
+
+    /// Whether the block can be climbed by standing inside it.
+    pub fn is_climbable(self) -> bool {
+        matches!(self, Block::Ladder | Block::Vine)
+    }
+
+    /// Whether light and sight pass through the block although entities collide with it.
+    pub fn is_translucent(self) -> bool {
+        matches!(self, Block::RedGlass | Block::GreenGlass | Block::BlueGlass)
+    }
+
+    /// Whether placed and landing blocks overwrite the block, so it can't be targeted either.
+    pub fn is_replaceable(self) -> bool {
+        self.is_air() || self.is_fluid()
+    }
+
+    /// Whether the block fully hides the faces of its neighbours.
+    pub fn is_opaque(self) -> bool {
+        !self.is_replaceable() && !self.is_climbable() && !self.is_translucent()
+    }
+
+    /// Whether entities collide with the block.
+    pub fn is_solid(self) -> bool {
+        !self.is_replaceable() && !self.is_climbable()
+    }
+
+    /// Whether entities collide with the whole block, so things can rest on top of it.
+    pub fn is_full_cube(self) -> bool {
+        self.is_solid()
+    }
+
+    /// Red, green and blue light levels the block emits, from 0 to `MAX_LIGHT`. Each colour
+    /// spreads on its own, so dimmer colours fade out closer to the block.
+    pub fn light_emission(self) -> [u8; 3] {
+        match self {
+            Block::Lava => [MAX_LIGHT, 9, 3],
+            Block::Glowstone => [MAX_LIGHT, 13, 9],
+            Block::RedstoneLamp => [MAX_LIGHT, 3, 2],
+            _ => [0; 3],
+        }
+    }
+
+    pub fn emits_light(self) -> bool {
+        self.light_emission() != [0; 3]
+    }
+
+    /// Whether the block's light is shaded as a point light in the lighting pass instead of
+    /// spreading through the voxel block light.
+    pub fn is_point_light(self) -> bool {
+        matches!(self, Block::Glowstone | Block::RedstoneLamp)
+    }
+
+    /// How many levels light loses passing through the block, on top of the one it loses per
+    /// block. Opaque blocks stop light entirely.
+    pub fn light_opacity(self) -> u8 {
+        if self.is_opaque() {
+            MAX_LIGHT
+        } else if self.is_fluid() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// How many more levels red, green and blue block light lose passing through the block, on
+    /// top of its opacity. Stained glass lets its own colour through.
+    pub fn light_filter(self) -> [u8; 3] {
+        match self {
+            Block::RedGlass => [0, 6, 6],
+            Block::GreenGlass => [6, 0, 6],
+            Block::BlueGlass => [6, 6, 0],
+            _ => [0; 3],
+        }
+    }
+
+    /// Whether the block falls when there's nothing under it.
+    pub fn has_gravity(self) -> bool {
+        matches!(self, Block::Sand | Block::Gravel)
+    }
+
+    /// How hard the block is to mine, mining time grows linearly with it.
+    pub fn hardness(self) -> f32 {
+        match self {
+            Block::Air => 0.,
+            Block::Stone => 1.5,
+            Block::Dirt => 0.5,
+            Block::Grass => 0.6,
+            Block::Sand => 0.5,
+            Block::Gravel => 0.6,
+            Block::Planks => 2.,
+            Block::Water | Block::Lava => 0.,
+            Block::Ladder => 0.4,
+            Block::Vine => 0.2,
+            Block::Glowstone | Block::RedstoneLamp => 0.3,
+            Block::RedGlass | Block::GreenGlass | Block::BlueGlass => 0.3,
+        }
+    }
+
+    pub fn texture_path(self) -> Option<&'static str> {
+        match self {
+            Block::Air => None,
+            Block::Stone => Some("assets/blocks/stone.png"),
+            Block::Dirt => Some("assets/blocks/dirt.png"),
+            Block::Grass => Some("assets/blocks/grass.png"),
+            Block::Sand => Some("assets/blocks/sand.png"),
+            Block::Gravel => Some("assets/blocks/gravel.png"),
+            Block::Planks => Some("assets/wood.png"),
+            Block::Water => Some("assets/blocks/water.png"),
+            Block::Lava => Some("assets/blocks/lava.png"),
+            Block::Ladder => Some("assets/blocks/ladder.png"),
+            Block::Vine => Some("assets/blocks/vine.png"),
+            Block::Glowstone => Some("assets/blocks/glowstone.png"),
+            Block::RedstoneLamp => Some("assets/blocks/redstone_lamp.png"),
+            Block::RedGlass => Some("assets/blocks/red_glass.png"),
+            Block::GreenGlass => Some("assets/blocks/green_glass.png"),
+            Block::BlueGlass => Some("assets/blocks/blue_glass.png"),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+
+use glam::{ivec3, IVec3};
+
+use super::{block::Block, light::LightChannel};
+
+pub const CHUNK_SIZE: i32 = 16;
+pub const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Clone)]
+pub struct Chunk {
+    blocks: Box<[Block]>,
+    /// Fluid state byte of fluid blocks that aren't plain sources, by block index.
+    fluid_levels: BTreeMap<u16, u8>,
+    /// Sky, red, green and blue light in the nibbles of each block, from the highest. It isn't
+    /// saved.
+    light: Box<[u16]>,
+    /// Number of blocks that aren't air.
+    solid: usize,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![Block::Air; CHUNK_VOLUME].into_boxed_slice(),
+            fluid_levels: BTreeMap::new(),
+            light: vec![0; CHUNK_VOLUME].into_boxed_slice(),
+            solid: 0,
+        }
+    }
+
+    pub fn from_blocks(blocks: Vec<Block>) -> Self {
+        assert_eq!(
+            blocks.len(),
+            CHUNK_VOLUME,
+            "Chunk must have exactly CHUNK_VOLUME blocks."
+        );
+        let solid = blocks.iter().filter(|b| !b.is_air()).count();
+        Self {
+            blocks: blocks.into_boxed_slice(),
+            fluid_levels: BTreeMap::new(),
+            light: vec![0; CHUNK_VOLUME].into_boxed_slice(),
+            solid,
+        }
+    }
+
+    pub fn get(&self, local: IVec3) -> Block {
+        self.blocks[Self::index(local)]
+    }
+
+    /// Sets the block, any fluid level it had is reset.
+    pub fn set(&mut self, local: IVec3, block: Block) {
+        let index = Self::index(local);
+        match (self.blocks[index].is_air(), block.is_air()) {
+            (true, false) => self.solid += 1,
+            (false, true) => self.solid -= 1,
+            _ => {}
+        }
+        self.blocks[index] = block;
+        self.fluid_levels.remove(&(index as u16));
+    }
+
+    /// The fluid state byte of the block, zero for sources and non-fluid blocks.
+    pub fn fluid_level(&self, local: IVec3) -> u8 {
+        self.fluid_levels
+            .get(&(Self::index(local) as u16))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn set_fluid_level(&mut self, local: IVec3, level: u8) {
+        let index = Self::index(local) as u16;
+        if level == 0 {
+            self.fluid_levels.remove(&index);
+        } else {
+            self.fluid_levels.insert(index, level);
+        }
+    }
+
+    /// Non-zero fluid state bytes by block index.
+    pub fn fluid_levels(&self) -> &BTreeMap<u16, u8> {
+        &self.fluid_levels
+    }
+
+    pub fn light(&self, local: IVec3, channel: LightChannel) -> u8 {
+        let light = self.light[Self::index(local)];
+        ((light >> light_shift(channel)) & 0xf) as u8
+    }
+
+    pub fn set_light(&mut self, local: IVec3, channel: LightChannel, level: u8) {
+        let shift = light_shift(channel);
+        let light = &mut self.light[Self::index(local)];
+        *light = (*light & !(0xf << shift)) | ((level as u16 & 0xf) << shift);
+    }
+
+    pub fn clear_light(&mut self) {
+        self.light.fill(0);
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solid == 0
+    }
+
+    /// Blocks are stored x-major, then z, then y, so a horizontal layer is contiguous.
+    pub fn index(local: IVec3) -> usize {
+        debug_assert!(
+            local.cmpge(IVec3::ZERO).all() && local.cmplt(IVec3::splat(CHUNK_SIZE)).all()
+        );
+        (local.x + local.z * CHUNK_SIZE + local.y * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    pub fn local_from_index(index: usize) -> IVec3 {
+        let i = index as i32;
+        ivec3(
+            i % CHUNK_SIZE,
+            i / (CHUNK_SIZE * CHUNK_SIZE),
+            (i / CHUNK_SIZE) % CHUNK_SIZE,
+        )
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn chunk_pos(block_pos: IVec3) -> IVec3 {
+    ivec3(
+        block_pos.x.div_euclid(CHUNK_SIZE),
+        block_pos.y.div_euclid(CHUNK_SIZE),
+        block_pos.z.div_euclid(CHUNK_SIZE),
+    )
+}
+
+pub fn local_pos(block_pos: IVec3) -> IVec3 {
+    ivec3(
+        block_pos.x.rem_euclid(CHUNK_SIZE),
+        block_pos.y.rem_euclid(CHUNK_SIZE),
+        block_pos.z.rem_euclid(CHUNK_SIZE),
+    )
+}
+
+pub fn chunk_origin(chunk_pos: IVec3) -> IVec3 {
+    chunk_pos * CHUNK_SIZE
+}
+
+/// Chunks whose meshes show the block at `block_pos`: its own chunk, and on a chunk border the
+/// neighbours that share its faces or sample its light, diagonal ones included.
+pub fn affected_chunks(block_pos: IVec3) -> Vec<IVec3> {
+    let chunk = chunk_pos(block_pos);
+    let local = local_pos(block_pos);
+    let range = |axis: usize| {
+        let min = if local[axis] == 0 { -1 } else { 0 };
+        let max = if local[axis] == CHUNK_SIZE - 1 { 1 } else { 0 };
+        min..=max
+    };
+
+    let mut chunks = Vec::new();
+    for x in range(0) {
+        for y in range(1) {
+            for z in range(2) {
+                chunks.push(chunk + ivec3(x, y, z));
+            }
+        }
+    }
+    chunks
+}
+
+/// Position of the channel's nibble in a block's light.
+fn light_shift(channel: LightChannel) -> u16 {
+    match channel {
+        LightChannel::Sky => 12,
+        LightChannel::Red => 8,
+        LightChannel::Green => 4,
+        LightChannel::Blue => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, IVec3};
+
+    use super::affected_chunks;
+
+    fn sorted(mut chunks: Vec<IVec3>) -> Vec<IVec3> {
+        chunks.sort_by_key(|c| (c.x, c.y, c.z));
+        chunks
+    }
+
+    #[test]
+    fn interior_block_affects_its_chunk() {
+        assert_eq!(affected_chunks(ivec3(37, 5, -8)), [ivec3(2, 0, -1)]);
+    }
+
+    #[test]
+    fn face_block_affects_the_neighbour_across_it() {
+        assert_eq!(
+            sorted(affected_chunks(ivec3(32, 5, 8))),
+            [ivec3(1, 0, 0), ivec3(2, 0, 0)]
+        );
+        assert_eq!(
+            sorted(affected_chunks(ivec3(5, -1, 8))),
+            [ivec3(0, -1, 0), ivec3(0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn edge_block_affects_both_neighbours_and_the_diagonal() {
+        assert_eq!(
+            sorted(affected_chunks(ivec3(0, 15, 8))),
+            [
+                ivec3(-1, 0, 0),
+                ivec3(-1, 1, 0),
+                ivec3(0, 0, 0),
+                ivec3(0, 1, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn corner_block_affects_eight_chunks() {
+        let chunks = sorted(affected_chunks(ivec3(-16, 31, 0)));
+        let mut expected = Vec::new();
+        for x in -2..=-1 {
+            for y in 1..=2 {
+                for z in -1..=0 {
+                    expected.push(ivec3(x, y, z));
+                }
+            }
+        }
+        assert_eq!(chunks, expected);
+    }
+}
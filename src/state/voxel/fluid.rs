@@ -0,0 +1,554 @@
+use glam::{ivec3, IVec3};
+
+use super::{block::Block, chunk::chunk_pos, terrain::Terrain, tick::TickScheduler};
+
+const HORIZONTAL: [IVec3; 4] = [
+    ivec3(1, 0, 0),
+    ivec3(-1, 0, 0),
+    ivec3(0, 0, 1),
+    ivec3(0, 0, -1),
+];
+
+const NEIGHBOURS: [IVec3; 6] = [
+    ivec3(1, 0, 0),
+    ivec3(-1, 0, 0),
+    ivec3(0, 1, 0),
+    ivec3(0, -1, 0),
+    ivec3(0, 0, 1),
+    ivec3(0, 0, -1),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    pub fn from_block(block: Block) -> Option<Self> {
+        match block {
+            Block::Water => Some(Fluid::Water),
+            Block::Lava => Some(Fluid::Lava),
+            _ => None,
+        }
+    }
+
+    pub fn block(self) -> Block {
+        match self {
+            Fluid::Water => Block::Water,
+            Fluid::Lava => Block::Lava,
+        }
+    }
+
+    /// Physics steps between updates of a block of this fluid.
+    pub fn tick_rate(self) -> u64 {
+        match self {
+            Fluid::Water => 5,
+            Fluid::Lava => 30,
+        }
+    }
+
+    /// Highest level flowing fluid reaches, which is how far it spreads over flat ground.
+    pub fn max_level(self) -> u8 {
+        match self {
+            Fluid::Water => 7,
+            Fluid::Lava => 3,
+        }
+    }
+
+    /// Whether flowing fluid between two sources becomes a source itself.
+    pub fn infinite_sources(self) -> bool {
+        match self {
+            Fluid::Water => true,
+            Fluid::Lava => false,
+        }
+    }
+}
+
+/// A fluid block. Sources have level 0, flowing fluid gets one level higher per block away from
+/// what feeds it. Falling fluid is fed from above and spreads like a source when it lands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FluidState {
+    pub fluid: Fluid,
+    pub level: u8,
+    pub falling: bool,
+}
+
+impl FluidState {
+    const FALLING: u8 = 0x8;
+
+    pub fn source(fluid: Fluid) -> Self {
+        Self {
+            fluid,
+            level: 0,
+            falling: false,
+        }
+    }
+
+    pub fn flowing(fluid: Fluid, level: u8) -> Self {
+        Self {
+            fluid,
+            level,
+            falling: false,
+        }
+    }
+
+    pub fn falling(fluid: Fluid) -> Self {
+        Self {
+            fluid,
+            level: 1,
+            falling: true,
+        }
+    }
+
+    pub fn is_source(&self) -> bool {
+        self.level == 0 && !self.falling
+    }
+
+    /// The byte stored with the block in its chunk.
+    pub fn to_byte(self) -> u8 {
+        self.level | if self.falling { Self::FALLING } else { 0 }
+    }
+
+    pub fn from_byte(fluid: Fluid, byte: u8) -> Self {
+        Self {
+            fluid,
+            level: byte & !Self::FALLING,
+            falling: byte & Self::FALLING != 0,
+        }
+    }
+
+    /// Level of the blocks this one spreads to sideways.
+    fn spread_level(&self) -> u8 {
+        if self.falling {
+            1
+        } else {
+            self.level + 1
+        }
+    }
+}
+
+/// What a fluid update turns a block into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FluidUpdate {
+    Fluid(Option<FluidState>),
+    /// Lava touching water hardens.
+    Solid(Block),
+}
+
+/// Schedules fluid updates around a block changed outside the simulation.
+pub fn schedule_fluid_updates(terrain: &Terrain, ticks: &mut TickScheduler, pos: IVec3) {
+    schedule(terrain, ticks, pos);
+    for offset in NEIGHBOURS {
+        schedule(terrain, ticks, pos + offset);
+    }
+}
+
+/// Runs the fluid updates of the blocks whose ticks are `due` and returns the blocks that
+/// changed.
+///
+/// Water and lava move through the terrain as a cellular automaton. Only blocks next to a change
+/// are updated: changing a block schedules it and its neighbours, after the tick rate of the
+/// fluids around them. All updates due in a step are computed from the same state and then
+/// applied together, in position order, so the result doesn't depend on the order they were
+/// scheduled in.
+pub fn update_fluids(
+    terrain: &mut Terrain,
+    ticks: &mut TickScheduler,
+    due: &[IVec3],
+) -> Vec<IVec3> {
+    let mut due = due.to_vec();
+    due.sort_by_key(|p| (p.x, p.y, p.z));
+    due.dedup();
+
+    let updates: Vec<_> = due
+        .into_iter()
+        .filter_map(|pos| next_state(terrain, pos).map(|update| (pos, update)))
+        .collect();
+
+    for (pos, update) in &updates {
+        match *update {
+            FluidUpdate::Fluid(state) => terrain.set_fluid(*pos, state),
+            FluidUpdate::Solid(block) => terrain.set_block(*pos, block),
+        }
+    }
+
+    let changed: Vec<_> = updates.into_iter().map(|(pos, _)| pos).collect();
+    for pos in &changed {
+        for offset in NEIGHBOURS {
+            schedule(terrain, ticks, *pos + offset);
+        }
+    }
+
+    changed
+}
+
+/// Schedules an update of `pos` after the tick rate of the fastest fluid that could change it,
+/// or not at all if there's no fluid around.
+fn schedule(terrain: &Terrain, ticks: &mut TickScheduler, pos: IVec3) {
+    let delay = std::iter::once(pos)
+        .chain(NEIGHBOURS.iter().map(|offset| pos + *offset))
+        .filter_map(|p| terrain.get_fluid(p))
+        .map(|state| state.fluid.tick_rate())
+        .min();
+
+    if let Some(delay) = delay {
+        ticks.schedule(pos, delay, 0);
+    }
+}
+
+/// Whether fluid can flow into the block, replacing it.
+fn is_open(terrain: &Terrain, pos: IVec3, fluid: Fluid) -> bool {
+    if !terrain.is_loaded(chunk_pos(pos)) {
+        return false;
+    }
+    match terrain.get_fluid(pos) {
+        Some(state) => state.fluid == fluid && !state.is_source(),
+        None => terrain.get_block(pos).is_air(),
+    }
+}
+
+fn touches(terrain: &Terrain, pos: IVec3, fluid: Fluid) -> bool {
+    NEIGHBOURS.iter().any(|offset| {
+        terrain
+            .get_fluid(pos + *offset)
+            .is_some_and(|s| s.fluid == fluid)
+    })
+}
+
+/// The state the block at `pos` should have given its neighbours, or `None` if it stays as is.
+fn next_state(terrain: &Terrain, pos: IVec3) -> Option<FluidUpdate> {
+    if !terrain.is_loaded(chunk_pos(pos)) {
+        return None;
+    }
+
+    let current = terrain.get_fluid(pos);
+    if current.is_none() && !terrain.get_block(pos).is_air() {
+        return None;
+    }
+
+    let next = match current {
+        Some(state) if state.is_source() => Some(state),
+        _ => flow_into(terrain, pos),
+    };
+
+    if next.is_some_and(|s| s.fluid == Fluid::Lava) && touches(terrain, pos, Fluid::Water) {
+        return Some(FluidUpdate::Solid(Block::Stone));
+    }
+
+    (next != current).then_some(FluidUpdate::Fluid(next))
+}
+
+/// The fluid flowing into an empty or flowing block from its neighbours.
+fn flow_into(terrain: &Terrain, pos: IVec3) -> Option<FluidState> {
+    if let Some(above) = terrain.get_fluid(pos + IVec3::Y) {
+        return Some(FluidState::falling(above.fluid));
+    }
+
+    let mut best: Option<FluidState> = None;
+    for offset in HORIZONTAL {
+        let neighbour = pos + offset;
+        let state = match terrain.get_fluid(neighbour) {
+            Some(state) => state,
+            None => continue,
+        };
+
+        // Flowing fluid falls rather than spreads while it can.
+        let resting = !is_open(terrain, neighbour - IVec3::Y, state.fluid);
+        if !state.is_source() && !resting {
+            continue;
+        }
+
+        let level = state.spread_level();
+        if level > state.fluid.max_level() {
+            continue;
+        }
+
+        let candidate = FluidState::flowing(state.fluid, level);
+        if best.is_none_or(|b| (level, state.fluid) < (b.level, b.fluid)) {
+            best = Some(candidate);
+        }
+    }
+
+    let fluid = best?.fluid;
+    if fluid.infinite_sources() {
+        let sources = HORIZONTAL
+            .iter()
+            .filter(|offset| {
+                terrain
+                    .get_fluid(pos + **offset)
+                    .is_some_and(|s| s.fluid == fluid && s.is_source())
+            })
+            .count();
+        let below = pos - IVec3::Y;
+        let supported = terrain.get_block(below).is_solid()
+            || terrain
+                .get_fluid(below)
+                .is_some_and(|s| s.fluid == fluid && s.is_source());
+
+        if sources >= 2 && supported {
+            return Some(FluidState::source(fluid));
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use glam::{ivec3, IVec3, Vec3};
+
+    use crate::state::{
+        clock::WorldClock,
+        save::{metadata::WorldMetadata, WorldSave},
+    };
+
+    use crate::state::voxel::{block::Block, terrain::Terrain, tick::TickScheduler};
+
+    use super::{schedule_fluid_updates, update_fluids, Fluid, FluidState};
+
+    fn place(ticks: &mut TickScheduler, terrain: &mut Terrain, pos: IVec3, state: FluidState) {
+        terrain.set_fluid(pos, Some(state));
+        schedule_fluid_updates(terrain, ticks, pos);
+    }
+
+    fn run(ticks: &mut TickScheduler, terrain: &mut Terrain, steps: usize) {
+        for _ in 0..steps {
+            let due = ticks.step();
+            update_fluids(terrain, ticks, &due);
+        }
+    }
+
+    #[test]
+    fn water_spreads_up_to_its_max_level() {
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        place(
+            &mut ticks,
+            &mut terrain,
+            ivec3(8, 1, 8),
+            FluidState::source(Fluid::Water),
+        );
+        run(&mut ticks, &mut terrain, 100);
+        assert!(ticks.is_idle());
+
+        for distance in 1..=7 {
+            let state = terrain.get_fluid(ivec3(8 + distance, 1, 8)).unwrap();
+            assert_eq!(state, FluidState::flowing(Fluid::Water, distance as u8));
+        }
+        assert_eq!(terrain.get_block(ivec3(16, 1, 8)), Block::Air);
+        assert_eq!(terrain.get_block(ivec3(8, 2, 8)), Block::Air);
+    }
+
+    #[test]
+    fn lava_is_slower_and_spreads_less() {
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        place(
+            &mut ticks,
+            &mut terrain,
+            ivec3(8, 1, 8),
+            FluidState::source(Fluid::Lava),
+        );
+
+        run(&mut ticks, &mut terrain, 29);
+        assert!(terrain.get_fluid(ivec3(9, 1, 8)).is_none());
+        run(&mut ticks, &mut terrain, 200);
+
+        assert_eq!(
+            terrain.get_fluid(ivec3(11, 1, 8)),
+            Some(FluidState::flowing(Fluid::Lava, 3))
+        );
+        assert!(terrain.get_fluid(ivec3(12, 1, 8)).is_none());
+    }
+
+    #[test]
+    fn water_falls_before_spreading() {
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        for y in 1..4 {
+            terrain.set_block(ivec3(4, y, 4), Block::Stone);
+        }
+        place(
+            &mut ticks,
+            &mut terrain,
+            ivec3(4, 4, 4),
+            FluidState::source(Fluid::Water),
+        );
+        run(&mut ticks, &mut terrain, 200);
+
+        assert_eq!(
+            terrain.get_fluid(ivec3(5, 3, 4)),
+            Some(FluidState::falling(Fluid::Water))
+        );
+        assert_eq!(
+            terrain.get_fluid(ivec3(5, 1, 4)),
+            Some(FluidState::falling(Fluid::Water))
+        );
+        // Flowing water doesn't spread sideways while it can fall.
+        assert!(terrain.get_fluid(ivec3(6, 3, 4)).is_none());
+        assert_eq!(
+            terrain.get_fluid(ivec3(7, 1, 4)),
+            Some(FluidState::flowing(Fluid::Water, 2))
+        );
+    }
+
+    #[test]
+    fn removing_the_source_drains_the_flow() {
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        place(
+            &mut ticks,
+            &mut terrain,
+            ivec3(8, 1, 8),
+            FluidState::source(Fluid::Water),
+        );
+        run(&mut ticks, &mut terrain, 100);
+
+        terrain.set_block(ivec3(8, 1, 8), Block::Air);
+        schedule_fluid_updates(&terrain, &mut ticks, ivec3(8, 1, 8));
+        run(&mut ticks, &mut terrain, 200);
+
+        assert!(ticks.is_idle());
+        assert!(terrain
+            .chunks()
+            .all(|(_, c)| !c.blocks().contains(&Block::Water)));
+    }
+
+    #[test]
+    fn water_between_two_sources_becomes_a_source() {
+        for fluid in [Fluid::Water, Fluid::Lava] {
+            let mut terrain = Terrain::stone_floor(2);
+            let mut ticks = TickScheduler::new(0);
+            place(
+                &mut ticks,
+                &mut terrain,
+                ivec3(8, 1, 8),
+                FluidState::source(fluid),
+            );
+            place(
+                &mut ticks,
+                &mut terrain,
+                ivec3(10, 1, 8),
+                FluidState::source(fluid),
+            );
+            run(&mut ticks, &mut terrain, 200);
+
+            let between = terrain.get_fluid(ivec3(9, 1, 8)).unwrap();
+            assert_eq!(between.is_source(), fluid == Fluid::Water);
+        }
+    }
+
+    #[test]
+    fn lava_touching_water_turns_to_stone() {
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        place(
+            &mut ticks,
+            &mut terrain,
+            ivec3(4, 1, 8),
+            FluidState::source(Fluid::Water),
+        );
+        place(
+            &mut ticks,
+            &mut terrain,
+            ivec3(12, 1, 8),
+            FluidState::source(Fluid::Lava),
+        );
+        run(&mut ticks, &mut terrain, 400);
+
+        assert!(ticks.is_idle());
+        let row: Vec<_> = (0..20).map(|x| terrain.get_block(ivec3(x, 1, 8))).collect();
+        assert!(row.contains(&Block::Stone));
+        assert_eq!(row[12], Block::Lava);
+        for pair in row.windows(2) {
+            assert!(!pair.contains(&Block::Water) || !pair.contains(&Block::Lava));
+        }
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let simulate = || {
+            let mut terrain = Terrain::stone_floor(2);
+            let mut ticks = TickScheduler::new(0);
+            for (pos, fluid) in [
+                (ivec3(3, 1, 3), Fluid::Water),
+                (ivec3(9, 1, 5), Fluid::Lava),
+                (ivec3(20, 1, 10), Fluid::Water),
+                (ivec3(6, 1, 12), Fluid::Lava),
+            ] {
+                place(&mut ticks, &mut terrain, pos, FluidState::source(fluid));
+            }
+            run(&mut ticks, &mut terrain, 300);
+
+            let mut blocks = Vec::new();
+            for x in 0..32 {
+                for z in 0..16 {
+                    let pos = ivec3(x, 1, z);
+                    blocks.push((terrain.get_block(pos), terrain.get_fluid(pos)));
+                }
+            }
+            blocks
+        };
+
+        assert_eq!(simulate(), simulate());
+    }
+
+    #[test]
+    fn flow_continues_after_saving_and_loading() {
+        let source = ivec3(8, 1, 8);
+        let row = |terrain: &Terrain| -> Vec<_> {
+            (0..32).map(|x| terrain.get_fluid(ivec3(x, 1, 8))).collect()
+        };
+
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        place(
+            &mut ticks,
+            &mut terrain,
+            source,
+            FluidState::source(Fluid::Water),
+        );
+        run(&mut ticks, &mut terrain, 100);
+        let expected = row(&terrain);
+
+        let mut terrain = Terrain::stone_floor(2);
+        let mut ticks = TickScheduler::new(0);
+        place(
+            &mut ticks,
+            &mut terrain,
+            source,
+            FluidState::source(Fluid::Water),
+        );
+        run(&mut ticks, &mut terrain, 12);
+        assert!(terrain.get_fluid(ivec3(13, 1, 8)).is_none());
+
+        let dir = std::env::temp_dir().join(format!("minerust-fluid-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let save = WorldSave::open(&dir).unwrap();
+        save.save_terrain(&mut terrain, &HashMap::new()).unwrap();
+        save.save_metadata(&WorldMetadata {
+            seed: 0,
+            player_pos: Vec3::ZERO,
+            yaw: 0.,
+            pitch: 0.,
+            tick: ticks.tick(),
+            scheduled_ticks: ticks.pending(),
+            clock: WorldClock::default(),
+        })
+        .unwrap();
+
+        let metadata = save.load_metadata().unwrap();
+        let mut terrain = Terrain::new(metadata.seed);
+        save.load_area(&mut terrain, ivec3(0, 0, 0), ivec3(1, 0, 0));
+        let mut ticks =
+            TickScheduler::from_saved(metadata.seed, metadata.tick, &metadata.scheduled_ticks);
+        run(&mut ticks, &mut terrain, 88);
+
+        assert!(ticks.is_idle());
+        assert_eq!(row(&terrain), expected);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
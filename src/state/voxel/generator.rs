@@ -0,0 +1,120 @@
+use glam::{ivec3, IVec3};
+
+use super::{
+    block::Block,
+    chunk::{chunk_origin, Chunk, CHUNK_SIZE},
+};
+
+pub const SEA_LEVEL: i32 = 0;
+
+const BASE_HEIGHT: f32 = 4.;
+const HEIGHT_AMPLITUDE: f32 = 14.;
+const DIRT_DEPTH: i32 = 3;
+
+/// Deterministic heightmap terrain generator based on fractal value noise.
+#[derive(Clone, Copy, Debug)]
+pub struct Generator {
+    seed: u32,
+}
+
+impl Generator {
+    pub fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn height(&self, x: i32, z: i32) -> i32 {
+        let (x, z) = (x as f32, z as f32);
+        let mut value = 0.;
+        let mut amplitude = 1.;
+        let mut frequency = 1. / 48.;
+        let mut total = 0.;
+        for octave in 0..4 {
+            value += self.value_noise(x * frequency, z * frequency, octave) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+
+        (BASE_HEIGHT + (value / total * 2. - 1.) * HEIGHT_AMPLITUDE).floor() as i32
+    }
+
+    pub fn block_at(&self, pos: IVec3, height: i32) -> Block {
+        if pos.y > height {
+            Block::Air
+        } else if pos.y == height {
+            if height <= SEA_LEVEL + 1 {
+                Block::Sand
+            } else {
+                Block::Grass
+            }
+        } else if pos.y > height - DIRT_DEPTH {
+            if height <= SEA_LEVEL + 1 {
+                Block::Sand
+            } else {
+                Block::Dirt
+            }
+        } else if self.hash(pos.x, pos.y, pos.z).is_multiple_of(23) {
+            Block::Gravel
+        } else {
+            Block::Stone
+        }
+    }
+
+    pub fn generate(&self, chunk_pos: IVec3) -> Chunk {
+        let origin = chunk_origin(chunk_pos);
+        let mut chunk = Chunk::new();
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let height = self.height(origin.x + x, origin.z + z);
+                for y in 0..CHUNK_SIZE {
+                    let local = ivec3(x, y, z);
+                    let block = self.block_at(origin + local, height);
+                    if !block.is_air() {
+                        chunk.set(local, block);
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+
+    fn value_noise(&self, x: f32, z: f32, octave: i32) -> f32 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (fx, fz) = (smoothstep(x - x0), smoothstep(z - z0));
+        let (x0, z0) = (x0 as i32, z0 as i32);
+
+        let corner =
+            |dx: i32, dz: i32| (self.hash(x0 + dx, octave, z0 + dz) & 0xffff) as f32 / 65535.;
+
+        let top = lerp(corner(0, 0), corner(1, 0), fx);
+        let bottom = lerp(corner(0, 1), corner(1, 1), fx);
+        lerp(top, bottom, fz)
+    }
+
+    fn hash(&self, x: i32, y: i32, z: i32) -> u32 {
+        let mut h = self
+            .seed
+            .wrapping_add((x as u32).wrapping_mul(0x8da6_b343))
+            .wrapping_add((y as u32).wrapping_mul(0xd816_3841))
+            .wrapping_add((z as u32).wrapping_mul(0xcb1a_b31f));
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x2c1b_3c6d);
+        h ^= h >> 12;
+        h = h.wrapping_mul(0x297a_2d39);
+        h ^ (h >> 15)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3. - 2. * t)
+}
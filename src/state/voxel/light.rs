@@ -0,0 +1,426 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use glam::{ivec3, IVec3};
+
+use super::{
+    block::Block,
+    chunk::{chunk_origin, chunk_pos, Chunk, CHUNK_SIZE},
+    terrain::Terrain,
+};
+
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// The independent kinds of light every block stores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    /// Light from the sky. Full sky light goes straight down without getting dimmer.
+    Sky,
+    /// The colours of the light emitted by blocks like lava, each spreading on its own.
+    Red,
+    Green,
+    Blue,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 4] = [
+        LightChannel::Sky,
+        LightChannel::Red,
+        LightChannel::Green,
+        LightChannel::Blue,
+    ];
+    /// The channels of block light.
+    pub const BLOCK: [LightChannel; 3] =
+        [LightChannel::Red, LightChannel::Green, LightChannel::Blue];
+
+    /// Light level `block` emits in the channel, none for blocks shaded as point lights.
+    pub fn emission(self, block: Block) -> u8 {
+        if block.is_point_light() {
+            return 0;
+        }
+        match self {
+            LightChannel::Sky => 0,
+            LightChannel::Red => block.light_emission()[0],
+            LightChannel::Green => block.light_emission()[1],
+            LightChannel::Blue => block.light_emission()[2],
+        }
+    }
+
+    /// Levels light of the channel loses passing through `block` on top of the one it loses
+    /// per block. Coloured blocks filter block light, the sky light has no colour to filter.
+    pub fn opacity(self, block: Block) -> u8 {
+        let filter = match self {
+            LightChannel::Sky => 0,
+            LightChannel::Red => block.light_filter()[0],
+            LightChannel::Green => block.light_filter()[1],
+            LightChannel::Blue => block.light_filter()[2],
+        };
+        block.light_opacity().saturating_add(filter)
+    }
+}
+
+/// Propagates light through the terrain by breadth-first flood fill.
+///
+/// Light loses one level per block plus the opacity of the blocks it enters. When a block
+/// changes, the light it used to carry is first removed by flooding outwards from it, collecting
+/// the brighter blocks around the darkened area, and then those and any new source flood back
+/// in. Light crosses chunk borders freely but never enters chunks that aren't loaded.
+#[derive(Default)]
+pub struct LightEngine {
+    additions: VecDeque<IVec3>,
+    removals: VecDeque<(IVec3, u8)>,
+    changed: HashSet<IVec3>,
+}
+
+impl LightEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Computes the light of every loaded chunk from scratch.
+    pub fn light_all(&mut self, terrain: &mut Terrain) {
+        let chunks: Vec<IVec3> = terrain.chunks().map(|(pos, _)| *pos).collect();
+        let mut tops = HashMap::<(i32, i32), i32>::new();
+        for pos in chunks {
+            terrain.clear_light(pos);
+            let top = tops.entry((pos.x, pos.z)).or_insert(pos.y);
+            *top = (*top).max(pos.y);
+        }
+
+        // Sky light fills every column from the top of the loaded terrain down to the first
+        // block that dims it. It only has to spread sideways from where a neighbouring column
+        // stops higher up, and down from the bottom of the column.
+        let mut bottoms = HashMap::<(i32, i32), i32>::new();
+        for ((x, z), top) in tops {
+            let origin = chunk_origin(ivec3(x, top, z));
+            for dz in 0..CHUNK_SIZE {
+                for dx in 0..CHUNK_SIZE {
+                    let mut pos = origin + ivec3(dx, CHUNK_SIZE, dz);
+                    while is_loaded(terrain, pos - IVec3::Y)
+                        && terrain.get_block(pos - IVec3::Y).light_opacity() == 0
+                    {
+                        pos.y -= 1;
+                        terrain.set_light(pos, LightChannel::Sky, MAX_LIGHT);
+                    }
+                    bottoms.insert((pos.x, pos.z), pos.y);
+                }
+            }
+        }
+        for (&(x, z), &bottom) in &bottoms {
+            let highest = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .filter_map(|(dx, dz)| bottoms.get(&(x + dx, z + dz)).copied())
+                .max()
+                .unwrap_or(bottom);
+            for y in bottom..highest.max(bottom + 1) {
+                self.additions.push_back(ivec3(x, y, z));
+            }
+        }
+        self.propagate(terrain, LightChannel::Sky);
+
+        let sources: Vec<(IVec3, Block)> = terrain
+            .chunks()
+            .flat_map(|(chunk_pos, chunk)| {
+                let origin = chunk_origin(*chunk_pos);
+                chunk
+                    .blocks()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, block)| block.emits_light())
+                    .map(move |(index, block)| (origin + Chunk::local_from_index(index), *block))
+            })
+            .collect();
+        for channel in LightChannel::BLOCK {
+            for &(pos, block) in &sources {
+                let emission = channel.emission(block);
+                if emission > 0 {
+                    terrain.set_light(pos, channel, emission);
+                    self.additions.push_back(pos);
+                }
+            }
+            self.propagate(terrain, channel);
+        }
+        self.changed.clear();
+    }
+
+    /// Updates the light around a block that changed.
+    pub fn relight(&mut self, terrain: &mut Terrain, pos: IVec3) {
+        for channel in LightChannel::ALL {
+            self.relight_channel(terrain, pos, channel);
+        }
+    }
+
+    /// Takes the blocks whose light changed since the last call, so the meshes showing them can
+    /// be rebuilt. Changes made by [`Self::light_all`] aren't included.
+    pub fn take_changed(&mut self) -> HashSet<IVec3> {
+        std::mem::take(&mut self.changed)
+    }
+
+    fn set_light(&mut self, terrain: &mut Terrain, pos: IVec3, channel: LightChannel, level: u8) {
+        terrain.set_light(pos, channel, level);
+        self.changed.insert(pos);
+    }
+
+    fn relight_channel(&mut self, terrain: &mut Terrain, pos: IVec3, channel: LightChannel) {
+        let old = terrain.get_light(pos, channel);
+        if old > 0 {
+            self.set_light(terrain, pos, channel, 0);
+            self.removals.push_back((pos, old));
+            self.unlight(terrain, channel);
+        }
+
+        let source = source_level(terrain, pos, channel);
+        if source > 0 {
+            self.set_light(terrain, pos, channel, source);
+            self.additions.push_back(pos);
+        }
+        for offset in NEIGHBOURS {
+            if terrain.get_light(pos + offset, channel) > 0 {
+                self.additions.push_back(pos + offset);
+            }
+        }
+
+        self.propagate(terrain, channel);
+    }
+
+    /// Removes the light that spread from the queued removals, queueing the brighter blocks at
+    /// the edge of the removed area and the sources inside it to flood back in.
+    fn unlight(&mut self, terrain: &mut Terrain, channel: LightChannel) {
+        while let Some((pos, level)) = self.removals.pop_front() {
+            for offset in NEIGHBOURS {
+                let neighbour = pos + offset;
+                if !is_loaded(terrain, neighbour) {
+                    continue;
+                }
+
+                let light = terrain.get_light(neighbour, channel);
+                if light == 0 {
+                    continue;
+                }
+
+                let sky_column = channel == LightChannel::Sky
+                    && offset == IVec3::NEG_Y
+                    && level == MAX_LIGHT
+                    && light == MAX_LIGHT;
+                if light < level || sky_column {
+                    self.set_light(terrain, neighbour, channel, 0);
+                    self.removals.push_back((neighbour, light));
+
+                    let source = source_level(terrain, neighbour, channel);
+                    if source > 0 {
+                        self.set_light(terrain, neighbour, channel, source);
+                        self.additions.push_back(neighbour);
+                    }
+                } else {
+                    self.additions.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Spreads light from the queued additions into every block it brightens.
+    fn propagate(&mut self, terrain: &mut Terrain, channel: LightChannel) {
+        while let Some(pos) = self.additions.pop_front() {
+            let level = terrain.get_light(pos, channel);
+            if level <= 1 {
+                continue;
+            }
+
+            for offset in NEIGHBOURS {
+                let neighbour = pos + offset;
+                if !is_loaded(terrain, neighbour) {
+                    continue;
+                }
+
+                let opacity = channel.opacity(terrain.get_block(neighbour));
+                let spread = if channel == LightChannel::Sky
+                    && offset == IVec3::NEG_Y
+                    && level == MAX_LIGHT
+                    && opacity == 0
+                {
+                    MAX_LIGHT
+                } else {
+                    level.saturating_sub(1 + opacity)
+                };
+
+                if spread > terrain.get_light(neighbour, channel) {
+                    self.set_light(terrain, neighbour, channel, spread);
+                    self.additions.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+/// Light the block at `pos` has regardless of its neighbours: its emission, or full sky light
+/// under an open sky.
+fn source_level(terrain: &Terrain, pos: IVec3, channel: LightChannel) -> u8 {
+    let block = terrain.get_block(pos);
+    match channel {
+        LightChannel::Red | LightChannel::Green | LightChannel::Blue => channel.emission(block),
+        LightChannel::Sky => {
+            let open = block.light_opacity() == 0
+                && terrain.get_light(pos + IVec3::Y, LightChannel::Sky) == MAX_LIGHT;
+            if open {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+    }
+}
+
+fn is_loaded(terrain: &Terrain, pos: IVec3) -> bool {
+    terrain.is_loaded(chunk_pos(pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{ivec3, IVec3};
+
+    use crate::state::voxel::{
+        block::Block,
+        chunk::{chunk_origin, Chunk, CHUNK_VOLUME},
+        terrain::Terrain,
+    };
+
+    use super::{LightChannel, LightEngine, MAX_LIGHT};
+
+    fn sky(terrain: &Terrain, pos: IVec3) -> u8 {
+        terrain.get_light(pos, LightChannel::Sky)
+    }
+
+    fn red(terrain: &Terrain, pos: IVec3) -> u8 {
+        terrain.get_light(pos, LightChannel::Red)
+    }
+
+    fn color(terrain: &Terrain, pos: IVec3) -> [u8; 3] {
+        LightChannel::BLOCK.map(|channel| terrain.get_light(pos, channel))
+    }
+
+    /// Asserts that the light of every loaded block is what lighting from scratch gives.
+    fn assert_matches_full_relight(terrain: &mut Terrain) {
+        let positions: Vec<IVec3> = terrain
+            .chunks()
+            .flat_map(|(pos, _)| {
+                let origin = chunk_origin(*pos);
+                (0..CHUNK_VOLUME).map(move |i| origin + Chunk::local_from_index(i))
+            })
+            .collect();
+        let light = |terrain: &Terrain| -> Vec<(u8, [u8; 3])> {
+            positions
+                .iter()
+                .map(|p| (sky(terrain, *p), color(terrain, *p)))
+                .collect()
+        };
+
+        let incremental = light(terrain);
+        LightEngine::new().light_all(terrain);
+        let full = light(terrain);
+        for (i, pos) in positions.iter().enumerate() {
+            assert_eq!(incremental[i], full[i], "Light differs at {}.", pos);
+        }
+    }
+
+    #[test]
+    fn sky_light_falls_straight_down_and_fades_under_roofs() {
+        let mut terrain = Terrain::stone_floor(2);
+        for x in 0..8 {
+            for z in 0..16 {
+                terrain.set_block(ivec3(x, 5, z), Block::Stone);
+            }
+        }
+        LightEngine::new().light_all(&mut terrain);
+
+        assert_eq!(sky(&terrain, ivec3(10, 1, 4)), MAX_LIGHT);
+        assert_eq!(sky(&terrain, ivec3(7, 4, 4)), MAX_LIGHT - 1);
+        assert_eq!(sky(&terrain, ivec3(3, 1, 4)), MAX_LIGHT - 5);
+        assert_eq!(sky(&terrain, ivec3(3, 0, 4)), 0);
+    }
+
+    #[test]
+    fn block_light_crosses_chunk_borders() {
+        let mut terrain = Terrain::stone_floor(2);
+        terrain.set_block(ivec3(14, 1, 4), Block::Lava);
+        LightEngine::new().light_all(&mut terrain);
+
+        assert_eq!(red(&terrain, ivec3(14, 1, 4)), MAX_LIGHT);
+        assert_eq!(red(&terrain, ivec3(16, 1, 4)), MAX_LIGHT - 2);
+        assert_eq!(red(&terrain, ivec3(20, 3, 6)), MAX_LIGHT - 10);
+        assert_eq!(red(&terrain, ivec3(29, 1, 4)), 0);
+    }
+
+    #[test]
+    fn placing_and_breaking_blocks_relights_incrementally() {
+        let mut terrain = Terrain::stone_floor(2);
+        let mut engine = LightEngine::new();
+        engine.light_all(&mut terrain);
+
+        let mut edit = |terrain: &mut Terrain, pos: IVec3, block: Block| {
+            terrain.set_block(pos, block);
+            engine.relight(terrain, pos);
+        };
+
+        // A roof spanning the chunk border, with a lava source under it.
+        for x in 10..22 {
+            for z in 2..8 {
+                edit(&mut terrain, ivec3(x, 4, z), Block::Stone);
+            }
+        }
+        edit(&mut terrain, ivec3(15, 1, 4), Block::Lava);
+        assert_matches_full_relight(&mut terrain);
+        assert!(sky(&terrain, ivec3(16, 3, 5)) < MAX_LIGHT);
+
+        // Opening a hole lets the sky in, removing the lava darkens its surroundings.
+        edit(&mut terrain, ivec3(16, 4, 5), Block::Air);
+        assert_eq!(sky(&terrain, ivec3(16, 1, 5)), MAX_LIGHT);
+        edit(&mut terrain, ivec3(15, 1, 4), Block::Air);
+        assert_eq!(color(&terrain, ivec3(16, 1, 4)), [0; 3]);
+        edit(&mut terrain, ivec3(12, 1, 3), Block::Water);
+        assert_matches_full_relight(&mut terrain);
+
+        // Coloured glass in front of lava filters its light.
+        edit(&mut terrain, ivec3(20, 1, 10), Block::Lava);
+        edit(&mut terrain, ivec3(21, 1, 10), Block::BlueGlass);
+        edit(&mut terrain, ivec3(21, 1, 10), Block::GreenGlass);
+        assert_matches_full_relight(&mut terrain);
+    }
+
+    #[test]
+    fn block_light_keeps_its_colour_and_is_filtered_by_glass() {
+        let mut terrain = Terrain::stone_floor(2);
+        // Lava in a stone box that's only open through a wall of red glass on its +X side.
+        for x in 4..=6 {
+            for y in 1..=3 {
+                for z in 4..=6 {
+                    terrain.set_block(ivec3(x, y, z), Block::Stone);
+                }
+            }
+        }
+        terrain.set_block(ivec3(5, 2, 5), Block::Lava);
+        terrain.set_block(ivec3(6, 2, 5), Block::RedGlass);
+        LightEngine::new().light_all(&mut terrain);
+
+        let [r, g, b] = Block::Lava.light_emission();
+        assert_eq!(color(&terrain, ivec3(5, 2, 5)), [r, g, b]);
+        // Red passes the glass losing a level per block, green and blue also lose the filter's.
+        assert_eq!(
+            color(&terrain, ivec3(7, 2, 5)),
+            [r - 2, g - 2 - 6, b.saturating_sub(2 + 6)]
+        );
+
+        // Blocks shaded as point lights don't add to the voxel light as well.
+        terrain.set_block(ivec3(5, 2, 5), Block::Glowstone);
+        LightEngine::new().light_all(&mut terrain);
+        assert_eq!(color(&terrain, ivec3(5, 2, 5)), [0; 3]);
+        assert_eq!(color(&terrain, ivec3(7, 2, 5)), [0; 3]);
+    }
+}
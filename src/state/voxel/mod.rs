@@ -0,0 +1,7 @@
+pub mod block;
+pub mod chunk;
+pub mod fluid;
+pub mod generator;
+pub mod light;
+pub mod terrain;
+pub mod tick;
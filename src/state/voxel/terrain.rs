@@ -0,0 +1,194 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use glam::IVec3;
+
+use super::{
+    block::Block,
+    chunk::{chunk_pos, local_pos, Chunk},
+    fluid::{Fluid, FluidState},
+    generator::Generator,
+    light::{LightChannel, MAX_LIGHT},
+};
+
+/// Sparse block storage made of cubic chunks keyed by chunk position.
+pub struct Terrain {
+    chunks: HashMap<IVec3, Chunk>,
+    generator: Generator,
+    /// Chunks that changed since they were last saved.
+    dirty: HashSet<IVec3>,
+    /// Loaded chunks with blocks other than air, as `(x, y, z)` so they stay in a fixed order.
+    non_empty: BTreeSet<(i32, i32, i32)>,
+}
+
+impl Terrain {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            chunks: HashMap::new(),
+            generator: Generator::new(seed),
+            dirty: HashSet::new(),
+            non_empty: BTreeSet::new(),
+        }
+    }
+
+    pub fn generator(&self) -> &Generator {
+        &self.generator
+    }
+
+    /// Returns `Block::Air` for positions in chunks that aren't loaded.
+    pub fn get_block(&self, pos: IVec3) -> Block {
+        self.chunks
+            .get(&chunk_pos(pos))
+            .map_or(Block::Air, |c| c.get(local_pos(pos)))
+    }
+
+    pub fn set_block(&mut self, pos: IVec3, block: Block) {
+        let chunk_pos = chunk_pos(pos);
+        self.chunks
+            .entry(chunk_pos)
+            .or_default()
+            .set(local_pos(pos), block);
+        self.dirty.insert(chunk_pos);
+        self.update_non_empty(chunk_pos);
+    }
+
+    /// The fluid at `pos`, if the block is a fluid.
+    pub fn get_fluid(&self, pos: IVec3) -> Option<FluidState> {
+        let chunk = self.chunks.get(&chunk_pos(pos))?;
+        let local = local_pos(pos);
+        let fluid = Fluid::from_block(chunk.get(local))?;
+        Some(FluidState::from_byte(fluid, chunk.fluid_level(local)))
+    }
+
+    /// Replaces the block with the fluid, or with air for `None`.
+    pub fn set_fluid(&mut self, pos: IVec3, state: Option<FluidState>) {
+        match state {
+            Some(state) => {
+                self.set_block(pos, state.fluid.block());
+                self.chunks
+                    .get_mut(&chunk_pos(pos))
+                    .unwrap()
+                    .set_fluid_level(local_pos(pos), state.to_byte());
+            }
+            None => self.set_block(pos, Block::Air),
+        }
+    }
+
+    /// Light level of `channel` at `pos`. Chunks that aren't loaded are lit by the sky only.
+    pub fn get_light(&self, pos: IVec3, channel: LightChannel) -> u8 {
+        match self.chunks.get(&chunk_pos(pos)) {
+            Some(chunk) => chunk.light(local_pos(pos), channel),
+            None if channel == LightChannel::Sky => MAX_LIGHT,
+            None => 0,
+        }
+    }
+
+    /// Sets the light level of a block in a loaded chunk. Light isn't saved, so the chunk isn't
+    /// marked as modified.
+    pub fn set_light(&mut self, pos: IVec3, channel: LightChannel, level: u8) {
+        if let Some(chunk) = self.chunks.get_mut(&chunk_pos(pos)) {
+            chunk.set_light(local_pos(pos), channel, level);
+        }
+    }
+
+    pub fn clear_light(&mut self, pos: IVec3) {
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            chunk.clear_light();
+        }
+    }
+
+    pub fn chunk(&self, pos: IVec3) -> Option<&Chunk> {
+        self.chunks.get(&pos)
+    }
+
+    /// The chunk is counted as modified and as not empty, whatever is done with it.
+    pub fn chunk_mut(&mut self, pos: IVec3) -> Option<&mut Chunk> {
+        let chunk = self.chunks.get_mut(&pos);
+        if chunk.is_some() {
+            self.dirty.insert(pos);
+            self.non_empty.insert(pos.into());
+        }
+        chunk
+    }
+
+    /// Inserts a chunk as it is on disk, so it isn't marked as modified.
+    pub fn insert_chunk(&mut self, pos: IVec3, chunk: Chunk) {
+        self.chunks.insert(pos, chunk);
+        self.update_non_empty(pos);
+    }
+
+    pub fn remove_chunk(&mut self, pos: IVec3) -> Option<Chunk> {
+        self.non_empty.remove(&pos.into());
+        self.chunks.remove(&pos)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (&IVec3, &Chunk)> {
+        self.chunks.iter()
+    }
+
+    /// Positions of the loaded chunks that aren't all air, ordered by x, then y, then z.
+    pub fn non_empty_chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.non_empty.iter().map(|&pos| pos.into())
+    }
+
+    pub fn is_loaded(&self, pos: IVec3) -> bool {
+        self.chunks.contains_key(&pos)
+    }
+
+    pub fn generate_chunk(&mut self, pos: IVec3) {
+        let chunk = self.generator.generate(pos);
+        self.chunks.insert(pos, chunk);
+        self.dirty.insert(pos);
+        self.update_non_empty(pos);
+    }
+
+    pub fn mark_dirty(&mut self, pos: IVec3) {
+        self.dirty.insert(pos);
+    }
+
+    fn update_non_empty(&mut self, pos: IVec3) {
+        match self.chunks.get(&pos) {
+            Some(chunk) if !chunk.is_empty() => self.non_empty.insert(pos.into()),
+            _ => self.non_empty.remove(&pos.into()),
+        };
+    }
+
+    /// Returns the chunks modified since the last call and clears the set.
+    pub fn take_dirty(&mut self) -> HashSet<IVec3> {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// Generates every missing chunk in the inclusive chunk range `min..=max`.
+    pub fn generate_area(&mut self, min: IVec3, max: IVec3) {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    let pos = IVec3::new(x, y, z);
+                    if !self.is_loaded(pos) {
+                        self.generate_chunk(pos);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Terrain {
+    /// A row of `chunks` empty chunks from the origin along +X, with a floor of stone at y = 0.
+    pub fn stone_floor(chunks: i32) -> Self {
+        use glam::ivec3;
+
+        use super::chunk::CHUNK_SIZE;
+
+        let mut terrain = Self::new(0);
+        for x in 0..chunks {
+            terrain.insert_chunk(ivec3(x, 0, 0), Chunk::new());
+        }
+        for x in 0..chunks * CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                terrain.set_block(ivec3(x, 0, z), Block::Stone);
+            }
+        }
+        terrain
+    }
+}
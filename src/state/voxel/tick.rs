@@ -0,0 +1,272 @@
+use std::collections::{BTreeSet, HashSet};
+
+use glam::{ivec3, IVec3};
+
+use super::{
+    block::Block,
+    chunk::{chunk_origin, Chunk, CHUNK_VOLUME},
+    terrain::Terrain,
+};
+
+/// Blocks picked at random in every loaded chunk each physics step.
+pub const RANDOM_TICKS_PER_CHUNK: usize = 3;
+
+/// Longest vine that keeps growing downwards.
+const MAX_VINE_LENGTH: i32 = 8;
+/// A random tick of a vine has a one in this many chance of growing it.
+const VINE_GROWTH_ODDS: u32 = 4;
+
+/// Due tick, priority, scheduling order and position of a scheduled tick.
+type QueueEntry = (u64, i8, u64, (i32, i32, i32));
+
+/// A block update due at a world tick. Among the ticks due in the same step, lower priorities
+/// run first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScheduledTick {
+    pub pos: IVec3,
+    pub due: u64,
+    pub priority: i8,
+}
+
+/// The world's clock, counted in physics steps, and the block updates scheduled on it.
+///
+/// A block has at most one scheduled tick at a time, scheduling it again before it ran does
+/// nothing. Random ticks come from a small generator seeded with the world seed and, for a
+/// loaded world, the tick it was saved at, so the same world plays out the same way but a
+/// reloaded one doesn't repeat the random ticks of the last session.
+pub struct TickScheduler {
+    tick: u64,
+    queue: BTreeSet<QueueEntry>,
+    scheduled: HashSet<IVec3>,
+    next_order: u64,
+    random: u64,
+}
+
+impl TickScheduler {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            tick: 0,
+            queue: BTreeSet::new(),
+            scheduled: HashSet::new(),
+            next_order: 0,
+            random: seed as u64,
+        }
+    }
+
+    /// A scheduler continuing from a saved tick with the saved pending ticks.
+    pub fn from_saved(seed: u32, tick: u64, ticks: &[ScheduledTick]) -> Self {
+        let mut scheduler = Self::new(seed);
+        scheduler.tick = tick;
+        scheduler.random ^= tick.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        for saved in ticks {
+            scheduler.insert(saved.pos, saved.due, saved.priority);
+        }
+        scheduler
+    }
+
+    /// Physics steps since the world was created.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Whether no ticks are scheduled.
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Schedules a tick of `pos` in `delay` steps, unless it already has one.
+    pub fn schedule(&mut self, pos: IVec3, delay: u64, priority: i8) {
+        self.insert(pos, self.tick + delay.max(1), priority);
+    }
+
+    /// The pending ticks, in the order they will run.
+    pub fn pending(&self) -> Vec<ScheduledTick> {
+        self.queue
+            .iter()
+            .map(|&(due, priority, _, (x, y, z))| ScheduledTick {
+                pos: ivec3(x, y, z),
+                due,
+                priority,
+            })
+            .collect()
+    }
+
+    /// Advances the clock by one step and returns the blocks whose ticks are due, in order.
+    pub fn step(&mut self) -> Vec<IVec3> {
+        self.tick += 1;
+
+        let mut due = Vec::new();
+        while let Some(&(tick, _, _, (x, y, z))) = self.queue.first() {
+            if tick > self.tick {
+                break;
+            }
+            self.queue.pop_first();
+            let pos = ivec3(x, y, z);
+            self.scheduled.remove(&pos);
+            due.push(pos);
+        }
+
+        due
+    }
+
+    /// Picks `per_chunk` random blocks in every loaded chunk that isn't empty.
+    pub fn random_ticks(&mut self, terrain: &Terrain, per_chunk: usize) -> Vec<IVec3> {
+        let mut positions = Vec::new();
+        // Chunks are visited in a fixed order so the same seed picks the same blocks.
+        for pos in terrain.non_empty_chunks() {
+            for _ in 0..per_chunk {
+                let index = self.random() as usize % CHUNK_VOLUME;
+                positions.push(chunk_origin(pos) + Chunk::local_from_index(index));
+            }
+        }
+
+        positions
+    }
+
+    /// Next number of a SplitMix64 sequence.
+    pub fn random(&mut self) -> u32 {
+        self.random = self.random.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.random;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 32) as u32
+    }
+
+    fn insert(&mut self, pos: IVec3, due: u64, priority: i8) {
+        if self.scheduled.insert(pos) {
+            self.queue
+                .insert((due, priority, self.next_order, (pos.x, pos.y, pos.z)));
+            self.next_order += 1;
+        }
+    }
+}
+
+/// What a random tick of the block at `pos` changes, as the block to set and where. `roll` is a
+/// random number for the block to use.
+///
+/// Grass covered by an opaque block dies back to dirt and otherwise spreads to dirt around it
+/// that has nothing opaque on top. Vines slowly grow downwards.
+pub fn random_tick(terrain: &Terrain, pos: IVec3, roll: u32) -> Option<(IVec3, Block)> {
+    match terrain.get_block(pos) {
+        Block::Grass => {
+            if terrain.get_block(pos + IVec3::Y).is_opaque() {
+                return Some((pos, Block::Dirt));
+            }
+
+            let offset = ivec3(
+                (roll % 3) as i32 - 1,
+                (roll / 3 % 3) as i32 - 1,
+                (roll / 9 % 3) as i32 - 1,
+            );
+            let target = pos + offset;
+            let covered = terrain.get_block(target + IVec3::Y).is_opaque();
+            (terrain.get_block(target) == Block::Dirt && !covered).then_some((target, Block::Grass))
+        }
+        Block::Vine => {
+            let below = pos - IVec3::Y;
+            let length = (1..=MAX_VINE_LENGTH)
+                .take_while(|y| terrain.get_block(pos + IVec3::Y * *y) == Block::Vine)
+                .count() as i32
+                + 1;
+            let grows = roll.is_multiple_of(VINE_GROWTH_ODDS) && length < MAX_VINE_LENGTH;
+            (grows && terrain.get_block(below).is_air()).then_some((below, Block::Vine))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use crate::state::voxel::{block::Block, chunk::Chunk, terrain::Terrain};
+
+    use super::{random_tick, TickScheduler};
+
+    #[test]
+    fn runs_due_ticks_by_time_then_priority() {
+        let mut ticks = TickScheduler::new(0);
+        ticks.schedule(ivec3(0, 0, 0), 3, 0);
+        ticks.schedule(ivec3(1, 0, 0), 2, 1);
+        ticks.schedule(ivec3(2, 0, 0), 2, -1);
+        // Already scheduled, so this doesn't move it earlier.
+        ticks.schedule(ivec3(0, 0, 0), 1, 0);
+        assert_eq!(ticks.pending().len(), 3);
+
+        assert!(ticks.step().is_empty());
+        assert_eq!(ticks.step(), vec![ivec3(2, 0, 0), ivec3(1, 0, 0)]);
+        assert_eq!(ticks.step(), vec![ivec3(0, 0, 0)]);
+        assert!(ticks.pending().is_empty());
+    }
+
+    #[test]
+    fn restores_saved_ticks() {
+        let mut ticks = TickScheduler::new(0);
+        ticks.step();
+        ticks.schedule(ivec3(4, 5, 6), 10, 2);
+
+        let mut restored = TickScheduler::from_saved(0, ticks.tick(), &ticks.pending());
+        assert_eq!(restored.pending(), ticks.pending());
+        for _ in 0..9 {
+            assert!(restored.step().is_empty());
+        }
+        assert_eq!(restored.step(), vec![ivec3(4, 5, 6)]);
+    }
+
+    #[test]
+    fn saved_tick_changes_the_random_sequence() {
+        let sequence =
+            |mut ticks: TickScheduler| -> Vec<u32> { (0..8).map(|_| ticks.random()).collect() };
+        let fresh = sequence(TickScheduler::new(7));
+        let early = sequence(TickScheduler::from_saved(7, 100, &[]));
+        let late = sequence(TickScheduler::from_saved(7, 200, &[]));
+        assert_ne!(early, fresh);
+        assert_ne!(early, late);
+        assert_eq!(early, sequence(TickScheduler::from_saved(7, 100, &[])));
+        assert_eq!(fresh, sequence(TickScheduler::from_saved(7, 0, &[])));
+    }
+
+    #[test]
+    fn random_ticks_skip_empty_chunks() {
+        let mut terrain = Terrain::new(0);
+        terrain.insert_chunk(ivec3(0, 0, 0), Chunk::new());
+        terrain.insert_chunk(ivec3(1, 0, 0), Chunk::new());
+        terrain.set_block(ivec3(20, 3, 4), Block::Stone);
+
+        let mut ticks = TickScheduler::new(7);
+        let positions = ticks.random_ticks(&terrain, 3);
+        assert_eq!(positions.len(), 3);
+        assert!(positions
+            .iter()
+            .all(|p| (16..32).contains(&p.x) && (0..16).contains(&p.y)));
+
+        // Chunks drop out once they're emptied or unloaded, and come back when filled again.
+        terrain.set_block(ivec3(20, 3, 4), Block::Air);
+        assert!(ticks.random_ticks(&terrain, 3).is_empty());
+        terrain.set_block(ivec3(2, 3, 4), Block::Dirt);
+        assert_eq!(ticks.random_ticks(&terrain, 3).len(), 3);
+        terrain.remove_chunk(ivec3(0, 0, 0));
+        assert!(ticks.random_ticks(&terrain, 3).is_empty());
+    }
+
+    #[test]
+    fn grass_spreads_and_dies_under_blocks() {
+        let mut terrain = Terrain::new(0);
+        terrain.insert_chunk(ivec3(0, 0, 0), Chunk::new());
+        terrain.set_block(ivec3(4, 4, 4), Block::Grass);
+        terrain.set_block(ivec3(5, 4, 4), Block::Dirt);
+
+        // A roll of 14 picks the block at +X, 13 the grass itself.
+        assert_eq!(
+            random_tick(&terrain, ivec3(4, 4, 4), 14),
+            Some((ivec3(5, 4, 4), Block::Grass))
+        );
+        assert_eq!(random_tick(&terrain, ivec3(4, 4, 4), 13), None);
+
+        terrain.set_block(ivec3(4, 5, 4), Block::Stone);
+        assert_eq!(
+            random_tick(&terrain, ivec3(4, 4, 4), 14),
+            Some((ivec3(4, 4, 4), Block::Dirt))
+        );
+    }
+}
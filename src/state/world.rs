@@ -1,133 +1,908 @@
-use std::f32::consts::PI;
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
 
-use glam::{vec2, vec3, vec4, Mat4, Vec3};
-use glow::Context;
-use hecs::World;
-use sdl2::{event::Event, keyboard::Scancode};
+use glam::{ivec3, vec3, IVec3, UVec3, Vec3};
+use hecs::{Entity, World};
+use sdl2::{event::Event, keyboard::Scancode, mouse::MouseButton};
 
 use crate::render::{
-    self,
+    atmosphere::SkyMode,
     camera::Camera,
-    mesh::{Mesh, Quad, Vertex},
+    cluster::ClusterLight,
+    mesh::Mesh,
+    mesher::{mesh_region, MesherOptions},
     model::Model,
-    renderer::Renderer,
-    shadow::CastShadow,
-    texture::{CubeMap, GameTexture, Skybox},
+    renderer::{FogQuality, Renderer, SkyState, SunLight},
+    shadow::{CastShadow, SHADOW_CASCADES},
+    texture::{GameTexture, Skybox},
+};
+
+use super::{
+    clock::WorldClock,
+    ecs::{
+        asset::{MeshAsset, RenderAsset},
+        chunk::ChunkMesh,
+        collider::Collider,
+        falling_block::FallingBlock,
+        interpolation::Interpolation,
+        item::ItemDrop,
+        light::{DirectionalLight, PointLight, SpotLight},
+        persistent::Persistent,
+        player::{Player, EYE_HEIGHT, PLAYER_SIZE, REACH},
+        rigid_body::RigidBody,
+        transform::Transform,
+    },
+    input::InputState,
+    physics::{
+        aabb::Aabb,
+        falling::{is_unsupported, step_falling_blocks, Landing},
+        movement::update_player,
+        raycast::{raycast, RayHit},
+        rigid_body::{step_rigid_bodies, wake_rigid_bodies},
+    },
+    save::{backup::BackupStore, entity::ComponentRegistry, metadata::WorldMetadata, WorldSave},
+    voxel::{
+        block::Block,
+        chunk::{affected_chunks, chunk_origin, chunk_pos, Chunk, CHUNK_SIZE},
+        fluid::{schedule_fluid_updates, update_fluids},
+        light::{LightEngine, MAX_LIGHT},
+        terrain::Terrain,
+        tick::{random_tick, TickScheduler, RANDOM_TICKS_PER_CHUNK},
+    },
+};
+
+pub const WORLD_SEED: u32 = 1337;
+pub const WORLD_DIR: &str = "saves/world";
+
+/// Seconds between automatic saves.
+const AUTOSAVE_INTERVAL: f32 = 60.;
+/// Seconds between automatic backups, and how many automatic and manual snapshots are kept.
+const BACKUP_INTERVAL: f32 = 600.;
+const BACKUP_RETENTION: usize = 24;
+
+/// Number of chunks generated around the origin in each horizontal direction.
+const WORLD_RADIUS: i32 = 4;
+const WORLD_MIN_CHUNK_Y: i32 = -2;
+const WORLD_MAX_CHUNK_Y: i32 = 1;
+
+/// Seconds it takes to mine a block per point of hardness.
+const MINING_TIME_PER_HARDNESS: f32 = 1.5;
+/// Number of crack textures shown while mining.
+const CRACK_STAGES: usize = 10;
+
+/// Physics steps between a block losing its support and starting to fall.
+const FALL_DELAY: u64 = 2;
+
+/// Size of the cube dropped items are drawn as.
+const ITEM_SIZE: f32 = 0.25;
+/// Upward velocity items are dropped with, in blocks per second.
+const ITEM_POP_VELOCITY: f32 = 4.;
+const ITEM_FRICTION: f32 = 0.6;
+const ITEM_RESTITUTION: f32 = 0.4;
+
+const PROP_FRICTION: f32 = 0.8;
+const PROP_RESTITUTION: f32 = 0.1;
+
+/// Brightness of the point lights on light emitting blocks. They have the colour the blocks
+/// emit and reach as far as their brightest colour.
+///
+/// Only blocks that are point lights get one, and those are left out of the voxel block light so
+/// their light isn't counted twice. Glowstone and redstone lamps are placed one at a time and
+/// are shaded per pixel this way. Lava comes in pools too big for a light per block, so it
+/// only lights its surroundings through the voxel block light, which also lets stained glass
+/// colour it.
+const EMITTER_LIGHT_INTENSITY: f32 = 0.8;
+
+/// The torch the player can hold up with T.
+const TORCH_LIGHT: PointLight = PointLight {
+    color: vec3(1., 0.75, 0.45),
+    intensity: 2.,
+    range: 10.,
+    cast_shadows: false,
 };
 
-use super::{ecs::transform::Transform, input::InputState};
+/// The flashlight the player can turn on with F.
+const FLASHLIGHT: SpotLight = SpotLight {
+    color: vec3(1., 0.95, 0.85),
+    intensity: 4.,
+    range: 24.,
+    inner_angle: 12. * PI / 180.,
+    outer_angle: 20. * PI / 180.,
+    cast_shadows: false,
+};
+
+/// Progress on mining a block, from 0 to 1.
+#[derive(Clone, Copy)]
+struct Mining {
+    pos: IVec3,
+    progress: f32,
+}
 
 pub struct GameWorld {
     camera: Camera,
     input: InputState,
-    light_dir: Vec3,
     world: World,
+    player: Entity,
+    /// Entity carrying the light of the sun or the moon, whichever is up.
+    sun: Entity,
+    clock: WorldClock,
+    /// Entity carrying the flashlight, following the camera while it's on.
+    flashlight: Option<Entity>,
+    /// The block the player is looking at.
+    target: Option<RayHit>,
+    mining: Option<Mining>,
+    /// Block placed with right click.
+    selected: Block,
+    /// Chunks whose meshes are rebuilt before the next frame.
+    remesh: HashSet<IVec3>,
+    /// Point light blocks with an exposed face in each meshed chunk.
+    emitters: HashMap<IVec3, Vec<IVec3>>,
+    light: LightEngine,
+    /// World clock with the scheduled block updates.
+    ticks: TickScheduler,
     skybox: Skybox,
-    light_angle: f32,
+    /// Switched with K.
+    sky_mode: SkyMode,
+    /// Switched with G.
+    fog_quality: FogQuality,
+    /// Screen-space ambient occlusion, switched with O.
+    ambient_occlusion: bool,
+    terrain: Terrain,
+    textures: HashMap<String, GameTexture>,
+    /// Models shared by every entity with the same mesh asset.
+    models: Vec<(MeshAsset, Model)>,
+    save: Option<WorldSave>,
+    autosave_timer: f32,
+    backup_timer: f32,
+    components: ComponentRegistry,
+    /// Chunks that had persistent entities in them when the world was last loaded or saved.
+    entity_chunks: HashSet<IVec3>,
 }
 
 impl GameWorld {
     pub fn new(renderer: &Renderer) -> Self {
-        let camera = Camera::new(vec3(0., 1., 0.));
+        let save = WorldSave::open(WORLD_DIR)
+            .map_err(|e| eprintln!("Couldn't open the world save at {}: {}", WORLD_DIR, e))
+            .ok();
+        let metadata = save.as_ref().and_then(|s| s.load_metadata());
 
-        let mut world = World::new();
+        let mut terrain = Terrain::new(metadata.as_ref().map_or(WORLD_SEED, |m| m.seed));
+        let area_min = ivec3(-WORLD_RADIUS, WORLD_MIN_CHUNK_Y, -WORLD_RADIUS);
+        let area_max = ivec3(WORLD_RADIUS - 1, WORLD_MAX_CHUNK_Y, WORLD_RADIUS - 1);
+        let saved_entities = match &save {
+            Some(save) => save.load_area(&mut terrain, area_min, area_max),
+            None => Vec::new(),
+        };
+        terrain.generate_area(area_min, area_max);
+
+        let mut light = LightEngine::new();
+        light.light_all(&mut terrain);
+
+        let camera = match &metadata {
+            Some(metadata) => {
+                let mut camera = Camera::new(metadata.player_pos);
+                camera.set_rotation(metadata.yaw, metadata.pitch);
+                camera
+            }
+            None => {
+                let spawn_height = terrain.generator().height(0, 0) as f32 + 1.;
+                Camera::new(vec3(0.5, spawn_height + EYE_HEIGHT, 0.5))
+            }
+        };
 
-        let mut mesh = Mesh::new();
-        mesh.push_quad(Quad::new(
-            Vertex::new(vec3(-5., 0., -5.), vec3(0., 1., 0.), vec2(0., 0.)),
-            Vertex::new(vec3(-5., 0., 5.), vec3(0., 1., 0.), vec2(5., 0.)),
-            Vertex::new(vec3(5., 0., 5.), vec3(0., 1., 0.), vec2(5., 5.)),
-            Vertex::new(vec3(5., 0., -5.), vec3(0., 1., 0.), vec2(0., 5.)),
+        let skybox = renderer.create_skybox();
+
+        let mut world = World::new();
+        let player_transform = Transform::from_translation(camera.pos() - Player::eye_offset());
+        let player = world.spawn((
+            player_transform,
+            Interpolation::new(player_transform),
+            Collider::new(PLAYER_SIZE),
+            Player::default(),
         ));
+        let sun = world.spawn((
+            Transform::new(),
+            DirectionalLight {
+                color: Vec3::ONE,
+                intensity: 1.,
+                cast_shadows: true,
+            },
+        ));
+
+        let mut game_world = Self {
+            camera,
+            input: Default::default(),
+            world,
+            player,
+            sun,
+            clock: metadata
+                .as_ref()
+                .map_or_else(WorldClock::default, |m| m.clock),
+            flashlight: None,
+            target: None,
+            mining: None,
+            selected: Block::Stone,
+            remesh: HashSet::new(),
+            emitters: HashMap::new(),
+            light,
+            ticks: match &metadata {
+                Some(metadata) => TickScheduler::from_saved(
+                    metadata.seed,
+                    metadata.tick,
+                    &metadata.scheduled_ticks,
+                ),
+                None => TickScheduler::new(WORLD_SEED),
+            },
+            skybox,
+            sky_mode: SkyMode::default(),
+            fog_quality: FogQuality::default(),
+            ambient_occlusion: true,
+            terrain,
+            textures: HashMap::new(),
+            models: Vec::new(),
+            save,
+            autosave_timer: 0.,
+            backup_timer: 0.,
+            components: ComponentRegistry::with_defaults(),
+            entity_chunks: HashSet::new(),
+        };
+
+        game_world.update_sun();
+
+        let chunks: Vec<IVec3> = game_world.terrain.chunks().map(|(pos, _)| *pos).collect();
+        for pos in chunks {
+            game_world.spawn_chunk_meshes(renderer, pos);
+        }
+
+        for saved in &saved_entities {
+            if let Err(e) = game_world.components.spawn(&mut game_world.world, saved) {
+                eprintln!("Warning: couldn't load a saved entity: {}", e);
+            }
+        }
+        // Interpolation isn't saved, bodies that move get it back after loading.
+        let bodies: Vec<_> = game_world
+            .world
+            .query::<&Transform>()
+            .with::<&RigidBody>()
+            .without::<&Interpolation>()
+            .iter()
+            .map(|(entity, transform)| (entity, *transform))
+            .collect();
+        for (entity, transform) in bodies {
+            game_world
+                .world
+                .insert_one(entity, Interpolation::new(transform))
+                .unwrap();
+        }
+        game_world.entity_chunks = game_world
+            .components
+            .save_world(&game_world.world)
+            .into_keys()
+            .collect();
+
+        if metadata.is_none() {
+            game_world.spawn_props();
+        }
+
+        game_world.build_render_components(renderer);
+
+        game_world
+    }
+
+    /// Places a few wooden crates around the spawn point of a new world.
+    fn spawn_props(&mut self) {
+        let generator = *self.terrain.generator();
+        for (x, z, size) in [(0, 3, 1.), (2, 4, 0.5), (-3, 2, 1.5)] {
+            let ground = generator.height(x, z) as f32 + 1.;
+            let center = vec3(x as f32 + 0.5, ground + size / 2., z as f32 + 0.5);
+
+            let transform = Transform::from_translation(center).scale(size);
+            self.world.spawn((
+                transform,
+                Interpolation::new(transform),
+                Collider::new(Vec3::splat(size)),
+                RigidBody::new(PROP_FRICTION, PROP_RESTITUTION),
+                RenderAsset::new(MeshAsset::Cube(1.), "assets/wood.png"),
+                CastShadow,
+                Persistent,
+            ));
+        }
+    }
+
+    /// Creates the model and texture of every entity that has a `RenderAsset` but no model yet,
+    /// like entities that were just loaded from the save.
+    fn build_render_components(&mut self, renderer: &Renderer) {
+        let pending: Vec<_> = self
+            .world
+            .query::<&RenderAsset>()
+            .without::<&Model>()
+            .iter()
+            .map(|(entity, asset)| (entity, asset.clone()))
+            .collect();
+
+        for (entity, asset) in pending {
+            let model = self.model(renderer, asset.mesh);
+            let texture = self.texture(renderer, &asset.texture);
+
+            self.world.insert(entity, (model, texture)).unwrap();
+        }
+    }
+
+    fn model(&mut self, renderer: &Renderer, asset: MeshAsset) -> Model {
+        if let Some((_, model)) = self.models.iter().find(|(a, _)| *a == asset) {
+            return *model;
+        }
+
+        let mesh = match asset {
+            MeshAsset::Cube(size) => Mesh::from_cube(size),
+        };
         let model = renderer.create_model(&mesh);
+        self.models.push((asset, model));
+        model
+    }
 
-        let texture = renderer.create_texture("assets/wood.png");
+    fn texture(&mut self, renderer: &Renderer, path: &str) -> GameTexture {
+        *self
+            .textures
+            .entry(path.to_string())
+            .or_insert_with(|| renderer.create_texture(path))
+    }
 
-        let floor = world.spawn((model, Transform::new(), texture, CastShadow));
+    pub fn save(&mut self) {
+        let save = match &self.save {
+            Some(save) => save,
+            None => return,
+        };
 
-        let cube_mesh = Mesh::from_cube(1.0);
-        let cube_model = renderer.create_model(&cube_mesh);
-        let cube = world.spawn((
-            cube_model,
-            Transform::from_translation(vec3(0., 0.5, 0.)),
-            texture,
-            CastShadow,
-        ));
+        let metadata = WorldMetadata {
+            seed: self.terrain.generator().seed(),
+            player_pos: self.camera.pos(),
+            yaw: self.camera.yaw(),
+            pitch: self.camera.pitch(),
+            tick: self.ticks.tick(),
+            scheduled_ticks: self.ticks.pending(),
+            clock: self.clock,
+        };
 
-        let cube_model = renderer.create_model(&cube_mesh);
-        let cube = world.spawn((
-            cube_model,
-            Transform::from_translation(vec3(1.5, 0.25, 3.)).scale(0.5),
-            texture,
-            CastShadow,
-        ));
+        let entities = self.components.save_world(&self.world);
+        for pos in self.entity_chunks.iter().chain(entities.keys()) {
+            self.terrain.mark_dirty(*pos);
+        }
+        self.entity_chunks = entities.keys().copied().collect();
+
+        if let Err(e) = save.save_terrain(&mut self.terrain, &entities) {
+            eprintln!("Couldn't save the terrain: {}", e);
+        }
+        if let Err(e) = save.save_metadata(&metadata) {
+            eprintln!("Couldn't save the world metadata: {}", e);
+        }
+    }
+
+    /// Saves the world and takes a snapshot of it, dropping the oldest snapshots past the
+    /// retention limit.
+    pub fn backup(&mut self) {
+        self.save();
+
+        let world_dir = match &self.save {
+            Some(save) => save.dir(),
+            None => return,
+        };
+
+        let store = BackupStore::for_world(world_dir);
+        match store.create(world_dir) {
+            Ok(stats) => println!(
+                "Created backup {} ({} files copied, {} unchanged).",
+                stats.snapshot.id, stats.copied, stats.linked
+            ),
+            Err(e) => eprintln!("Couldn't back up the world: {}", e),
+        }
+        if let Err(e) = store.prune(BACKUP_RETENTION) {
+            eprintln!("Warning: couldn't remove old backups: {}", e);
+        }
+    }
+
+    fn spawn_chunk_meshes(&mut self, renderer: &Renderer, pos: IVec3) {
+        self.emitters.remove(&pos);
+        let chunk = match self.terrain.chunk(pos) {
+            Some(chunk) if !chunk.is_empty() => chunk,
+            _ => return,
+        };
+
+        let origin = chunk_origin(pos);
+        let emitters: Vec<IVec3> = chunk
+            .blocks()
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| block.is_point_light())
+            .map(|(index, _)| origin + Chunk::local_from_index(index))
+            .filter(|&pos| {
+                let block = self.terrain.get_block(pos);
+                [
+                    IVec3::X,
+                    IVec3::NEG_X,
+                    IVec3::Y,
+                    IVec3::NEG_Y,
+                    IVec3::Z,
+                    IVec3::NEG_Z,
+                ]
+                .iter()
+                .map(|&offset| self.terrain.get_block(pos + offset))
+                .any(|neighbour| !neighbour.is_opaque() && neighbour != block)
+            })
+            .collect();
+        if !emitters.is_empty() {
+            self.emitters.insert(pos, emitters);
+        }
+
+        let options = MesherOptions {
+            merge_faces: true,
+            ..Default::default()
+        };
+
+        for (block, mesh) in mesh_region(&self.terrain, origin, origin + CHUNK_SIZE, options) {
+            let texture = self.texture(
+                renderer,
+                block.texture_path().expect("Block has no texture."),
+            );
+            let model = renderer.create_model(&mesh);
 
-        let cube_model = renderer.create_model(&cube_mesh);
-        let cube = world.spawn((
-            cube_model,
-            Transform::from_translation(vec3(-3.5, 0.75, 2.)).scale(1.5),
-            texture,
+            self.world.spawn((
+                model,
+                Transform::from_translation(origin.as_vec3()),
+                texture,
+                CastShadow,
+                ChunkMesh(pos),
+            ));
+        }
+    }
+
+    /// Changes a block in a loaded chunk, remeshes it and wakes up fluids around it. Returns
+    /// whether the block was changed.
+    fn set_block(&mut self, pos: IVec3, block: Block) -> bool {
+        if !self.terrain.is_loaded(chunk_pos(pos)) {
+            return false;
+        }
+
+        self.terrain.set_block(pos, block);
+        self.light.relight(&mut self.terrain, pos);
+        schedule_fluid_updates(&self.terrain, &mut self.ticks, pos);
+        self.queue_remesh(pos);
+        self.schedule_support_checks(pos);
+        wake_rigid_bodies(&mut self.world, pos);
+
+        true
+    }
+
+    /// Schedules ticks for the gravity-affected blocks that a change at `pos` could leave
+    /// without support.
+    fn schedule_support_checks(&mut self, pos: IVec3) {
+        for pos in [pos, pos + IVec3::Y] {
+            if self.terrain.get_block(pos).has_gravity() {
+                self.ticks.schedule(pos, FALL_DELAY, 0);
+            }
+        }
+    }
+
+    /// Runs the scheduled block ticks that are due and this step's random ticks. A due tick
+    /// updates the fluid at the block and checks whether it lost its support.
+    fn run_block_ticks(&mut self) {
+        let due = self.ticks.step();
+
+        for pos in update_fluids(&mut self.terrain, &mut self.ticks, &due) {
+            self.light.relight(&mut self.terrain, pos);
+            self.queue_remesh(pos);
+            self.schedule_support_checks(pos);
+        }
+
+        for pos in due {
+            if is_unsupported(&self.terrain, pos) {
+                self.start_falling(pos);
+            }
+        }
+
+        for pos in self
+            .ticks
+            .random_ticks(&self.terrain, RANDOM_TICKS_PER_CHUNK)
+        {
+            let roll = self.ticks.random();
+            if let Some((pos, block)) = random_tick(&self.terrain, pos, roll) {
+                self.set_block(pos, block);
+            }
+        }
+    }
+
+    /// Replaces the block at `pos` with a falling block entity.
+    fn start_falling(&mut self, pos: IVec3) {
+        let block = self.terrain.get_block(pos);
+        self.set_block(pos, Block::Air);
+
+        let transform = Transform::from_translation(pos.as_vec3() + 0.5);
+        self.world.spawn((
+            transform,
+            Interpolation::new(transform),
+            FallingBlock::new(block),
+            RenderAsset::new(MeshAsset::Cube(1.), block.texture_path().unwrap()),
             CastShadow,
+            Persistent,
         ));
+    }
 
-        let skybox = renderer.create_skybox();
+    /// Lands the falling blocks that hit the ground.
+    fn update_falling_blocks(&mut self) {
+        for (entity, landing) in step_falling_blocks(&mut self.world, &self.terrain) {
+            self.world.despawn(entity).unwrap();
 
-        Self {
-            camera,
-            input: Default::default(),
-            light_dir: vec3(0.5, -1., -0.8).normalize(),
-            world,
-            skybox,
-            light_angle: 0.0f32,
+            match landing {
+                Landing::Place(pos, block) => {
+                    self.set_block(pos, block);
+                }
+                Landing::Drop(center, block) => {
+                    let transform = Transform::from_translation(center);
+                    let body = RigidBody::new(ITEM_FRICTION, ITEM_RESTITUTION)
+                        .with_velocity(Vec3::Y * ITEM_POP_VELOCITY);
+                    self.world.spawn((
+                        transform,
+                        Interpolation::new(transform),
+                        Collider::new(Vec3::splat(ITEM_SIZE)),
+                        body,
+                        ItemDrop { block },
+                        RenderAsset::new(MeshAsset::Cube(ITEM_SIZE), block.texture_path().unwrap()),
+                        CastShadow,
+                        Persistent,
+                    ));
+                }
+                Landing::Lost => {}
+            }
+        }
+    }
+
+    /// Queues the loaded chunks whose meshes show the block at `pos`.
+    fn queue_remesh(&mut self, pos: IVec3) {
+        for chunk in affected_chunks(pos) {
+            if self.terrain.is_loaded(chunk) {
+                self.remesh.insert(chunk);
+            }
+        }
+    }
+
+    /// Replaces the meshes of the chunks queued for remeshing.
+    fn remesh_chunks(&mut self, renderer: &Renderer) {
+        for pos in self.light.take_changed() {
+            self.queue_remesh(pos);
+        }
+        if self.remesh.is_empty() {
+            return;
+        }
+
+        let chunks = std::mem::take(&mut self.remesh);
+        let stale: Vec<_> = self
+            .world
+            .query::<(&ChunkMesh, &Model)>()
+            .iter()
+            .filter(|(_, (chunk, _))| chunks.contains(&chunk.0))
+            .map(|(entity, (_, model))| (entity, *model))
+            .collect();
+
+        for (entity, model) in stale {
+            renderer.delete_model(&model);
+            self.world.despawn(entity).unwrap();
+        }
+        for pos in chunks {
+            self.spawn_chunk_meshes(renderer, pos);
+        }
+    }
+
+    /// Advances mining of the targeted block while the mine button is held, breaking it once
+    /// done. Looking at another block starts over.
+    fn update_mining(&mut self, delta: f32) {
+        let target = match self.target {
+            Some(target) if self.input.mine => target,
+            _ => {
+                self.mining = None;
+                return;
+            }
+        };
+
+        let mut mining = match self.mining {
+            Some(mining) if mining.pos == target.pos => mining,
+            _ => Mining {
+                pos: target.pos,
+                progress: 0.,
+            },
+        };
+
+        let time = self.terrain.get_block(target.pos).hardness() * MINING_TIME_PER_HARDNESS;
+        mining.progress += if time > 0. { delta / time } else { 1. };
+
+        if mining.progress >= 1. {
+            self.set_block(target.pos, Block::Air);
+            self.target = None;
+            self.mining = None;
+        } else {
+            self.mining = Some(mining);
+        }
+    }
+
+    /// Places the selected block against the targeted face, unless it would end up inside the
+    /// player.
+    fn place_block(&mut self) {
+        let target = match self.target {
+            Some(target) if target.normal != IVec3::ZERO => target,
+            _ => return,
+        };
+
+        let pos = target.pos + target.normal;
+        if !self.terrain.get_block(pos).is_replaceable() {
+            return;
         }
+
+        let mut query = self
+            .world
+            .query_one::<(&Transform, &Collider)>(self.player)
+            .unwrap();
+        let (transform, collider) = query.get().unwrap();
+        let player = collider.aabb(transform.translation());
+        drop(query);
+
+        if !Aabb::block(pos).intersects(&player) {
+            self.set_block(pos, self.selected);
+        }
+    }
+
+    fn select_block(&mut self, offset: i32) {
+        // Air isn't placeable, so selection cycles through the rest.
+        let placeable = &Block::ALL[1..];
+        let index = placeable
+            .iter()
+            .position(|b| *b == self.selected)
+            .unwrap_or(0);
+        let index = (index as i32 + offset).rem_euclid(placeable.len() as i32);
+        self.selected = placeable[index as usize];
     }
 
-    pub fn physics_update(&mut self) {}
+    /// Runs a fixed step of player movement and the rest of the simulation. The camera is put
+    /// at the player's eyes when drawing, between the last two steps.
+    pub fn physics_update(&mut self) {
+        for (_entity, (transform, interpolation)) in
+            self.world.query_mut::<(&Transform, &mut Interpolation)>()
+        {
+            interpolation.previous = *transform;
+        }
+
+        let mut query = self
+            .world
+            .query_one::<(&mut Transform, &Collider, &mut Player)>(self.player)
+            .unwrap();
+        let (transform, collider, player) = query.get().unwrap();
+
+        let center = transform.translation();
+        let offset = update_player(
+            &self.terrain,
+            player,
+            collider.aabb(center),
+            &self.input,
+            self.camera.yaw(),
+        );
+
+        *transform = Transform::from_translation(center + offset);
+        drop(query);
+        self.input.consume_toggles();
+
+        self.run_block_ticks();
+        self.update_falling_blocks();
+        step_rigid_bodies(&mut self.world, &self.terrain);
+    }
 
     pub fn update(&mut self, delta: f32) {
-        self.camera.update_movement(&self.input, delta);
+        self.update_mining(delta);
 
-        let rotmat = Mat4::from_rotation_z(self.light_angle);
-        self.light_angle = PI * delta / 20.0;
+        self.autosave_timer += delta;
+        if self.autosave_timer >= AUTOSAVE_INTERVAL {
+            self.autosave_timer = 0.;
+            self.save();
+        }
 
-        let light_dir = rotmat * vec4(self.light_dir.x, self.light_dir.y, self.light_dir.z, 0.);
+        self.backup_timer += delta;
+        if self.backup_timer >= BACKUP_INTERVAL {
+            self.backup_timer = 0.;
+            self.autosave_timer = 0.;
+            self.backup();
+        }
 
-        self.light_dir = vec3(light_dir.x, light_dir.y, light_dir.z).normalize();
+        self.clock.advance(delta);
+        self.update_sun();
     }
 
-    pub fn draw(&mut self, renderer: &mut Renderer) {
+    /// Points the sun entity's light along the sunlight or moonlight at the current time of day.
+    fn update_sun(&mut self) {
+        let daylight = self.clock.daylight();
+        let (transform, light) = self
+            .world
+            .query_one_mut::<(&mut Transform, &mut DirectionalLight)>(self.sun)
+            .unwrap();
+        *transform = Transform::from_direction(daylight.light_direction);
+        light.color = daylight.light_color;
+        light.intensity = daylight.light_intensity;
+    }
+
+    /// Draws the world `alpha` of the way between the last two physics steps.
+    pub fn draw(&mut self, renderer: &mut Renderer, alpha: f32) {
+        self.remesh_chunks(renderer);
+        self.build_render_components(renderer);
+
+        let player = self.interpolated_transform(self.player, alpha);
+        self.camera
+            .set_pos(player.translation() + Player::eye_offset());
+        self.target = raycast(&self.terrain, self.camera.pos(), self.camera.front(), REACH);
+        if let Some(flashlight) = self.flashlight {
+            let transform =
+                Transform::from_direction(self.camera.front()).translate(self.camera.pos());
+            *self.world.get::<&mut Transform>(flashlight).unwrap() = transform;
+        }
+        let (sun, lights) = self.lights(alpha);
+
         renderer.prepare(&mut self.camera);
-        for (_entity, (model, transform, texture)) in self
+        for (_entity, (model, transform, texture, interpolation)) in self
             .world
-            .query::<(&Model, &Transform, &GameTexture)>()
+            .query::<(&Model, &Transform, &GameTexture, Option<&Interpolation>)>()
             .iter()
         {
+            let transform = interpolation.map_or(*transform, |i| i.interpolate(transform, alpha));
             renderer.bind_texture(texture);
-            renderer.render(model, transform);
+            renderer.render(model, &transform);
         }
         renderer.end();
 
-        renderer.prepare_shadow_map(&self.light_dir);
-        for (_entity, (model, transform, _)) in self
+        if let Some(sun) = sun.filter(|sun| sun.cast_shadows) {
+            let mut query = self
+                .world
+                .query::<(&Model, &Transform, &CastShadow, Option<&Interpolation>)>();
+            let casters: Vec<_> = query
+                .iter()
+                .map(|(_entity, (model, transform, _, interpolation))| {
+                    let transform =
+                        interpolation.map_or(*transform, |i| i.interpolate(transform, alpha));
+                    (model, transform)
+                })
+                .collect();
+
+            renderer.prepare_shadow_map(&self.camera, &sun.direction);
+            for cascade in 0..SHADOW_CASCADES {
+                renderer.prepare_shadow_cascade(cascade);
+                for (model, transform) in &casters {
+                    renderer.render_shadow_map(model, transform);
+                }
+            }
+            renderer.end_shadow_map();
+        }
+
+        let daylight = self.clock.daylight();
+        let sky = SkyState {
+            sun_direction: daylight.sun_direction,
+            night: daylight.night,
+            stars: daylight.stars,
+            horizon_color: daylight.horizon_color,
+            ambient: daylight.ambient,
+            mode: self.sky_mode,
+        };
+        renderer.bake_sky(&self.skybox, &sky);
+        renderer.render_shading(
+            &self.camera,
+            sun.as_ref(),
+            &sky,
+            &lights,
+            self.ambient_occlusion,
+        );
+
+        if let Some(target) = self.target {
+            renderer.render_block_outline(&self.camera, target.pos);
+        }
+        if let Some(mining) = self.mining {
+            let stage = ((mining.progress * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1);
+            let texture = self.texture(
+                renderer,
+                &format!("assets/blocks/destroy/stage_{}.png", stage),
+            );
+            renderer.render_block_overlay(&self.camera, mining.pos, &texture);
+        }
+
+        renderer.render_skybox(&self.skybox, &self.camera, &sky);
+        renderer.render_fog(&self.camera, sun.as_ref(), &sky, self.fog_quality);
+    }
+
+    /// The sun and the point and spot lights to shade the frame with: the light components in
+    /// the world and the light emitting blocks.
+    fn lights(&self, alpha: f32) -> (Option<SunLight>, Vec<ClusterLight>) {
+        let sun = self
             .world
-            .query::<(&Model, &Transform, &CastShadow)>()
+            .query::<(&Transform, &DirectionalLight)>()
+            .iter()
+            .next()
+            .map(|(_entity, (transform, light))| SunLight {
+                direction: transform.direction(),
+                color: light.color * light.intensity,
+                cast_shadows: light.cast_shadows,
+            });
+
+        let mut lights: Vec<ClusterLight> = self
+            .emitters
+            .values()
+            .flatten()
+            .map(|&pos| {
+                let emission =
+                    UVec3::from(self.terrain.get_block(pos).light_emission().map(u32::from));
+                let radius = emission.max_element() as f32;
+                let color = emission.as_vec3() / MAX_LIGHT as f32;
+                ClusterLight::point(pos.as_vec3() + 0.5, color * EMITTER_LIGHT_INTENSITY, radius)
+            })
+            .collect();
+
+        for (_entity, (transform, light, interpolation)) in self
+            .world
+            .query::<(&Transform, &PointLight, Option<&Interpolation>)>()
             .iter()
         {
-            renderer.render_shadow_map(model, transform);
+            let transform = interpolation.map_or(*transform, |i| i.interpolate(transform, alpha));
+            lights.push(ClusterLight::point(
+                transform.translation(),
+                light.color * light.intensity,
+                light.range,
+            ));
+        }
+        for (_entity, (transform, light, interpolation)) in self
+            .world
+            .query::<(&Transform, &SpotLight, Option<&Interpolation>)>()
+            .iter()
+        {
+            let transform = interpolation.map_or(*transform, |i| i.interpolate(transform, alpha));
+            lights.push(ClusterLight::spot(
+                transform.translation(),
+                transform.direction(),
+                light.color * light.intensity,
+                light.range,
+                light.inner_angle,
+                light.outer_angle,
+            ));
         }
-        renderer.end_shadow_map();
 
-        renderer.render_shading(&self.camera);
+        (sun, lights)
+    }
 
-        renderer.render_skybox(self.skybox.model(), &self.camera, self.skybox.cube_map());
+    /// Holds up or puts away the torch. It's a light on the player entity, so it moves with it.
+    fn toggle_torch(&mut self) {
+        if self.world.remove_one::<PointLight>(self.player).is_err() {
+            self.world.insert_one(self.player, TORCH_LIGHT).unwrap();
+        }
+    }
+
+    fn toggle_flashlight(&mut self) {
+        match self.flashlight.take() {
+            Some(flashlight) => self.world.despawn(flashlight).unwrap(),
+            None => {
+                self.flashlight = Some(self.world.spawn((Transform::new(), FLASHLIGHT)));
+            }
+        }
+    }
+
+    fn interpolated_transform(&self, entity: Entity, alpha: f32) -> Transform {
+        let mut query = self
+            .world
+            .query_one::<(&Transform, Option<&Interpolation>)>(entity)
+            .unwrap();
+        let (transform, interpolation) = query.get().unwrap();
+        interpolation.map_or(*transform, |i| i.interpolate(transform, alpha))
     }
 
     pub fn handle_input(&mut self, event: Event) {
         match event {
             Event::KeyDown {
                 scancode: Some(Scancode::W),
+                repeat: false,
+                timestamp,
                 ..
-            } => self.input.forward = true,
+            } => self.input.press_forward(timestamp),
             Event::KeyDown {
                 scancode: Some(Scancode::S),
                 ..
@@ -143,7 +918,7 @@ impl GameWorld {
             Event::KeyUp {
                 scancode: Some(Scancode::W),
                 ..
-            } => self.input.forward = false,
+            } => self.input.release_forward(),
             Event::KeyUp {
                 scancode: Some(Scancode::S),
                 ..
@@ -163,8 +938,81 @@ impl GameWorld {
             }
             Event::KeyDown {
                 scancode: Some(Scancode::Space),
+                repeat: false,
+                timestamp,
+                ..
+            } => self.input.press_jump(timestamp),
+            Event::KeyUp {
+                scancode: Some(Scancode::Space),
+                ..
+            } => self.input.jump = false,
+            Event::KeyDown {
+                scancode: Some(Scancode::LShift),
+                ..
+            } => self.input.crouch = true,
+            Event::KeyUp {
+                scancode: Some(Scancode::LShift),
+                ..
+            } => self.input.crouch = false,
+            Event::KeyDown {
+                scancode: Some(Scancode::LCtrl),
+                ..
+            } => self.input.sprint = true,
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => self.input.mine = true,
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => self.input.mine = false,
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Right,
+                ..
+            } => self.place_block(),
+            Event::MouseWheel { y, .. } if y != 0 => self.select_block(-y.signum()),
+            // SDL orders the number keys from 1 to 9 and then 0, like on the keyboard.
+            Event::KeyDown {
+                scancode: Some(scancode),
+                ..
+            } if (Scancode::Num1 as i32..=Scancode::Num0 as i32).contains(&(scancode as i32)) => {
+                self.selected = Block::ALL[1 + (scancode as i32 - Scancode::Num1 as i32) as usize];
+            }
+            Event::KeyDown {
+                scancode: Some(Scancode::N),
+                repeat: false,
+                ..
+            } => self.input.toggle_spectator = true,
+            Event::KeyDown {
+                scancode: Some(Scancode::T),
+                repeat: false,
+                ..
+            } => self.toggle_torch(),
+            Event::KeyDown {
+                scancode: Some(Scancode::F),
+                repeat: false,
+                ..
+            } => self.toggle_flashlight(),
+            Event::KeyDown {
+                scancode: Some(Scancode::K),
+                repeat: false,
+                ..
+            } => self.sky_mode = self.sky_mode.next(),
+            Event::KeyDown {
+                scancode: Some(Scancode::G),
+                repeat: false,
+                ..
+            } => self.fog_quality = self.fog_quality.next(),
+            Event::KeyDown {
+                scancode: Some(Scancode::O),
+                repeat: false,
+                ..
+            } => self.ambient_occlusion = !self.ambient_occlusion,
+            Event::KeyDown {
+                scancode: Some(Scancode::F5),
+                repeat: false,
                 ..
-            } => self.input.space_toggle = !self.input.space_toggle,
+            } => self.backup(),
             _ => {}
         }
     }